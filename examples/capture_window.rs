@@ -17,30 +17,9 @@ fn main() {
 
     println!("Fetching windows...");
 
-    match Window::all() {
-        Ok(windows) => {
-            // Filter for windows that are more likely to be capturable
-            // (have a title or app name, are on screen, and are not too small)
-            let capturable_windows: Vec<_> = windows
-                .into_iter()
-                .filter(|w| {
-                    let title = w.title().unwrap_or_default();
-                    let app = w.app_name().unwrap_or_default();
-                    let on_screen = w.is_on_screen();
-                    let width = w.width().unwrap_or(0);
-                    let height = w.height().unwrap_or(0);
-
-                    // Filter criteria:
-                    // - Has either a title or app name
-                    // - Is on screen (not minimized)
-                    // - Has reasonable dimensions
-                    (!title.is_empty() || !app.is_empty())
-                        && on_screen
-                        && width >= 100
-                        && height >= 100
-                })
-                .collect();
-
+    // Only windows that are on screen, reasonably sized, at the normal layer and named
+    match Window::all_capturable() {
+        Ok(capturable_windows) => {
             let windows_to_capture: Vec<_> = if let Some(filter) = filter {
                 capturable_windows
                     .into_iter()
@@ -0,0 +1,209 @@
+//! Window state and elements from the Accessibility API
+//!
+//! CGWindowList and ScreenCaptureKit only say whether a window is on screen.
+//! Whether an off-screen window is minimized to the Dock is only exposed by the
+//! window's `AXUIElement`, which requires the Accessibility permission.
+
+use std::ffi::{c_void, CString};
+
+use crate::ffi::{
+    from_cf_string, CFArrayGetCount, CFArrayGetValueAtIndex, CFBooleanGetValue, CFRelease, CFRetain,
+    CFStringCreateWithCString, CFTypeRef, CF_STRING_ENCODING_UTF8,
+};
+
+/// `kAXErrorSuccess`
+const AX_SUCCESS: i32 = 0;
+/// `kAXValueCGPointType`
+const AX_VALUE_CG_POINT: u32 = 1;
+/// `kAXValueCGSizeType`
+const AX_VALUE_CG_SIZE: u32 = 2;
+
+#[repr(C)]
+#[derive(Default)]
+struct CgPair {
+    a: f64,
+    b: f64,
+}
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+    fn AXUIElementCreateApplication(pid: i32) -> CFTypeRef;
+    fn AXUIElementCopyAttributeValue(element: CFTypeRef, attribute: CFTypeRef, value: *mut CFTypeRef) -> i32;
+    fn AXUIElementGetPid(element: CFTypeRef, pid: *mut i32) -> i32;
+    fn AXValueGetValue(value: CFTypeRef, value_type: u32, out: *mut c_void) -> bool;
+    // Private but long-stable; the only way to map an AX window to its CGWindowID
+    fn _AXUIElementGetWindow(element: CFTypeRef, window_id: *mut u32) -> i32;
+}
+
+/// Check whether this process has the Accessibility permission
+pub fn is_trusted() -> bool {
+    unsafe { AXIsProcessTrusted() }
+}
+
+/// An owned CoreFoundation reference, released on drop
+struct CfOwned(CFTypeRef);
+
+impl Drop for CfOwned {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { CFRelease(self.0) };
+        }
+    }
+}
+
+/// A retained `AXUIElementRef` for a window
+///
+/// Bridges accessibility-driven tools and this crate: build one from an
+/// element obtained elsewhere with [`AxElement::from_raw`] and pass it to
+/// [`crate::Window::from_ax_element`], or get a window's element from
+/// [`crate::Window::ax_element`] and hand [`AxElement::as_raw`] to AX calls.
+pub struct AxElement(CfOwned);
+
+// SAFETY: AXUIElementRef is an immutable CoreFoundation type, and the
+// Accessibility API may be called from any thread.
+unsafe impl Send for AxElement {}
+unsafe impl Sync for AxElement {}
+
+impl AxElement {
+    /// Wrap an `AXUIElementRef`, retaining it
+    ///
+    /// # Safety
+    ///
+    /// `element` must be a valid, non-null `AXUIElementRef`.
+    pub unsafe fn from_raw(element: *const c_void) -> AxElement {
+        AxElement(CfOwned(CFRetain(element)))
+    }
+
+    /// Get the underlying `AXUIElementRef`
+    ///
+    /// The reference is only valid while this `AxElement` is alive; retain it
+    /// to keep it longer.
+    pub fn as_raw(&self) -> *const c_void {
+        self.0 .0
+    }
+}
+
+impl Clone for AxElement {
+    fn clone(&self) -> Self {
+        unsafe { AxElement::from_raw(self.as_raw()) }
+    }
+}
+
+impl std::fmt::Debug for AxElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AxElement").field(&self.as_raw()).finish()
+    }
+}
+
+/// What the Accessibility API reports about a window element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AxWindowInfo {
+    /// Owning process
+    pub pid: i32,
+    /// CGWindowID, when the private lookup succeeds
+    pub window_id: Option<u32>,
+    /// `AXTitle`
+    pub title: Option<String>,
+    /// `AXPosition` and `AXSize` as `(x, y, width, height)` in global points, top-left origin
+    pub frame: Option<(f64, f64, f64, f64)>,
+}
+
+/// Create a CFString for an attribute name
+fn cf_string(value: &str) -> CfOwned {
+    let c_str = CString::new(value).expect("attribute names contain no NUL");
+    CfOwned(unsafe { CFStringCreateWithCString(std::ptr::null(), c_str.as_ptr(), CF_STRING_ENCODING_UTF8) })
+}
+
+/// Copy an attribute of an element, or `None` if it is missing or the call fails
+fn copy_attribute(element: CFTypeRef, attribute: &str) -> Option<CfOwned> {
+    let name = cf_string(attribute);
+    let mut value: CFTypeRef = std::ptr::null();
+    let error = unsafe { AXUIElementCopyAttributeValue(element, name.0, &mut value) };
+    (error == AX_SUCCESS && !value.is_null()).then(|| CfOwned(value))
+}
+
+/// Read a point or size attribute
+fn copy_pair(element: CFTypeRef, attribute: &str, value_type: u32) -> Option<(f64, f64)> {
+    let value = copy_attribute(element, attribute)?;
+    let mut pair = CgPair::default();
+    let ok = unsafe { AXValueGetValue(value.0, value_type, &mut pair as *mut CgPair as *mut c_void) };
+    ok.then_some((pair.a, pair.b))
+}
+
+/// Read the CGWindowID of a window element
+fn element_window_id(element: CFTypeRef) -> Option<u32> {
+    let mut id = 0u32;
+    (unsafe { _AXUIElementGetWindow(element, &mut id) } == AX_SUCCESS && id != 0).then_some(id)
+}
+
+/// Read what identifies a window element
+pub fn element_info(element: &AxElement) -> AxWindowInfo {
+    let raw = element.as_raw();
+    let mut pid = 0;
+    unsafe { AXUIElementGetPid(raw, &mut pid) };
+    let title = copy_attribute(raw, "AXTitle").and_then(|title| unsafe { from_cf_string(title.0) });
+    let position = copy_pair(raw, "AXPosition", AX_VALUE_CG_POINT);
+    let size = copy_pair(raw, "AXSize", AX_VALUE_CG_SIZE);
+    AxWindowInfo {
+        pid,
+        window_id: element_window_id(raw),
+        title,
+        frame: position.zip(size).map(|((x, y), (width, height))| (x, y, width, height)),
+    }
+}
+
+/// Find a window element of application `pid` and read it
+///
+/// Elements with a matching CGWindowID win; otherwise the first element for
+/// which `matches` accepts the [`AxWindowInfo`] is used. Returns `None`
+/// without the Accessibility permission or if no element matches.
+fn with_window<T>(
+    pid: i32,
+    window_id: u32,
+    matches: impl Fn(&AxWindowInfo) -> bool,
+    read: impl FnOnce(CFTypeRef) -> Option<T>,
+) -> Option<T> {
+    if !is_trusted() {
+        return None;
+    }
+
+    let app = CfOwned(unsafe { AXUIElementCreateApplication(pid) });
+    if app.0.is_null() {
+        return None;
+    }
+    let windows = copy_attribute(app.0, "AXWindows")?;
+    let count = unsafe { CFArrayGetCount(windows.0) };
+    let elements: Vec<CFTypeRef> = (0..count)
+        .map(|i| unsafe { CFArrayGetValueAtIndex(windows.0, i) })
+        .collect();
+
+    let by_id = elements.iter().copied().find(|&e| element_window_id(e) == Some(window_id));
+    let element = by_id.or_else(|| {
+        elements.iter().copied().find(|&e| {
+            let element = unsafe { AxElement::from_raw(e) };
+            matches(&element_info(&element))
+        })
+    })?;
+    read(element)
+}
+
+/// Find the element for a window of application `pid`
+///
+/// See [`with_window`] for how the element is matched.
+pub fn window_element(pid: i32, window_id: u32, matches: impl Fn(&AxWindowInfo) -> bool) -> Option<AxElement> {
+    with_window(pid, window_id, matches, |element| Some(unsafe { AxElement::from_raw(element) }))
+}
+
+/// Read a window's `AXMinimized` attribute
+///
+/// Returns `None` when the state cannot be determined, e.g. without the
+/// Accessibility permission.
+pub fn window_minimized(pid: i32, window_id: u32) -> Option<bool> {
+    with_window(
+        pid,
+        window_id,
+        |_| false,
+        |element| copy_attribute(element, "AXMinimized").map(|value| unsafe { CFBooleanGetValue(value.0) }),
+    )
+}
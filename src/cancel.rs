@@ -0,0 +1,81 @@
+//! Cooperative cancellation of in-flight captures
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// A handle for cancelling captures started with a `capture_image_cancellable` method
+///
+/// Clones share the same state, so keep one clone to cancel from, e.g. when
+/// the user navigates away, and pass another to the capture. Once cancelled,
+/// a token stays cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every capture using this token or one of its clones
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Check whether the token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the token is cancelled
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            // Register interest before checking the flag so a concurrent cancel is not missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_shared_and_sticky() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+        token.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancelled_wakes_waiter() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let token = CancellationToken::new();
+        let waiter = token.clone();
+        runtime.block_on(async move {
+            let wait = tokio::spawn(async move { waiter.cancelled().await });
+            tokio::task::yield_now().await;
+            token.cancel();
+            wait.await.unwrap();
+        });
+    }
+}
@@ -1,28 +1,166 @@
 //! Core capture functionality using ScreenCaptureKit via cidre
 
-use cidre::{cv, ns, sc};
-use image::RgbaImage;
-use once_cell::sync::Lazy;
+use cidre::{cg, cv, ns, sc};
+use image::{RgbImage, RgbaImage};
 use std::panic;
-use tokio::runtime::Runtime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::runtime::{Handle, Runtime};
 use tracing::debug;
 
-use crate::error::{XCapError, XCapResult};
+use crate::cancel::CancellationToken;
+use crate::cg_window;
+use crate::change::{FrameHasher, PixelRect};
+use crate::color;
+use crate::cursor;
+use crate::error::{OsError, XCapError, XCapResult, USER_DECLINED};
+use crate::phash::PerceptualHasher;
+use crate::options::{BackgroundMode, CaptureOptions, CoordinateSpace, LetterboxPolicy, ScalingMode};
+use crate::stream;
+use crate::timing::{timed, timed_async};
+use crate::yuv::{self, YuvFrame};
 
 /// Global tokio runtime for blocking on async operations (only used when not in an existing runtime)
-static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .expect("Failed to create tokio runtime")
-});
+///
+/// Built lazily, and never built at all if a handle is set with [`set_runtime_handle`].
+/// [`shutdown`] drops it; the next capture builds a new one.
+static RUNTIME: RwLock<Option<Arc<Runtime>>> = RwLock::new(None);
+
+/// Get the built-in runtime, creating it if needed
+fn runtime() -> Arc<Runtime> {
+    if let Some(runtime) = RUNTIME.read().unwrap().as_ref() {
+        return runtime.clone();
+    }
+    let mut slot = RUNTIME.write().unwrap();
+    slot.get_or_insert_with(|| {
+        Arc::new(
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to create tokio runtime"),
+        )
+    })
+    .clone()
+}
+
+/// Release the crate's built-in tokio runtime and its worker threads
+///
+/// The runtime is created on first use and otherwise lives for the rest of
+/// the process. Call this once capture work is done to get its threads back.
+/// Captures already running keep the runtime alive and complete normally; its
+/// threads exit when the last of them returns. Running streams count as in
+/// flight until they are stopped.
+///
+/// Capturing again afterwards lazily creates a new runtime. A handle set with
+/// [`set_runtime_handle`] is not affected, since that runtime belongs to the caller.
+///
+/// Safe to call from async code: the runtime is never dropped in place, so
+/// tokio's "cannot drop a runtime in an async context" panic cannot occur.
+pub fn shutdown() {
+    // Release the lock before touching the runtime, so captures being set up are not held up
+    let taken = RUNTIME.write().unwrap().take();
+    let Some(runtime) = taken else {
+        return;
+    };
+    match Arc::try_unwrap(runtime) {
+        Ok(runtime) => {
+            runtime.shutdown_background();
+            debug!("Released built-in runtime");
+        }
+        // In-flight captures hold the rest; the last of them drops it on its own thread
+        Err(_) => debug!("Released built-in runtime, captures in flight still hold it"),
+    }
+}
+
+/// Caller-provided runtime used instead of [`RUNTIME`]
+static RUNTIME_HANDLE: RwLock<Option<Handle>> = RwLock::new(None);
+
+/// Use an existing tokio runtime for the crate's blocking capture calls
+///
+/// By default the crate lazily creates its own multi-thread runtime. Once a
+/// handle is set, captures and streams are driven on the caller's runtime
+/// instead and the built-in one is never created. Setting a new handle
+/// replaces the previous one.
+///
+/// The handle should belong to a multi-thread runtime: a current-thread
+/// runtime cannot drive its timers from [`Handle::block_on`], which stalls
+/// capture timeouts.
+pub fn set_runtime_handle(handle: Handle) {
+    *RUNTIME_HANDLE.write().unwrap() = Some(handle);
+}
+
+/// Whether monitor captures leave out windows owned by this process
+static EXCLUDE_CURRENT_PROCESS: AtomicBool = AtomicBool::new(false);
+
+/// Exclude the calling process's own windows from every monitor capture
+///
+/// When enabled, monitor captures and streams are built with a filter that
+/// excludes all windows owned by this process, so a recording app does not
+/// capture its own UI. Window captures are unaffected. Disabled by default.
+pub fn set_exclude_current_process(exclude: bool) {
+    EXCLUDE_CURRENT_PROCESS.store(exclude, Ordering::Relaxed);
+}
+
+/// Build a content filter for a whole display
+///
+/// Excludes this process's windows when [`set_exclude_current_process`] is enabled.
+pub fn display_filter(content: &sc::ShareableContent, display: &sc::Display) -> cidre::arc::R<sc::ContentFilter> {
+    display_filter_excluding(content, display, &[])
+}
+
+/// Build a content filter for a whole display that also excludes the given window ids
+///
+/// Ids of windows missing from `content` are ignored.
+pub fn display_filter_excluding(
+    content: &sc::ShareableContent,
+    display: &sc::Display,
+    window_ids: &[u32],
+) -> cidre::arc::R<sc::ContentFilter> {
+    let exclude_own = EXCLUDE_CURRENT_PROCESS.load(Ordering::Relaxed);
+    if !exclude_own && window_ids.is_empty() {
+        let empty_windows = ns::Array::new();
+        return sc::ContentFilter::with_display_excluding_windows(display, &empty_windows);
+    }
+
+    let pid = std::process::id() as i32;
+    let windows = content.windows();
+    let excluded: Vec<&sc::Window> = windows
+        .iter()
+        .filter(|w| {
+            window_ids.contains(&w.id())
+                || (exclude_own && w.owning_app().is_some_and(|app| app.process_id() == pid))
+        })
+        .collect();
+    debug!("Excluding {} windows from display capture", excluded.len());
+
+    let excluded = ns::Array::from_slice(&excluded);
+    sc::ContentFilter::with_display_excluding_windows(display, &excluded)
+}
+
+/// Build a filter rendering only a display's desktop elements
+///
+/// The wallpaper and Finder desktop icons live below the normal window level
+/// (`kCGDesktopWindowLevel` and `kCGDesktopIconWindowLevel` are negative), so
+/// every window with a negative layer is kept and everything else is left out.
+pub fn desktop_filter(content: &sc::ShareableContent, display: &sc::Display) -> cidre::arc::R<sc::ContentFilter> {
+    let windows = content.windows();
+    let desktop: Vec<&sc::Window> = windows.iter().filter(|w| w.window_layer() < 0).collect();
+    debug!("Capturing {} desktop windows only", desktop.len());
 
-/// Run an async operation synchronously using the global runtime
+    let desktop = ns::Array::from_slice(&desktop);
+    sc::ContentFilter::with_display_including_windows(display, &desktop)
+}
+
+/// Run an async operation synchronously on the configured or global runtime
 ///
 /// Note: This must be called from outside a tokio runtime context.
 /// For use within async code, use the async capture functions directly.
 pub fn block_on<F: std::future::Future>(f: F) -> F::Output {
-    RUNTIME.block_on(f)
+    let handle = RUNTIME_HANDLE.read().unwrap().clone();
+    match handle {
+        Some(handle) => handle.block_on(f),
+        None => runtime().block_on(f),
+    }
 }
 
 /// Run a sync closure in a separate thread to avoid nested runtime issues
@@ -49,20 +187,50 @@ where
     }
 }
 
+/// Map a ShareableContent fetch error, detecting missing screen recording permission
+pub(crate) fn map_content_error(e: cidre::arc::R<ns::Error>) -> XCapError {
+    let err_str = format!("{:?}", e);
+    if e.code() == USER_DECLINED || err_str.contains("permission") || err_str.contains("denied") {
+        XCapError::permission_denied_os(OsError::from_ns(&e))
+    } else {
+        XCapError::capture_failed_ns("Failed to get shareable content", &e)
+    }
+}
+
 /// Get shareable content synchronously
 pub fn get_shareable_content() -> XCapResult<cidre::arc::R<sc::ShareableContent>> {
     let fetch = || {
         block_on(async {
-            sc::ShareableContent::current()
+            timed_async("get_shareable_content", sc::ShareableContent::current())
                 .await
-                .map_err(|e| {
-                    let err_str = format!("{:?}", e);
-                    if err_str.contains("permission") || err_str.contains("denied") || err_str.contains("-3801") {
-                        XCapError::permission_denied()
-                    } else {
-                        XCapError::capture_failed(format!("Failed to get shareable content: {}", err_str))
-                    }
-                })
+                .map_err(map_content_error)
+        })
+    };
+
+    // If we're in a tokio runtime, run in a separate thread to avoid nested runtime panic
+    if tokio::runtime::Handle::try_current().is_ok() {
+        run_in_thread(fetch)?
+    } else {
+        fetch()
+    }
+}
+
+/// Get shareable content synchronously with explicit SCK visibility flags
+///
+/// `exclude_desktop_windows` drops the wallpaper and Finder desktop icon windows.
+/// `on_screen_windows_only` drops windows that are minimized, hidden, or on another Space.
+pub fn get_shareable_content_filtered(
+    exclude_desktop_windows: bool,
+    on_screen_windows_only: bool,
+) -> XCapResult<cidre::arc::R<sc::ShareableContent>> {
+    let fetch = move || {
+        block_on(async move {
+            timed_async(
+                "get_shareable_content",
+                sc::ShareableContent::current_excluding_desktop_windows(exclude_desktop_windows, on_screen_windows_only),
+            )
+            .await
+            .map_err(map_content_error)
         })
     };
 
@@ -81,26 +249,63 @@ extern "C" {
 }
 
 /// Extract an RGBA image from a cv::ImageBuf (pixel buffer)
-fn image_buf_to_rgba(image_buf: &mut cv::ImageBuf) -> XCapResult<RgbaImage> {
+pub fn image_buf_to_rgba(image_buf: &mut cv::ImageBuf) -> XCapResult<RgbaImage> {
+    let mut buffer = Vec::new();
+    let (width, height) = image_buf_to_rgba_into(image_buf, None, &mut buffer)?;
+    RgbaImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::capture_failed("Failed to create image from buffer"))
+}
+
+/// Extract an RGB image from a cv::ImageBuf, dropping the alpha channel
+fn image_buf_to_rgb(image_buf: &mut cv::ImageBuf, crop: Option<CropRect>) -> XCapResult<RgbImage> {
+    let mut buffer = Vec::new();
+    let (width, height) = image_buf_convert_into(image_buf, crop, &mut buffer, bgra_to_rgb_into)?;
+    RgbImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::capture_failed("Failed to create image from buffer"))
+}
+
+/// Convert a cv::ImageBuf (pixel buffer) to RGBA bytes in a caller-provided buffer
+///
+/// Only the `crop` region is converted (the whole buffer when `None`). `out` is
+/// cleared and reused, so its allocation carries over between calls. Returns the
+/// dimensions of the converted region.
+fn image_buf_to_rgba_into(
+    image_buf: &mut cv::ImageBuf,
+    crop: Option<CropRect>,
+    out: &mut Vec<u8>,
+) -> XCapResult<(u32, u32)> {
+    image_buf_convert_into(image_buf, crop, out, bgra_to_rgba_into)
+}
+
+/// Extract an RGBA image from a cv::ImageBuf along with its perceptual hash
+///
+/// The hash is fed each row as it is converted, so the frame is walked once.
+fn image_buf_to_rgba_phash(image_buf: &mut cv::ImageBuf, crop: Option<CropRect>) -> XCapResult<(RgbaImage, u64)> {
+    let mut buffer = Vec::new();
+    let mut hash = 0;
+    let (width, height) = image_buf_convert_into(image_buf, crop, &mut buffer, |pixels, bytes_per_row, rect, out| {
+        hash = bgra_to_rgba_phash_into(pixels, bytes_per_row, rect, out);
+    })?;
+    let image = RgbaImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::capture_failed("Failed to create image from buffer"))?;
+    Ok((image, hash))
+}
+
+/// Lock a BGRA pixel buffer and run `convert` over the `crop` region
+fn image_buf_convert_into(
+    image_buf: &mut cv::ImageBuf,
+    crop: Option<CropRect>,
+    out: &mut Vec<u8>,
+    convert: impl FnOnce(&[u8], usize, CropRect, &mut Vec<u8>),
+) -> XCapResult<(u32, u32)> {
     // Get all metadata BEFORE locking
     let width = image_buf.width();
     let height = image_buf.height();
     let plane_count = image_buf.plane_count();
     let pixel_format = image_buf.pixel_format();
 
-    // Validate dimensions before any unsafe operations
-    if width == 0 || height == 0 {
-        return Err(XCapError::capture_failed(format!(
-            "Invalid image buffer dimensions: {}x{}", width, height
-        )));
-    }
-
-    // Guard against absurdly large buffers (>256 megapixels)
-    if width > 16384 || height > 16384 {
-        return Err(XCapError::capture_failed(format!(
-            "Image buffer dimensions too large: {}x{}", width, height
-        )));
-    }
+    // Validate dimensions and crop before any unsafe operations
+    let crop = validate_buffer_region(width, height, crop)?;
 
     // Lock the buffer for reading using raw lock/unlock
     let lock_flags = cv::pixel_buffer::LockFlags::READ_ONLY;
@@ -131,49 +336,18 @@ fn image_buf_to_rgba(image_buf: &mut cv::ImageBuf) -> XCapResult<RgbaImage> {
 
     let result = if pixels_ptr.is_null() {
         Err(XCapError::capture_failed("Pixel buffer base address is null"))
-    } else if bytes_per_row == 0 {
-        Err(XCapError::capture_failed("Pixel buffer bytes_per_row is 0"))
-    } else if bytes_per_row < width * 4 {
-        Err(XCapError::capture_failed(format!(
-            "bytes_per_row ({}) is less than width * 4 ({}), buffer may be corrupt",
-            bytes_per_row, width * 4
-        )))
     } else {
-        // Validate data_size won't overflow
-        let data_size = match bytes_per_row.checked_mul(height) {
-            Some(size) if size > 0 => size,
-            _ => {
-                // Must unlock before returning
-                let _ = unsafe { image_buf.unlock_lock_base_addr(lock_flags) };
-                return Err(XCapError::capture_failed(format!(
-                    "Invalid buffer size: bytes_per_row={} * height={} overflows or is zero",
-                    bytes_per_row, height
-                )));
-            }
-        };
+        match buffer_size(width, height, bytes_per_row) {
+            Ok(data_size) => {
+                // Create a slice from the raw pixel data
+                let pixels = unsafe { std::slice::from_raw_parts(pixels_ptr, data_size) };
 
-        // Create a slice from the raw pixel data
-        let pixels = unsafe { std::slice::from_raw_parts(pixels_ptr, data_size) };
-
-        // Copy and convert BGRA to RGBA
-        let mut buffer = Vec::with_capacity(width * height * 4);
-
-        for row in 0..height {
-            let row_start = row * bytes_per_row;
-            for col in 0..width {
-                let pixel_start = row_start + col * 4;
-                if pixel_start + 3 < pixels.len() {
-                    // BGRA to RGBA conversion
-                    buffer.push(pixels[pixel_start + 2]); // R
-                    buffer.push(pixels[pixel_start + 1]); // G
-                    buffer.push(pixels[pixel_start]);     // B
-                    buffer.push(pixels[pixel_start + 3]); // A
-                }
+                // Copy and convert from BGRA
+                convert(pixels, bytes_per_row, crop, out);
+                Ok((crop.width, crop.height))
             }
+            Err(e) => Err(e),
         }
-
-        RgbaImage::from_raw(width as u32, height as u32, buffer)
-            .ok_or_else(|| XCapError::capture_failed("Failed to create image from buffer"))
     };
 
     // Unlock
@@ -185,205 +359,2167 @@ fn image_buf_to_rgba(image_buf: &mut cv::ImageBuf) -> XCapResult<RgbaImage> {
     result
 }
 
-/// Safely call image_buf_to_rgba with catch_unwind to prevent panics from
-/// corrupt pixel buffers from crashing the entire application.
-fn safe_image_buf_to_rgba(image_buf: &mut cv::ImageBuf) -> XCapResult<RgbaImage> {
-    match panic::catch_unwind(panic::AssertUnwindSafe(|| image_buf_to_rgba(image_buf))) {
-        Ok(result) => result,
-        Err(_) => Err(XCapError::capture_failed(
-            "Panic in image_buf_to_rgba: pixel buffer may be corrupt or deallocated",
-        )),
-    }
-}
-
-/// Capture a single frame from a window using ScreenCaptureKit
+/// Validate pixel buffer dimensions and resolve the region to convert
 ///
-/// This captures the display containing the window and crops to the window bounds.
-/// This approach works reliably for all window types.
-pub fn capture_window_sync(window_id: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
-    // If we're in a tokio runtime, run in a separate thread to avoid nested runtime panic
-    if tokio::runtime::Handle::try_current().is_ok() {
-        run_in_thread(move || block_on(capture_window_async(window_id, width, height)))?
-    } else {
-        block_on(capture_window_async(window_id, width, height))
+/// `crop` defaults to the whole buffer.
+fn validate_buffer_region(width: usize, height: usize, crop: Option<CropRect>) -> XCapResult<CropRect> {
+    if width == 0 || height == 0 {
+        return Err(XCapError::capture_failed(format!(
+            "Invalid image buffer dimensions: {}x{}", width, height
+        )));
     }
-}
 
-/// Async version of window capture
-async fn capture_window_async(window_id: u32, _width: u32, _height: u32) -> XCapResult<RgbaImage> {
-    // Get shareable content
-    let content = sc::ShareableContent::current()
-        .await
-        .map_err(|e| XCapError::capture_failed(format!("Failed to get shareable content: {:?}", e)))?;
+    // Guard against absurdly large buffers (>256 megapixels)
+    if width > 16384 || height > 16384 {
+        return Err(XCapError::capture_failed(format!(
+            "Image buffer dimensions too large: {}x{}", width, height
+        )));
+    }
 
-    // Find the window
-    let windows = content.windows();
-    let window = windows
-        .iter()
-        .find(|w| w.id() == window_id)
-        .ok_or_else(|| XCapError::window_not_found(window_id))?;
+    let crop = crop.unwrap_or_else(|| CropRect::full(width as u32, height as u32));
+    if crop.width == 0
+        || crop.height == 0
+        || (crop.x + crop.width) as usize > width
+        || (crop.y + crop.height) as usize > height
+    {
+        return Err(XCapError::capture_failed(format!(
+            "Crop region {}x{} at ({}, {}) is outside buffer {}x{}",
+            crop.width, crop.height, crop.x, crop.y, width, height
+        )));
+    }
+    Ok(crop)
+}
 
-    // Get window frame
-    let window_frame = window.frame();
-    let window_x = window_frame.origin.x;
-    let window_y = window_frame.origin.y;
-    let window_width = window_frame.size.width as u32;
-    let window_height = window_frame.size.height as u32;
+/// Validate a BGRA row stride and get the total size of the pixel data
+fn buffer_size(width: usize, height: usize, bytes_per_row: usize) -> XCapResult<usize> {
+    if bytes_per_row == 0 {
+        return Err(XCapError::capture_failed("Pixel buffer bytes_per_row is 0"));
+    }
+    if bytes_per_row < width * 4 {
+        return Err(XCapError::capture_failed(format!(
+            "bytes_per_row ({}) is less than width * 4 ({}), buffer may be corrupt",
+            bytes_per_row, width * 4
+        )));
+    }
+    match bytes_per_row.checked_mul(height) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(XCapError::capture_failed(format!(
+            "Invalid buffer size: bytes_per_row={} * height={} overflows or is zero",
+            bytes_per_row, height
+        ))),
+    }
+}
 
-    debug!(
-        "Window {} frame: {}x{} at ({}, {})",
-        window_id, window_width, window_height, window_x, window_y
-    );
+/// Pixel layout of a buffer passed to [`capture_from_buffer`]
+#[cfg(any(test, feature = "synthetic-capture"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BufferFormat {
+    /// 8-bit BGRA, the format ScreenCaptureKit is configured to deliver
+    Bgra8,
+}
 
-    // Find the display that contains this window
-    let displays = content.displays();
-    let display = displays
-        .iter()
-        .find(|d| {
-            let display_frame = d.frame();
-            // Check if window's origin is within this display
-            window_x >= display_frame.origin.x
-                && window_y >= display_frame.origin.y
-                && window_x < display_frame.origin.x + display_frame.size.width
-                && window_y < display_frame.origin.y + display_frame.size.height
-        })
-        .or_else(|| displays.first())
-        .ok_or_else(|| XCapError::capture_failed("No display found for window"))?;
+/// Run the capture conversion on a caller-supplied pixel buffer
+///
+/// Goes through the same validation and row/channel conversion as a real
+/// capture, so it can be tested and benchmarked without a display or screen
+/// recording permission. `raw` holds `height` rows of `bytes_per_row` bytes,
+/// which may include padding after each row of `width` pixels.
+#[cfg(any(test, feature = "synthetic-capture"))]
+pub fn capture_from_buffer(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_row: usize,
+    format: BufferFormat,
+) -> XCapResult<RgbaImage> {
+    let (width, height) = (width as usize, height as usize);
+    let crop = validate_buffer_region(width, height, None)?;
+    let data_size = buffer_size(width, height, bytes_per_row)?;
+    if raw.len() < data_size {
+        return Err(XCapError::capture_failed(format!(
+            "Buffer holds {} bytes, expected at least {}",
+            raw.len(),
+            data_size
+        )));
+    }
 
-    let display_frame = display.frame();
-    let display_width = display.width() as u32;
-    let display_height = display.height() as u32;
+    let mut out = Vec::new();
+    match format {
+        BufferFormat::Bgra8 => bgra_to_rgba_into(&raw[..data_size], bytes_per_row, crop, &mut out),
+    }
+    RgbaImage::from_raw(crop.width, crop.height, out)
+        .ok_or_else(|| XCapError::capture_failed("Failed to create image from buffer"))
+}
 
-    debug!(
-        "Display frame: {}x{} at ({}, {})",
-        display_width, display_height, display_frame.origin.x, display_frame.origin.y
-    );
+/// Copy a region of BGRA rows into `out` as tightly packed RGBA
+///
+/// `out` is cleared first; its capacity is reused.
+fn bgra_to_rgba_into(pixels: &[u8], bytes_per_row: usize, rect: CropRect, out: &mut Vec<u8>) {
+    out.clear();
+    out.reserve(rect.width as usize * rect.height as usize * 4);
 
-    // Create content filter for the display (captures everything)
-    let empty_windows = ns::Array::new();
-    let filter = sc::ContentFilter::with_display_excluding_windows(&display, &empty_windows);
+    for row in rect.y as usize..(rect.y + rect.height) as usize {
+        let row_start = row * bytes_per_row + rect.x as usize * 4;
+        for col in 0..rect.width as usize {
+            let pixel_start = row_start + col * 4;
+            if pixel_start + 3 < pixels.len() {
+                // BGRA to RGBA conversion
+                out.push(pixels[pixel_start + 2]); // R
+                out.push(pixels[pixel_start + 1]); // G
+                out.push(pixels[pixel_start]);     // B
+                out.push(pixels[pixel_start + 3]); // A
+            }
+        }
+    }
+}
 
-    // Create stream configuration - capture at display resolution
-    let mut cfg = sc::StreamCfg::new();
-    cfg.set_width(display_width as usize);
-    cfg.set_height(display_height as usize);
-    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
-    cfg.set_shows_cursor(false);
-    cfg.set_scales_to_fit(false); // Don't scale, capture at native resolution
+/// Copy a region of BGRA rows into `out` as tightly packed RGBA and return its perceptual hash
+///
+/// `out` is cleared first; its capacity is reused.
+fn bgra_to_rgba_phash_into(pixels: &[u8], bytes_per_row: usize, rect: CropRect, out: &mut Vec<u8>) -> u64 {
+    out.clear();
+    out.reserve(rect.width as usize * rect.height as usize * 4);
+    let mut hasher = PerceptualHasher::new(rect.width, rect.height);
 
-    // Use ScreenshotManager for single frame capture (macOS 14.0+)
-    let sample_buf = sc::ScreenshotManager::capture_sample_buf(&filter, &cfg)
-        .await
-        .map_err(|e| XCapError::capture_failed(format!("Screenshot capture failed: {:?}", e)))?;
+    for (y, row) in (rect.y as usize..(rect.y + rect.height) as usize).enumerate() {
+        let row_start = row * bytes_per_row + rect.x as usize * 4;
+        let row_end = row_start + rect.width as usize * 4;
+        if let Some(row_pixels) = pixels.get(row_start..row_end) {
+            let converted = out.len();
+            for bgra in row_pixels.chunks_exact(4) {
+                out.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+            }
+            // The row is still in cache, so hashing it here costs no extra pass over memory
+            hasher.add_row(y, &out[converted..]);
+        }
+    }
+    hasher.finish()
+}
 
-    // Get the image buffer from the sample buffer
-    let mut image_buf = sample_buf
-        .image_buf()
-        .ok_or_else(|| XCapError::capture_failed("Failed to get image buffer from sample"))?
-        .retained();
+/// Copy a region of BGRA rows into `out` as tightly packed RGB, dropping alpha
+///
+/// `out` is cleared first; its capacity is reused.
+fn bgra_to_rgb_into(pixels: &[u8], bytes_per_row: usize, rect: CropRect, out: &mut Vec<u8>) {
+    out.clear();
+    out.reserve(rect.width as usize * rect.height as usize * 3);
 
-    // Convert to RGBA (with catch_unwind safety net)
-    let full_image = safe_image_buf_to_rgba(&mut image_buf)?;
+    for row in rect.y as usize..(rect.y + rect.height) as usize {
+        let row_start = row * bytes_per_row + rect.x as usize * 4;
+        let row_end = row_start + rect.width as usize * 4;
+        if let Some(row_pixels) = pixels.get(row_start..row_end) {
+            for bgra in row_pixels.chunks_exact(4) {
+                out.extend_from_slice(&[bgra[2], bgra[1], bgra[0]]);
+            }
+        }
+    }
+}
 
-    // Calculate crop coordinates relative to display origin
-    let crop_x = (window_x - display_frame.origin.x) as u32;
-    let crop_y = (window_y - display_frame.origin.y) as u32;
+/// Run a pixel buffer conversion with catch_unwind, so a panic on a corrupt
+/// pixel buffer fails the capture instead of crashing the entire application
+pub fn safe_convert<T>(f: impl FnOnce() -> XCapResult<T>) -> XCapResult<T> {
+    match timed("image_buf_to_rgba", || panic::catch_unwind(panic::AssertUnwindSafe(f))) {
+        Ok(result) => result,
+        Err(_) => Err(XCapError::capture_failed(
+            "Panic while converting a pixel buffer: it may be corrupt or deallocated",
+        )),
+    }
+}
 
-    // Clamp crop region to image bounds
-    let crop_x = crop_x.min(full_image.width().saturating_sub(1));
-    let crop_y = crop_y.min(full_image.height().saturating_sub(1));
-    let crop_width = window_width.min(full_image.width().saturating_sub(crop_x));
-    let crop_height = window_height.min(full_image.height().saturating_sub(crop_y));
+/// Estimate the bytes of an RGBA image covering `width` x `height` points
+///
+/// Each point is `scale` pixels wide and tall, and each pixel takes 4 bytes.
+pub fn estimated_rgba_bytes(width: u32, height: u32, scale: f64) -> usize {
+    let pixel_width = (width as f64 * scale).round() as usize;
+    let pixel_height = (height as f64 * scale).round() as usize;
+    pixel_width.saturating_mul(pixel_height).saturating_mul(4)
+}
 
-    debug!(
-        "Cropping: {}x{} at ({}, {})",
-        crop_width, crop_height, crop_x, crop_y
-    );
+/// Lower `scale` until a `width` x `height` point capture fits in `max_bytes` of RGBA
+///
+/// Returns `scale` unchanged if it already fits. Never goes below one pixel
+/// on the shorter side, so a tiny cap still yields an image.
+pub(crate) fn capped_scale(scale: f64, width: u32, height: u32, max_bytes: usize) -> f64 {
+    let bytes = estimated_rgba_bytes(width, height, scale);
+    if bytes <= max_bytes {
+        return scale;
+    }
+    let min_scale = 1.0 / width.min(height).max(1) as f64;
+    let mut capped = scale * (max_bytes as f64 / bytes as f64).sqrt();
+    // Rounding to whole pixels can overshoot the cap by a row or column
+    while capped > min_scale && estimated_rgba_bytes(width, height, capped) > max_bytes {
+        capped *= 0.999;
+    }
+    capped.max(min_scale)
+}
 
-    // Crop to window bounds
-    let cropped = image::imageops::crop_imm(&full_image, crop_x, crop_y, crop_width, crop_height);
+/// Downscale one captured image to several sizes
+///
+/// Each size is the length of the longest side; the aspect ratio is kept and
+/// images are never upscaled, so a size at or above the native resolution
+/// yields the full image. Results are in the order requested.
+pub fn build_pyramid(image: RgbaImage, sizes: &[u32]) -> XCapResult<Vec<RgbaImage>> {
+    if let Some(size) = sizes.iter().find(|&&size| size == 0) {
+        return Err(XCapError::new(format!("Invalid pyramid size {}", size)));
+    }
 
-    Ok(cropped.to_image())
+    let longest = image.width().max(image.height());
+    let levels = timed("build_pyramid", || {
+        sizes
+            .iter()
+            .map(|&size| {
+                if size >= longest {
+                    return image.clone();
+                }
+                let scale = size as f64 / longest as f64;
+                let width = ((image.width() as f64 * scale).round() as u32).max(1);
+                let height = ((image.height() as f64 * scale).round() as u32).max(1);
+                image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle)
+            })
+            .collect()
+    });
+    Ok(levels)
 }
 
-/// Capture a single frame from a monitor using ScreenCaptureKit
-pub fn capture_monitor_sync(monitor_id: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
-    // If we're in a tokio runtime, run in a separate thread to avoid nested runtime panic
-    if tokio::runtime::Handle::try_current().is_ok() {
-        run_in_thread(move || block_on(capture_monitor_async(monitor_id, width, height)))?
-    } else {
-        block_on(capture_monitor_async(monitor_id, width, height))
-    }
+/// Check whether the running macOS version is at least `major.minor`
+pub fn is_os_at_least(major: isize, minor: isize) -> bool {
+    ns::ProcessInfo::current().is_os_at_least_version(ns::OsVersion {
+        major,
+        minor,
+        patch: 0,
+    })
 }
 
-/// Async version of monitor capture
-async fn capture_monitor_async(monitor_id: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
-    // Get shareable content
-    let content = sc::ShareableContent::current()
-        .await
-        .map_err(|e| XCapError::capture_failed(format!("Failed to get shareable content: {:?}", e)))?;
+/// Check whether `SCScreenshotManager` is available (macOS 14.0+)
+pub fn screenshot_manager_available() -> bool {
+    is_os_at_least(14, 0)
+}
 
-    // Find the display
-    let displays = content.displays();
-    let display = displays
-        .iter()
-        .find(|d| d.display_id().0 == monitor_id)
-        .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+/// Largest width or height ScreenCaptureKit can render into
+///
+/// Frames are Metal textures, and every GPU in a Mac that runs macOS 12.3+
+/// (Apple silicon and the Mac2 family) caps 2D textures at 16384 pixels per
+/// side. Larger requests fail inside ScreenCaptureKit with an opaque error.
+pub(crate) const MAX_TEXTURE_SIZE: u32 = 16384;
 
-    // Create content filter for this display (excluding no windows)
-    let empty_windows = ns::Array::new();
-    let filter = sc::ContentFilter::with_display_excluding_windows(&display, &empty_windows);
+/// Check that an output size fits in a single Metal texture
+pub(crate) fn check_texture_size(width: u32, height: u32) -> XCapResult<()> {
+    if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+        return Err(XCapError::too_large(width, height, MAX_TEXTURE_SIZE));
+    }
+    Ok(())
+}
 
-    // Create stream configuration
-    // Use the physical pixel dimensions passed in (from CGDisplayPixelsWide/High)
-    // This ensures we capture at the actual native resolution of the display
-    let mut cfg = sc::StreamCfg::new();
-    cfg.set_width(width as usize);
-    cfg.set_height(height as usize);
-    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
-    cfg.set_shows_cursor(true);
-    // IMPORTANT: Don't scale to fit - capture at native resolution
-    // This prevents distortion on ultrawide monitors (32:9 aspect ratio like 5120x1440)
-    // When scales_to_fit is true and dimensions don't match exactly, content gets cropped
-    cfg.set_scales_to_fit(false);
+/// Capture a single frame for a filter and configuration
+///
+/// Uses `SCScreenshotManager` on macOS 14.0+, and falls back to grabbing the
+/// first frame of a short-lived `SCStream` on macOS 12.3-13.x where the
+/// screenshot API does not exist. Fails with [`crate::ErrorKind::TooLarge`]
+/// when the configured output exceeds [`MAX_TEXTURE_SIZE`].
+async fn capture_image_buf(
+    filter: &sc::ContentFilter,
+    cfg: &sc::StreamCfg,
+) -> XCapResult<cidre::arc::R<cv::ImageBuf>> {
+    check_texture_size(cfg.width() as u32, cfg.height() as u32)?;
 
-    debug!(
-        "Capturing monitor {} at {}x{} (scales_to_fit=false)",
-        monitor_id, width, height
-    );
+    if !screenshot_manager_available() {
+        debug!("SCScreenshotManager unavailable, capturing via one-shot SCStream");
+        return timed_async("capture_single_frame", stream::capture_single_frame(filter, cfg)).await;
+    }
 
     // Use ScreenshotManager for single frame capture (macOS 14.0+)
-    let sample_buf = sc::ScreenshotManager::capture_sample_buf(&filter, &cfg)
+    let sample_buf = timed_async("capture_sample_buf", sc::ScreenshotManager::capture_sample_buf(filter, cfg))
         .await
-        .map_err(|e| XCapError::capture_failed(format!("Screenshot capture failed: {:?}", e)))?;
+        .map_err(|e| XCapError::capture_failed_ns("Screenshot capture failed", &e))?;
 
     // Get the image buffer from the sample buffer
-    let mut image_buf = sample_buf
+    let image_buf = sample_buf
         .image_buf()
         .ok_or_else(|| XCapError::capture_failed("Failed to get image buffer from sample"))?
         .retained();
 
-    // Convert to RGBA (with catch_unwind safety net)
-    let result = safe_image_buf_to_rgba(&mut image_buf)?;
-    
-    debug!(
-        "Captured image: {}x{} (requested {}x{})",
-        result.width(), result.height(), width, height
-    );
+    Ok(image_buf)
+}
 
-    Ok(result)
+/// Find the display containing a point in global coordinates
+///
+/// Falls back to the first display when no display contains the point
+/// (e.g. a window whose origin is slightly off screen).
+pub fn display_for_point(displays: &ns::Array<sc::Display>, x: f64, y: f64) -> Option<&sc::Display> {
+    displays
+        .iter()
+        .find(|d| {
+            let frame = d.frame();
+            frame_contains(frame.origin.x, frame.origin.y, frame.size.width, frame.size.height, x, y)
+        })
+        .or_else(|| displays.first())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Check whether a point lies inside a frame (origin inclusive, far edges exclusive)
+pub fn frame_contains(origin_x: f64, origin_y: f64, width: f64, height: f64, x: f64, y: f64) -> bool {
+    x >= origin_x && y >= origin_y && x < origin_x + width && y < origin_y + height
+}
 
-    #[test]
-    fn test_get_shareable_content() {
-        // This test will fail if screen recording permission is not granted
-        let content = get_shareable_content();
-        // We just verify the API works
-        assert!(content.is_ok() || content.is_err());
+/// Find the windows attached to `parent`, such as sheets, popovers and tooltips
+///
+/// Public APIs expose no parent-child relationship between windows, so a
+/// child is a window of the same process, at the same or a higher layer,
+/// whose frame overlaps or touches the parent's.
+fn child_windows<'a>(windows: &'a ns::Array<sc::Window>, parent: &sc::Window) -> Vec<&'a sc::Window> {
+    let Some(pid) = parent.owning_app().map(|app| app.process_id()) else {
+        return Vec::new();
+    };
+    let parent_frame = parent.frame();
+    windows
+        .iter()
+        .filter(|w| w.id() != parent.id() && w.is_on_screen())
+        .filter(|w| w.owning_app().is_some_and(|app| app.process_id() == pid))
+        .filter(|w| w.window_layer() >= parent.window_layer())
+        .filter(|w| rects_touch(parent_frame, w.frame()))
+        .collect()
+}
+
+/// Check whether two rects overlap or share an edge
+fn rects_touch(a: cg::Rect, b: cg::Rect) -> bool {
+    a.origin.x <= b.origin.x + b.size.width
+        && b.origin.x <= a.origin.x + a.size.width
+        && a.origin.y <= b.origin.y + b.size.height
+        && b.origin.y <= a.origin.y + a.size.height
+}
+
+/// Get the smallest rect containing both rects
+fn union_rect(a: cg::Rect, b: cg::Rect) -> cg::Rect {
+    let left = a.origin.x.min(b.origin.x);
+    let top = a.origin.y.min(b.origin.y);
+    let right = (a.origin.x + a.size.width).max(b.origin.x + b.size.width);
+    let bottom = (a.origin.y + a.size.height).max(b.origin.y + b.size.height);
+    cg::Rect {
+        origin: cg::Point { x: left, y: top },
+        size: cg::Size {
+            width: right - left,
+            height: bottom - top,
+        },
+    }
+}
+
+/// Region of a captured pixel buffer, in buffer pixels
+#[derive(Debug, Clone, Copy)]
+struct CropRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl CropRect {
+    /// A rect covering the whole buffer
+    fn full(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width, height }
+    }
+
+    /// Narrow to a sub-rect given relative to this rect's top-left corner
+    ///
+    /// The sub-rect is clipped to this rect; returns `None` if it starts outside it.
+    fn sub_rect(&self, x: u32, y: u32, width: u32, height: u32) -> Option<Self> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(Self {
+            x: self.x + x,
+            y: self.y + y,
+            width: width.min(self.width - x),
+            height: height.min(self.height - y),
+        })
+    }
+
+    /// Crop an image to this rect, skipping the copy when it covers the whole image
+    fn apply(&self, image: RgbaImage) -> RgbaImage {
+        if self.x == 0 && self.y == 0 && self.width == image.width() && self.height == image.height() {
+            return image;
+        }
+        image::imageops::crop_imm(&image, self.x, self.y, self.width, self.height).to_image()
+    }
+}
+
+/// Where a window capture, cropped to its display, sits within the window's frame
+///
+/// With the `xcap-compat` feature, full-window captures cover the window's
+/// whole frame at capture time, as xcap's do: the parts of the window off its
+/// display are left transparent (black without alpha) rather than cropped
+/// away. Without the feature, captures are returned cropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FramePlacement {
+    /// Offset of the visible part within the frame
+    x: u32,
+    y: u32,
+    /// Size of the whole window frame, in output pixels
+    width: u32,
+    height: u32,
+}
+
+impl FramePlacement {
+    /// Whether a `width` x `height` crop needs padding to fill the frame
+    fn pads(&self, width: u32, height: u32) -> bool {
+        cfg!(feature = "xcap-compat") && (width, height) != (self.width, self.height) && self.width > 0 && self.height > 0
+    }
+
+    /// Pad a cropped window capture to the window's frame, if enabled
+    fn apply<P: image::Pixel>(
+        &self,
+        image: image::ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> image::ImageBuffer<P, Vec<P::Subpixel>> {
+        if !self.pads(image.width(), image.height()) {
+            return image;
+        }
+        pad_to_frame(image, self.x, self.y, self.width, self.height)
+    }
+
+    /// Pad tightly packed RGBA bytes in place, returning the new size
+    ///
+    /// Only a padded capture loses the buffer's original allocation.
+    fn apply_into(&self, buf: &mut Vec<u8>, width: u32, height: u32) -> (u32, u32) {
+        if !self.pads(width, height) {
+            return (width, height);
+        }
+        buf.truncate(width as usize * height as usize * 4);
+        let Some(image) = RgbaImage::from_raw(width, height, std::mem::take(buf)) else {
+            return (width, height);
+        };
+        let padded = pad_to_frame(image, self.x, self.y, self.width, self.height);
+        let size = padded.dimensions();
+        *buf = padded.into_raw();
+        size
+    }
+}
+
+/// Place `image` at (`x`, `y`) on an empty `width` x `height` canvas
+fn pad_to_frame<P: image::Pixel>(
+    image: image::ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> image::ImageBuffer<P, Vec<P::Subpixel>> {
+    debug!(
+        "Padding {}x{} window capture to its {}x{} frame at ({}, {})",
+        image.width(),
+        image.height(),
+        width,
+        height,
+        x,
+        y
+    );
+    let mut frame = image::ImageBuffer::new(width, height);
+    image::imageops::replace(&mut frame, &image, x as i64, y as i64);
+    frame
+}
+
+/// Hash the BGRA pixels of a region of an image buffer without converting it
+///
+/// Used to short-circuit the RGBA conversion when a frame is identical to the previous one.
+fn hash_image_buf_region(image_buf: &mut cv::ImageBuf, rect: CropRect) -> XCapResult<u64> {
+    let width = image_buf.width();
+    let height = image_buf.height();
+    let plane_count = image_buf.plane_count();
+
+    if rect.width == 0
+        || rect.height == 0
+        || (rect.x + rect.width) as usize > width
+        || (rect.y + rect.height) as usize > height
+    {
+        return Err(XCapError::capture_failed(format!(
+            "Hash region {}x{} at ({}, {}) is outside buffer {}x{}",
+            rect.width, rect.height, rect.x, rect.y, width, height
+        )));
+    }
+
+    let lock_flags = cv::pixel_buffer::LockFlags::READ_ONLY;
+    let lock_result = unsafe { image_buf.lock_base_addr(lock_flags) };
+    if lock_result.is_err() {
+        return Err(XCapError::capture_failed(format!("Failed to lock pixel buffer: {:?}", lock_result)));
+    }
+
+    let (bytes_per_row, pixels_ptr) = if plane_count == 0 {
+        let bpr = unsafe { CVPixelBufferGetBytesPerRow(image_buf as *const _ as *const std::ffi::c_void) };
+        let ptr = unsafe { CVPixelBufferGetBaseAddress(image_buf as *const _ as *const std::ffi::c_void) };
+        (bpr, ptr)
+    } else {
+        (image_buf.plane_bytes_per_row(0), image_buf.plane_base_address(0))
+    };
+
+    let result = if pixels_ptr.is_null() {
+        Err(XCapError::capture_failed("Pixel buffer base address is null"))
+    } else if bytes_per_row < width * 4 {
+        Err(XCapError::capture_failed(format!(
+            "bytes_per_row ({}) is less than width * 4 ({}), buffer may be corrupt",
+            bytes_per_row, width * 4
+        )))
+    } else {
+        let pixels = unsafe { std::slice::from_raw_parts(pixels_ptr, bytes_per_row * height) };
+        let mut hasher = FrameHasher::new();
+        hasher.write_dimensions(rect.width, rect.height);
+        for row in rect.y as usize..(rect.y + rect.height) as usize {
+            let start = row * bytes_per_row + rect.x as usize * 4;
+            hasher.write_row(&pixels[start..start + rect.width as usize * 4]);
+        }
+        Ok(hasher.finish())
+    };
+
+    let unlock_result = unsafe { image_buf.unlock_lock_base_addr(lock_flags) };
+    if unlock_result.is_err() {
+        debug!("Warning: failed to unlock pixel buffer: {:?}", unlock_result);
+    }
+
+    result
+}
+
+/// Hash a captured buffer and convert it only if it differs from `last_hash`
+fn convert_if_changed(
+    image_buf: &mut cv::ImageBuf,
+    crop: CropRect,
+    last_hash: Option<u64>,
+) -> XCapResult<(u64, Option<RgbaImage>)> {
+    let hash = hash_image_buf_region(image_buf, crop)?;
+    if last_hash == Some(hash) {
+        debug!("Frame unchanged (hash {:016x}), skipping conversion", hash);
+        return Ok((hash, None));
+    }
+
+    let image = safe_convert(|| image_buf_to_rgba(image_buf))?;
+    Ok((hash, Some(crop.apply(image))))
+}
+
+/// Run a blocking capture, moving it to a separate thread when called from inside a tokio runtime
+fn run_blocking<F, T>(f: F) -> XCapResult<T>
+where
+    F: FnOnce() -> XCapResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    // If we're in a tokio runtime, run in a separate thread to avoid nested runtime panic
+    if tokio::runtime::Handle::try_current().is_ok() {
+        run_in_thread(f)?
+    } else {
+        f()
+    }
+}
+
+/// Capture a single frame from a window using ScreenCaptureKit
+///
+/// This captures the display containing the window and crops to the window bounds.
+/// This approach works reliably for all window types.
+pub fn capture_window_sync(window_id: u32, width: u32, height: u32, options: CaptureOptions) -> XCapResult<RgbaImage> {
+    run_blocking(move || block_on(capture_window_async(window_id, width, height, options)))
+}
+
+/// Capture a window into a caller-provided RGBA buffer
+///
+/// The buffer is moved in and always handed back, even on failure, so its
+/// allocation can be reused by the caller.
+pub fn capture_window_into_sync(window_id: u32, buf: Vec<u8>) -> XCapResult<(Vec<u8>, XCapResult<(u32, u32)>)> {
+    run_blocking(move || {
+        let mut buf = buf;
+        let result = block_on(async {
+            let (mut image_buf, crop, placement) =
+                window_image_buf_async(window_id, &CaptureOptions::default()).await?;
+            let (width, height) = safe_convert(|| image_buf_to_rgba_into(&mut image_buf, Some(crop), &mut buf))?;
+            Ok(placement.apply_into(&mut buf, width, height))
+        });
+        Ok((buf, result))
+    })
+}
+
+/// Capture a window, skipping the RGBA conversion if it is identical to `last_hash`
+///
+/// Returns the hash of the captured frame and the image, which is `None` when unchanged.
+pub fn capture_window_if_changed_sync(
+    window_id: u32,
+    last_hash: Option<u64>,
+) -> XCapResult<(u64, Option<RgbaImage>)> {
+    run_blocking(move || {
+        block_on(async move {
+            let (mut image_buf, crop, placement) =
+                window_image_buf_async(window_id, &CaptureOptions::default()).await?;
+            let (hash, image) = convert_if_changed(&mut image_buf, crop, last_hash)?;
+            Ok((hash, image.map(|image| placement.apply(image))))
+        })
+    })
+}
+
+/// Capture a window and compute its perceptual hash during conversion
+pub fn capture_window_phash_sync(window_id: u32) -> XCapResult<(RgbaImage, u64)> {
+    run_blocking(move || {
+        block_on(async move {
+            let (mut image_buf, crop, placement) =
+                window_image_buf_async(window_id, &CaptureOptions::default()).await?;
+            let (image, hash) = safe_convert(|| image_buf_to_rgba_phash(&mut image_buf, Some(crop)))?;
+            Ok((placement.apply(image), hash))
+        })
+    })
+}
+
+/// Longest side, in pixels, of the downscaled probe hashed by [`window_probe_hash_sync`]
+const PROBE_SIZE: u32 = 64;
+
+/// Get the size of a probe image for a `width` x `height` area, keeping its aspect ratio
+fn probe_dimensions(width: u32, height: u32) -> (u32, u32) {
+    let longest = width.max(height).max(1);
+    if longest <= PROBE_SIZE {
+        return (width.max(1), height.max(1));
+    }
+    let scale = PROBE_SIZE as f64 / longest as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Hash a tiny downscaled capture of a window
+///
+/// ScreenCaptureKit crops to the window and scales it down to at most
+/// [`PROBE_SIZE`] pixels on a side on the GPU, so only a few kilobytes are
+/// read back and hashed. Small changes (a blinking caret, one changed
+/// character) can be averaged away by the downscale.
+pub fn window_probe_hash_sync(window_id: u32) -> XCapResult<u64> {
+    run_blocking(move || {
+        block_on(async move {
+            let mut target = window_target_async(window_id, &CaptureOptions::default()).await?;
+            let crop = target.crop_within(target.display_width, target.display_height);
+            // The source rect is in points, the output size in pixels
+            let scale = target.scale;
+            target.cfg.set_src_rect(cg::Rect {
+                origin: cg::Point { x: crop.x as f64 / scale, y: crop.y as f64 / scale },
+                size: cg::Size { width: crop.width as f64 / scale, height: crop.height as f64 / scale },
+            });
+            let (width, height) = probe_dimensions(crop.width, crop.height);
+            target.cfg.set_width(width as usize);
+            target.cfg.set_height(height as usize);
+            target.cfg.set_scales_to_fit(true);
+
+            let mut image_buf = capture_image_buf(&target.filter, &target.cfg).await?;
+            let full = CropRect::full(image_buf.width() as u32, image_buf.height() as u32);
+            hash_image_buf_region(&mut image_buf, full)
+        })
+    })
+}
+
+/// Capture a sub-region of a window
+///
+/// The rect is relative to the window's top-left corner, in points, and is
+/// clipped to the part of the window visible on its display. Only the region
+/// is converted to RGBA.
+pub fn capture_window_rect_sync(window_id: u32, x: u32, y: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let (mut image_buf, window_crop, _) = window_image_buf_async(window_id, &CaptureOptions::default()).await?;
+            let crop = window_crop.sub_rect(x, y, width, height).ok_or_else(|| {
+                XCapError::capture_failed(format!("Region at ({}, {}) is not visible in window {}", x, y, window_id))
+            })?;
+
+            let mut rgba = Vec::new();
+            let (out_width, out_height) = safe_convert(|| image_buf_to_rgba_into(&mut image_buf, Some(crop), &mut rgba))?;
+            RgbaImage::from_raw(out_width, out_height, rgba)
+                .ok_or_else(|| XCapError::capture_failed("Failed to create image from raw data"))
+        })
+    })
+}
+
+/// Capture a window from the window server's cached image
+///
+/// Works for minimized windows, which ScreenCaptureKit cannot render, and
+/// returns the last contents the window server composited for them.
+pub fn capture_window_snapshot_sync(window_id: u32) -> XCapResult<RgbaImage> {
+    let snapshot = timed("create_window_image", || cg_window::create_window_image(window_id))
+        .ok_or_else(|| XCapError::capture_failed(format!("No snapshot available for window {}", window_id)))?;
+
+    let mut rgba = Vec::new();
+    bgra_to_rgba_into(
+        &snapshot.bgra,
+        snapshot.bytes_per_row,
+        CropRect::full(snapshot.width, snapshot.height),
+        &mut rgba,
+    );
+    RgbaImage::from_raw(snapshot.width, snapshot.height, rgba)
+        .ok_or_else(|| XCapError::capture_failed("Window snapshot has an unexpected size"))
+}
+
+/// Capture a window from an already-fetched ShareableContent
+pub fn capture_window_in_sync(
+    content: cidre::arc::R<sc::ShareableContent>,
+    window_id: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    run_blocking(move || block_on(capture_window_in_async(&content, window_id, options)))
+}
+
+/// Async version of window capture
+async fn capture_window_async(
+    window_id: u32,
+    _width: u32,
+    _height: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    // Get shareable content
+    let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+        .await
+        .map_err(map_content_error)?;
+
+    capture_window_in_async(&content, window_id, options).await
+}
+
+/// Capture a window, looking it up in `content`
+async fn capture_window_in_async(
+    content: &sc::ShareableContent,
+    window_id: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    let prepared = prepare_window(content, window_id, options)?;
+    capture_prepared_async(&prepared).await
+}
+
+/// Filter, configuration and post-processing for a capture, built ahead of time
+pub(crate) enum PreparedCapture {
+    /// A window, cropped out of its display's frame
+    Window {
+        target: WindowTarget,
+        options: CaptureOptions,
+    },
+    /// A whole display
+    Display {
+        filter: cidre::arc::R<sc::ContentFilter>,
+        cfg: cidre::arc::R<sc::StreamCfg>,
+        display_id: u32,
+        /// Display frame in global points
+        region: cg::Rect,
+        options: CaptureOptions,
+    },
+}
+
+// SAFETY: the filter and configuration are never mutated after preparation, and
+// ScreenCaptureKit only reads them when a capture starts.
+unsafe impl Send for PreparedCapture {}
+unsafe impl Sync for PreparedCapture {}
+
+/// Build the filter and configuration for capturing a window in `content`
+pub(crate) fn prepare_window(
+    content: &sc::ShareableContent,
+    window_id: u32,
+    options: CaptureOptions,
+) -> XCapResult<PreparedCapture> {
+    let target = window_target_in(content, window_id, &options)?;
+    Ok(PreparedCapture::Window { target, options })
+}
+
+/// Build the filter and configuration for capturing a display in `content`
+pub(crate) fn prepare_monitor(
+    content: &sc::ShareableContent,
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: CaptureOptions,
+) -> XCapResult<PreparedCapture> {
+    let displays = content.displays();
+    let display = displays
+        .iter()
+        .find(|d| d.display_id().0 == monitor_id)
+        .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+
+    let (filter, cfg) = display_capture_setup(content, display, width, height, &options);
+    Ok(PreparedCapture::Display {
+        filter,
+        cfg,
+        display_id: monitor_id,
+        region: display.frame(),
+        options,
+    })
+}
+
+/// Capture a single frame of a prepared target
+pub(crate) fn capture_prepared_sync(prepared: Arc<PreparedCapture>) -> XCapResult<RgbaImage> {
+    run_blocking(move || block_on(capture_prepared_async(&prepared)))
+}
+
+/// Capture a prepared target, then convert, crop and post-process the frame
+pub(crate) async fn capture_prepared_async(prepared: &PreparedCapture) -> XCapResult<RgbaImage> {
+    match prepared {
+        PreparedCapture::Window { target, options } => {
+            let mut image_buf = capture_image_buf(&target.filter, &target.cfg).await?;
+            let crop = target.crop_within(image_buf.width() as u32, image_buf.height() as u32);
+
+            // Convert to RGBA (with catch_unwind safety net)
+            let mut full_image = safe_convert(|| image_buf_to_rgba(&mut image_buf))?;
+            if options.normalize_srgb {
+                color::ensure_srgb(&image_buf, &mut full_image, target.display_id)?;
+            }
+
+            // Crop to window bounds, then pad back to the frame if parts were off the display
+            let mut image = target.placement(&crop).apply(crop.apply(full_image));
+            fill_background(target, options.background, &image_buf, &mut image).await?;
+            if let Some(cursor_image) = options.custom_cursor() {
+                cursor::composite(&mut image, cursor_image, target.region);
+            }
+            options.flip.apply(&mut image);
+            Ok(image)
+        }
+        PreparedCapture::Display {
+            filter,
+            cfg,
+            display_id,
+            region,
+            options,
+        } => {
+            let mut image_buf = capture_image_buf(filter, cfg).await?;
+
+            // Convert to RGBA (with catch_unwind safety net)
+            let mut image = safe_convert(|| image_buf_to_rgba(&mut image_buf))?;
+            if options.normalize_srgb {
+                color::ensure_srgb(&image_buf, &mut image, *display_id)?;
+            }
+            if let Some(cursor_image) = options.custom_cursor() {
+                cursor::composite(&mut image, cursor_image, *region);
+            }
+            options.flip.apply(&mut image);
+            Ok(image)
+        }
+    }
+}
+
+/// Composite a window capture over the background chosen in the options
+///
+/// Does nothing for opaque captures, i.e. windows cropped from the screen.
+async fn fill_background(
+    target: &WindowTarget,
+    background: BackgroundMode,
+    image_buf: &cv::ImageBuf,
+    image: &mut RgbaImage,
+) -> XCapResult<()> {
+    let backdrop = match background {
+        BackgroundMode::Transparent => return Ok(()),
+        BackgroundMode::Color([r, g, b]) => RgbaImage::from_pixel(image.width(), image.height(), image::Rgba([r, g, b, 255])),
+        BackgroundMode::Wallpaper => match &target.backdrop {
+            Some((filter, cfg)) => {
+                let mut backdrop_buf = capture_image_buf(filter, cfg).await?;
+                safe_convert(|| image_buf_to_rgba(&mut backdrop_buf))?
+            }
+            // The wallpaper is already rendered into the frame by the filter
+            None => return Ok(()),
+        },
+    };
+    let mode = color::alpha_mode(image_buf, image);
+    color::composite_over(image, &backdrop, mode);
+    Ok(())
+}
+
+/// Capture the display containing a window
+///
+/// Returns the display's pixel buffer, the window's bounds within it and
+/// where those bounds sit within the window's frame.
+async fn window_image_buf_async(
+    window_id: u32,
+    options: &CaptureOptions,
+) -> XCapResult<(cidre::arc::R<cv::ImageBuf>, CropRect, FramePlacement)> {
+    let target = window_target_async(window_id, options).await?;
+    let image_buf = capture_image_buf(&target.filter, &target.cfg).await?;
+    let crop = target.crop_within(image_buf.width() as u32, image_buf.height() as u32);
+    let placement = target.placement(&crop);
+    Ok((image_buf, crop, placement))
+}
+
+/// Filter and configuration for capturing the display under a window
+pub(crate) struct WindowTarget {
+    filter: cidre::arc::R<sc::ContentFilter>,
+    cfg: cidre::arc::R<sc::StreamCfg>,
+    /// Window origin relative to the display origin, in points
+    offset_x: f64,
+    offset_y: f64,
+    window_width: u32,
+    window_height: u32,
+    /// Window frame in global points
+    region: cg::Rect,
+    /// Display the window is captured from
+    display_id: u32,
+    /// Output pixels per point (1.0 for logical output)
+    scale: f64,
+    /// Configured capture size of the display, in output pixels
+    display_width: u32,
+    display_height: u32,
+    /// Desktop behind the window, captured separately for [`BackgroundMode::Wallpaper`]
+    backdrop: Option<(cidre::arc::R<sc::ContentFilter>, cidre::arc::R<sc::StreamCfg>)>,
+}
+
+impl WindowTarget {
+    /// Get the window's bounds within a captured frame of the display
+    fn crop_within(&self, buf_width: u32, buf_height: u32) -> CropRect {
+        window_crop(
+            self.offset_x * self.scale,
+            self.offset_y * self.scale,
+            (self.window_width as f64 * self.scale).round() as u32,
+            (self.window_height as f64 * self.scale).round() as u32,
+            buf_width,
+            buf_height,
+        )
+    }
+
+    /// Get where a crop from [`WindowTarget::crop_within`] sits within the window's frame
+    fn placement(&self, crop: &CropRect) -> FramePlacement {
+        // Nonzero only when the window extends past the display's left or top edge
+        let offset = |crop_start: u32, window_start: f64| (crop_start as f64 - window_start).round().max(0.0) as u32;
+        FramePlacement {
+            x: offset(crop.x, self.offset_x * self.scale),
+            y: offset(crop.y, self.offset_y * self.scale),
+            width: (self.window_width as f64 * self.scale).round() as u32,
+            height: (self.window_height as f64 * self.scale).round() as u32,
+        }
+    }
+}
+
+/// Clamp a window's bounds (in output pixels, relative to the display) to a captured frame
+fn window_crop(x: f64, y: f64, width: u32, height: u32, buf_width: u32, buf_height: u32) -> CropRect {
+    // Calculate crop coordinates relative to display origin; the part of a
+    // window left of or above the display is not in the frame at all
+    let crop_x = x as u32;
+    let crop_y = y as u32;
+    let width = (width as f64 + x.min(0.0)).max(0.0) as u32;
+    let height = (height as f64 + y.min(0.0)).max(0.0) as u32;
+
+    // Clamp crop region to image bounds
+    let crop_x = crop_x.min(buf_width.saturating_sub(1));
+    let crop_y = crop_y.min(buf_height.saturating_sub(1));
+    let crop_width = width.min(buf_width.saturating_sub(crop_x));
+    let crop_height = height.min(buf_height.saturating_sub(crop_y));
+
+    debug!(
+        "Cropping: {}x{} at ({}, {})",
+        crop_width, crop_height, crop_x, crop_y
+    );
+
+    CropRect {
+        x: crop_x,
+        y: crop_y,
+        width: crop_width,
+        height: crop_height,
+    }
+}
+
+/// Resolve a window to a capture of the display that contains it
+async fn window_target_async(window_id: u32, options: &CaptureOptions) -> XCapResult<WindowTarget> {
+    // Get shareable content
+    let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+        .await
+        .map_err(map_content_error)?;
+
+    window_target_in(&content, window_id, options)
+}
+
+/// Resolve a window in `content` to a capture of the display that contains it
+fn window_target_in(
+    content: &sc::ShareableContent,
+    window_id: u32,
+    options: &CaptureOptions,
+) -> XCapResult<WindowTarget> {
+    // Find the window
+    let windows = content.windows();
+    let window = windows
+        .iter()
+        .find(|w| w.id() == window_id)
+        .ok_or_else(|| XCapError::window_not_found(window_id))?;
+
+    let children: Vec<&sc::Window> = if options.include_child_windows {
+        child_windows(&windows, window)
+    } else {
+        Vec::new()
+    };
+
+    // Get window frame, grown to cover any child windows
+    let parent_frame = window.frame();
+    let window_frame = children
+        .iter()
+        .fold(parent_frame, |frame, child| union_rect(frame, child.frame()));
+    let window_x = window_frame.origin.x;
+    let window_y = window_frame.origin.y;
+    let window_width = window_frame.size.width as u32;
+    let window_height = window_frame.size.height as u32;
+
+    debug!(
+        "Window {} frame: {}x{} at ({}, {}), {} child windows",
+        window_id,
+        window_width,
+        window_height,
+        window_x,
+        window_y,
+        children.len()
+    );
+
+    // Find the display that contains this window
+    let displays = content.displays();
+    let display = display_for_point(&displays, parent_frame.origin.x, parent_frame.origin.y)
+        .ok_or_else(|| XCapError::capture_failed("No display found for window"))?;
+
+    let display_frame = display.frame();
+    let native_scale = options.output_scale(display.display_id().0, display.width() as u32, display.height() as u32);
+    let scale = options.capped_scale(native_scale, display.width() as u32, display.height() as u32);
+    let downscaled = scale < native_scale;
+    let display_width = (display.width() as f64 * scale).round() as u32;
+    let display_height = (display.height() as f64 * scale).round() as u32;
+
+    debug!(
+        "Display frame: {}x{} at ({}, {}), output scale {}",
+        display_width, display_height, display_frame.origin.x, display_frame.origin.y, scale
+    );
+
+    if options.include_shadow && !options.include_child_windows {
+        // contentRect, which gives the size including the shadow, was added in macOS 14.0
+        if is_os_at_least(14, 0) {
+            return Ok(shadowed_window_target(content, window, display, window_frame, scale, downscaled, options));
+        }
+        debug!("include_shadow requested but needs macOS 14.0+, ignoring");
+    }
+
+    let filter = if options.include_child_windows {
+        // Render only the window and its children, so overlapping windows of other apps are left out
+        let mut included = vec![window];
+        included.extend(children.iter().copied());
+        if options.background == BackgroundMode::Wallpaper {
+            // The wallpaper and desktop icons sit below every window, so the filter composites them
+            included.extend(windows.iter().filter(|w| w.window_layer() < 0));
+        }
+        sc::ContentFilter::with_display_including_windows(display, &ns::Array::from_slice(&included))
+    } else {
+        // Create content filter for the display (captures everything)
+        let empty_windows = ns::Array::new();
+        sc::ContentFilter::with_display_excluding_windows(&display, &empty_windows)
+    };
+
+    // Create stream configuration - capture at display resolution in the requested coordinate space
+    let mut cfg = sc::StreamCfg::new();
+    cfg.set_width(display_width as usize);
+    cfg.set_height(display_height as usize);
+    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+    cfg.set_shows_cursor(false);
+    cfg.set_scales_to_fit(false); // Don't scale, capture at native resolution
+    options.apply(&mut cfg);
+    if downscaled {
+        cfg.set_scales_to_fit(true);
+    }
+
+    Ok(WindowTarget {
+        filter,
+        cfg,
+        offset_x: window_x - display_frame.origin.x,
+        offset_y: window_y - display_frame.origin.y,
+        window_width,
+        window_height,
+        region: window_frame,
+        display_id: display.display_id().0,
+        scale,
+        display_width,
+        display_height,
+        backdrop: None,
+    })
+}
+
+/// Build a target that renders a window alone, with its drop shadow
+///
+/// A desktop-independent window filter draws the window on a transparent
+/// background and includes the shadow, so the whole frame is the capture and
+/// no crop offset is needed. Such a filter cannot include other windows, so
+/// for [`BackgroundMode::Wallpaper`] the desktop behind the window is set up
+/// as a second capture of the same area.
+fn shadowed_window_target(
+    content: &sc::ShareableContent,
+    window: &sc::Window,
+    display: &sc::Display,
+    window_frame: cg::Rect,
+    scale: f64,
+    downscaled: bool,
+    options: &CaptureOptions,
+) -> WindowTarget {
+    let filter = sc::ContentFilter::with_desktop_independent_window(window);
+    let content_size = filter.content_rect().size;
+    let width = content_size.width.round() as u32;
+    let height = content_size.height.round() as u32;
+    let output_width = (content_size.width * scale).round() as u32;
+    let output_height = (content_size.height * scale).round() as u32;
+
+    let mut cfg = sc::StreamCfg::new();
+    cfg.set_width(output_width as usize);
+    cfg.set_height(output_height as usize);
+    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+    cfg.set_shows_cursor(false);
+    cfg.set_scales_to_fit(downscaled);
+    options.apply(&mut cfg);
+
+    // The shadow surrounds the window roughly evenly, so center the frame in it
+    let region = cg::Rect {
+        origin: cg::Point {
+            x: window_frame.origin.x - (content_size.width - window_frame.size.width) / 2.0,
+            y: window_frame.origin.y - (content_size.height - window_frame.size.height) / 2.0,
+        },
+        size: content_size,
+    };
+
+    debug!(
+        "Window {} with shadow: {}x{} points, {}x{} output pixels",
+        window.id(),
+        width,
+        height,
+        output_width,
+        output_height
+    );
+
+    let backdrop = (options.background == BackgroundMode::Wallpaper).then(|| {
+        let display_frame = display.frame();
+        let mut backdrop_cfg = sc::StreamCfg::new();
+        backdrop_cfg.set_src_rect(cg::Rect {
+            origin: cg::Point {
+                x: region.origin.x - display_frame.origin.x,
+                y: region.origin.y - display_frame.origin.y,
+            },
+            size: region.size,
+        });
+        backdrop_cfg.set_width(output_width as usize);
+        backdrop_cfg.set_height(output_height as usize);
+        backdrop_cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+        backdrop_cfg.set_shows_cursor(false);
+        backdrop_cfg.set_scales_to_fit(downscaled);
+        options.apply(&mut backdrop_cfg);
+        (desktop_filter(content, display), backdrop_cfg)
+    });
+
+    WindowTarget {
+        filter,
+        cfg,
+        offset_x: 0.0,
+        offset_y: 0.0,
+        window_width: width,
+        window_height: height,
+        region,
+        display_id: display.display_id().0,
+        scale,
+        display_width: output_width,
+        display_height: output_height,
+        backdrop,
+    }
+}
+
+/// A CoreGraphics image returned by the `capture_cg_image` methods
+///
+/// Wraps a retained `CGImage` so it can be handed straight to Vision or
+/// CoreImage without a round-trip through an RGBA byte buffer.
+pub struct CgImage(cidre::arc::R<cg::Image>);
+
+// SAFETY: CGImage is immutable once created and documented as thread-safe.
+unsafe impl Send for CgImage {}
+unsafe impl Sync for CgImage {}
+
+impl CgImage {
+    /// Get the underlying `CGImage`
+    pub fn as_cg_image(&self) -> &cg::Image {
+        &self.0
+    }
+
+    /// Consume the wrapper and return the retained `CGImage`
+    pub fn into_inner(self) -> cidre::arc::R<cg::Image> {
+        self.0
+    }
+
+    /// Get the image width in pixels
+    pub fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    /// Get the image height in pixels
+    pub fn height(&self) -> usize {
+        self.0.height()
+    }
+}
+
+impl std::fmt::Debug for CgImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CgImage")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish()
+    }
+}
+
+/// Capture a single frame as a `CGImage`
+///
+/// Requires `SCScreenshotManager` (macOS 14.0+); there is no one-shot stream
+/// fallback because streams only deliver pixel buffers.
+async fn capture_cg_image(filter: &sc::ContentFilter, cfg: &sc::StreamCfg) -> XCapResult<CgImage> {
+    if !screenshot_manager_available() {
+        return Err(XCapError::capture_failed("CGImage capture requires macOS 14.0 or later"));
+    }
+
+    let image = timed_async("capture_image", sc::ScreenshotManager::capture_image(filter, cfg))
+        .await
+        .map_err(|e| XCapError::capture_failed_ns("Screenshot capture failed", &e))?;
+    Ok(CgImage(image))
+}
+
+/// Capture a window as a `CGImage`
+///
+/// The display is captured with its source rect set to the window bounds, so
+/// ScreenCaptureKit does the crop and no pixels are copied on our side.
+pub fn capture_window_cg_image_sync(window_id: u32) -> XCapResult<CgImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let mut target = window_target_async(window_id, &CaptureOptions::default()).await?;
+            let crop = target.crop_within(target.display_width, target.display_height);
+            // The source rect is in points, the output size in pixels
+            let scale = target.scale;
+            target.cfg.set_src_rect(cg::Rect {
+                origin: cg::Point { x: crop.x as f64 / scale, y: crop.y as f64 / scale },
+                size: cg::Size { width: crop.width as f64 / scale, height: crop.height as f64 / scale },
+            });
+            target.cfg.set_width(crop.width as usize);
+            target.cfg.set_height(crop.height as usize);
+            capture_cg_image(&target.filter, &target.cfg).await
+        })
+    })
+}
+
+/// Capture a monitor as a `CGImage`
+pub fn capture_monitor_cg_image_sync(monitor_id: u32, width: u32, height: u32) -> XCapResult<CgImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+                .await
+                .map_err(map_content_error)?;
+            let displays = content.displays();
+            let display = displays
+                .iter()
+                .find(|d| d.display_id().0 == monitor_id)
+                .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+            let (filter, cfg) = display_capture_setup(&content, display, width, height, &CaptureOptions::default());
+            capture_cg_image(&filter, &cfg).await
+        })
+    })
+}
+
+/// Capture a single frame from a monitor using ScreenCaptureKit
+pub fn capture_monitor_sync(monitor_id: u32, width: u32, height: u32, options: CaptureOptions) -> XCapResult<RgbaImage> {
+    run_blocking(move || block_on(capture_monitor_async(monitor_id, width, height, options)))
+}
+
+/// Capture a monitor rendered at an arbitrary output resolution
+///
+/// The whole display is scaled into `width` x `height` by ScreenCaptureKit,
+/// as if that display mode were active, without changing the user's mode.
+/// `options.scaling` defaults to [`ScalingMode::Fit`]. The output size is
+/// explicit, so `options.coordinate_space` and `options.max_bytes` are
+/// ignored, and a custom cursor is not drawn.
+///
+/// Also returns the part of the image the display's content covers, which is
+/// smaller than the image when [`LetterboxPolicy::Pad`] adds black bars.
+pub fn capture_monitor_scaled_sync(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: CaptureOptions,
+) -> XCapResult<(RgbaImage, PixelRect)> {
+    run_blocking(move || {
+        block_on(async move {
+            let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+                .await
+                .map_err(map_content_error)?;
+            let displays = content.displays();
+            let display = displays
+                .iter()
+                .find(|d| d.display_id().0 == monitor_id)
+                .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+
+            let scaling = options.scaling.unwrap_or(ScalingMode::Fit);
+            let options = CaptureOptions {
+                coordinate_space: CoordinateSpace::Logical,
+                scaling: Some(scaling),
+                max_bytes: None,
+                ..options
+            };
+            let (filter, mut cfg) = display_capture_setup(&content, display, width, height, &options);
+
+            let source = cg::Size {
+                width: display.width() as f64,
+                height: display.height() as f64,
+            };
+            // Fit only keeps the aspect ratio where preservesAspectRatio exists
+            let fits = scaling == ScalingMode::Fit && is_os_at_least(14, 0);
+            let content_rect = if fits && options.letterbox == LetterboxPolicy::Crop {
+                cfg.set_src_rect(crop_to_aspect(source, width, height));
+                PixelRect { x: 0, y: 0, width, height }
+            } else if fits {
+                letterbox_rect(source, width, height)
+            } else {
+                PixelRect { x: 0, y: 0, width, height }
+            };
+
+            let mut image_buf = capture_image_buf(&filter, &cfg).await?;
+            let mut image = safe_convert(|| image_buf_to_rgba(&mut image_buf))?;
+            // Letterbox bars are centered, so mirroring moves the content rect by at most a pixel
+            options.flip.apply(&mut image);
+            Ok((image, content_rect))
+        })
+    })
+}
+
+/// Capture a region of a monitor at native resolution
+///
+/// `x`, `y`, `width` and `height` are in points relative to the display's
+/// top-left corner. The region is set as the stream's source rect, so
+/// ScreenCaptureKit only renders that area instead of the whole display being
+/// rendered and cropped afterwards. The output has one pixel per physical
+/// pixel of the display.
+pub fn capture_monitor_content_rect_sync(
+    monitor_id: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> XCapResult<RgbaImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+                .await
+                .map_err(map_content_error)?;
+            let displays = content.displays();
+            let display = displays
+                .iter()
+                .find(|d| d.display_id().0 == monitor_id)
+                .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+
+            let cg_id = cg::DirectDisplayId(monitor_id);
+            let scale = native_scale(cg_id.pixels_wide() as u32, display.width() as u32);
+            let (out_width, out_height) = native_rect_size(width, height, scale);
+
+            let filter = display_filter(&content, display);
+            let mut cfg = sc::StreamCfg::new();
+            cfg.set_src_rect(cg::Rect {
+                origin: cg::Point { x: x as f64, y: y as f64 },
+                size: cg::Size {
+                    width: width as f64,
+                    height: height as f64,
+                },
+            });
+            cfg.set_width(out_width as usize);
+            cfg.set_height(out_height as usize);
+            cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+            cfg.set_shows_cursor(true);
+            cfg.set_scales_to_fit(false);
+
+            debug!(
+                "Capturing {}x{} points at ({}, {}) of monitor {} into {}x{}",
+                width, height, x, y, monitor_id, out_width, out_height
+            );
+
+            let mut image_buf = capture_image_buf(&filter, &cfg).await?;
+            safe_convert(|| image_buf_to_rgba(&mut image_buf))
+        })
+    })
+}
+
+/// Get the display's pixels per point, or 1.0 if either size is unknown
+fn native_scale(pixels_wide: u32, points_wide: u32) -> f64 {
+    if pixels_wide == 0 || points_wide == 0 {
+        return 1.0;
+    }
+    pixels_wide as f64 / points_wide as f64
+}
+
+/// Get the output size in pixels of a `width` x `height` point rect
+fn native_rect_size(width: u32, height: u32, scale: f64) -> (u32, u32) {
+    let out_width = (width as f64 * scale).round().max(1.0) as u32;
+    let out_height = (height as f64 * scale).round().max(1.0) as u32;
+    (out_width, out_height)
+}
+
+/// Get the part of a `width` x `height` frame that a source scaled to fit covers
+///
+/// The source keeps its aspect ratio and is centered, so the rest of the frame
+/// is letterbox (top and bottom) or pillarbox (left and right) padding.
+fn letterbox_rect(source: cg::Size, width: u32, height: u32) -> PixelRect {
+    if source.width <= 0.0 || source.height <= 0.0 {
+        return PixelRect { x: 0, y: 0, width, height };
+    }
+    let scale = (width as f64 / source.width).min(height as f64 / source.height);
+    let content_width = ((source.width * scale).round() as u32).min(width);
+    let content_height = ((source.height * scale).round() as u32).min(height);
+    PixelRect {
+        x: (width - content_width) / 2,
+        y: (height - content_height) / 2,
+        width: content_width,
+        height: content_height,
+    }
+}
+
+/// Get the centered part of a source with the aspect ratio of a `width` x `height` frame
+///
+/// Used as the source rect so the scaled capture fills the frame without padding.
+fn crop_to_aspect(source: cg::Size, width: u32, height: u32) -> cg::Rect {
+    let aspect = width as f64 / height.max(1) as f64;
+    let (crop_width, crop_height) = if source.width / source.height > aspect {
+        (source.height * aspect, source.height)
+    } else {
+        (source.width, source.width / aspect)
+    };
+    cg::Rect {
+        origin: cg::Point {
+            x: (source.width - crop_width) / 2.0,
+            y: (source.height - crop_height) / 2.0,
+        },
+        size: cg::Size {
+            width: crop_width,
+            height: crop_height,
+        },
+    }
+}
+
+/// Run a capture until it completes or `token` is cancelled
+///
+/// On cancellation the capture future is dropped, which releases its
+/// ScreenCaptureKit objects; a screenshot already requested from the system
+/// finishes in the background and is discarded.
+async fn cancellable<T, F>(token: &CancellationToken, capture: F) -> XCapResult<T>
+where
+    F: std::future::Future<Output = XCapResult<T>>,
+{
+    if token.is_cancelled() {
+        return Err(XCapError::cancelled());
+    }
+    let capture = std::pin::pin!(capture);
+    let cancelled = std::pin::pin!(token.cancelled());
+    match futures::future::select(capture, cancelled).await {
+        futures::future::Either::Left((result, _)) => result,
+        futures::future::Either::Right(((), _)) => {
+            debug!("Capture cancelled");
+            Err(XCapError::cancelled())
+        }
+    }
+}
+
+/// Capture a monitor, giving up early if `token` is cancelled
+pub fn capture_monitor_cancellable_sync(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    token: CancellationToken,
+) -> XCapResult<RgbaImage> {
+    run_blocking(move || {
+        block_on(cancellable(
+            &token,
+            capture_monitor_async(monitor_id, width, height, CaptureOptions::default()),
+        ))
+    })
+}
+
+/// Capture a window, giving up early if `token` is cancelled
+///
+/// Runs the same capture and post-processing as [`capture_window_sync`].
+pub fn capture_window_cancellable_sync(
+    window_id: u32,
+    width: u32,
+    height: u32,
+    token: CancellationToken,
+) -> XCapResult<RgbaImage> {
+    run_blocking(move || {
+        block_on(cancellable(
+            &token,
+            capture_window_async(window_id, width, height, CaptureOptions::default()),
+        ))
+    })
+}
+
+/// Capture a monitor as an RGB image, skipping the alpha channel
+pub fn capture_monitor_rgb_sync(monitor_id: u32, width: u32, height: u32) -> XCapResult<RgbImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let mut image_buf = monitor_image_buf_async(monitor_id, width, height, &CaptureOptions::default()).await?;
+            safe_convert(|| image_buf_to_rgb(&mut image_buf, None))
+        })
+    })
+}
+
+/// Capture a monitor as an NV12 frame
+///
+/// ScreenCaptureKit is asked for `420v` (biplanar 4:2:0, video range), so the
+/// planes are copied out as delivered without any color conversion.
+pub fn capture_monitor_yuv_sync(monitor_id: u32, width: u32, height: u32) -> XCapResult<YuvFrame> {
+    run_blocking(move || {
+        block_on(async move {
+            let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+                .await
+                .map_err(map_content_error)?;
+            let displays = content.displays();
+            let display = displays
+                .iter()
+                .find(|d| d.display_id().0 == monitor_id)
+                .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+
+            let (filter, mut cfg) = display_capture_setup(&content, display, width, height, &CaptureOptions::default());
+            cfg.set_pixel_format(cv::PixelFormat::_420V);
+            let mut image_buf = capture_image_buf(&filter, &cfg).await?;
+            image_buf_to_yuv(&mut image_buf)
+        })
+    })
+}
+
+/// Copy the two planes of a biplanar YUV pixel buffer
+fn image_buf_to_yuv(image_buf: &mut cv::ImageBuf) -> XCapResult<YuvFrame> {
+    let width = image_buf.width();
+    let height = image_buf.height();
+    let plane_count = image_buf.plane_count();
+    if plane_count != 2 {
+        return Err(XCapError::capture_failed(format!(
+            "Expected a biplanar YUV buffer, got {} planes ({:?})",
+            plane_count,
+            image_buf.pixel_format()
+        )));
+    }
+    validate_buffer_region(width, height, None)?;
+    let (width, height) = (width as u32, height as u32);
+    let (_, chroma_height) = yuv::chroma_size(width, height);
+
+    let lock_flags = cv::pixel_buffer::LockFlags::READ_ONLY;
+    let lock_result = unsafe { image_buf.lock_base_addr(lock_flags) };
+    if lock_result.is_err() {
+        return Err(XCapError::capture_failed(format!("Failed to lock pixel buffer: {:?}", lock_result)));
+    }
+
+    let copy_plane = |plane: usize, rows: u32| {
+        let stride = image_buf.plane_bytes_per_row(plane);
+        let ptr = image_buf.plane_base_address(plane);
+        if ptr.is_null() {
+            return None;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, stride * rows as usize) };
+        Some((bytes.to_vec(), stride))
+    };
+    let planes = copy_plane(0, height).zip(copy_plane(1, chroma_height));
+
+    let unlock_result = unsafe { image_buf.unlock_lock_base_addr(lock_flags) };
+    if unlock_result.is_err() {
+        debug!("Warning: failed to unlock pixel buffer: {:?}", unlock_result);
+    }
+
+    let ((y, y_stride), (uv, uv_stride)) =
+        planes.ok_or_else(|| XCapError::capture_failed("Pixel buffer plane address is null"))?;
+    YuvFrame::new(width, height, y, y_stride, uv, uv_stride)
+        .ok_or_else(|| XCapError::capture_failed("YUV planes are smaller than the frame"))
+}
+
+/// Capture a window as an RGB image, skipping the alpha channel
+pub fn capture_window_rgb_sync(window_id: u32) -> XCapResult<RgbImage> {
+    run_blocking(move || {
+        block_on(async move {
+            let (mut image_buf, crop, placement) =
+                window_image_buf_async(window_id, &CaptureOptions::default()).await?;
+            Ok(placement.apply(safe_convert(|| image_buf_to_rgb(&mut image_buf, Some(crop)))?))
+        })
+    })
+}
+
+/// Capture a monitor at its display mode's exact pixel size
+///
+/// Fails if ScreenCaptureKit returns a buffer of any other size.
+pub fn capture_monitor_physical_sync(monitor_id: u32) -> XCapResult<RgbaImage> {
+    let cg_id = cg::DirectDisplayId(monitor_id);
+    let (width, height) = (cg_id.pixels_wide() as u32, cg_id.pixels_high() as u32);
+    if width == 0 || height == 0 {
+        return Err(XCapError::monitor_not_found(monitor_id));
+    }
+
+    let image = capture_monitor_sync(monitor_id, width, height, CaptureOptions::default())?;
+    check_physical_size(image.dimensions(), (width, height))?;
+    Ok(image)
+}
+
+/// Check that a capture came back at the requested pixel size
+fn check_physical_size(actual: (u32, u32), expected: (u32, u32)) -> XCapResult<()> {
+    if actual != expected {
+        return Err(XCapError::capture_failed(format!(
+            "Expected a {}x{} physical capture, got {}x{}",
+            expected.0, expected.1, actual.0, actual.1
+        )));
+    }
+    Ok(())
+}
+
+/// Capture several monitors concurrently from one ShareableContent snapshot
+///
+/// `targets` holds `(monitor_id, width, height)` for each display. All captures
+/// are started together so the frames are as close in time as possible. Each
+/// display gets its own result, in the same order as `targets`.
+pub fn capture_monitors_sync(
+    content: cidre::arc::R<sc::ShareableContent>,
+    targets: Vec<(u32, u32, u32)>,
+) -> XCapResult<Vec<XCapResult<RgbaImage>>> {
+    run_blocking(move || {
+        block_on(async move {
+            // Borrow once so each per-display future shares the content instead of moving it
+            let content = &*content;
+            let displays = content.displays();
+            let captures = targets.iter().map(|&(monitor_id, width, height)| {
+                let display = displays.iter().find(|d| d.display_id().0 == monitor_id);
+                async move {
+                    let display = display.ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+                    let mut image_buf =
+                        display_image_buf_async(content, display, width, height, &CaptureOptions::default()).await?;
+                    safe_convert(|| image_buf_to_rgba(&mut image_buf))
+                }
+            });
+            Ok(futures::future::join_all(captures).await)
+        })
+    })
+}
+
+/// Capture several windows from one ShareableContent snapshot
+///
+/// At most `concurrency` captures run at once (at least one). Each window gets
+/// its own result, in the same order as `window_ids`.
+pub fn capture_windows_sync(
+    content: cidre::arc::R<sc::ShareableContent>,
+    window_ids: Vec<u32>,
+    concurrency: usize,
+) -> XCapResult<Vec<XCapResult<RgbaImage>>> {
+    use futures::StreamExt;
+
+    run_blocking(move || {
+        block_on(async move {
+            // Borrow once so each per-window future shares the content instead of moving it
+            let content = &*content;
+            let captures = window_ids
+                .iter()
+                .map(|&window_id| capture_window_in_async(content, window_id, CaptureOptions::default()));
+            Ok(futures::stream::iter(captures)
+                .buffered(concurrency.max(1))
+                .collect()
+                .await)
+        })
+    })
+}
+
+/// Capture several monitors concurrently, giving up on those not done by `deadline`
+///
+/// Like [`capture_monitors_sync`], but each capture is raced against the shared
+/// deadline, so one slow display cannot hold up the batch. Displays that did
+/// not finish in time get `None`.
+pub fn capture_monitors_within_sync(
+    content: cidre::arc::R<sc::ShareableContent>,
+    targets: Vec<(u32, u32, u32)>,
+    deadline: std::time::Instant,
+) -> XCapResult<Vec<Option<XCapResult<RgbaImage>>>> {
+    run_blocking(move || {
+        block_on(async move {
+            let deadline = tokio::time::Instant::from_std(deadline);
+            // Borrow once so each per-display future shares the content instead of moving it
+            let content = &*content;
+            let displays = content.displays();
+            let captures = targets.iter().map(|&(monitor_id, width, height)| {
+                let display = displays.iter().find(|d| d.display_id().0 == monitor_id);
+                let capture = async move {
+                    let display = display.ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+                    let mut image_buf =
+                        display_image_buf_async(content, display, width, height, &CaptureOptions::default()).await?;
+                    safe_convert(|| image_buf_to_rgba(&mut image_buf))
+                };
+                async move { tokio::time::timeout_at(deadline, capture).await.ok() }
+            });
+            Ok(futures::future::join_all(captures).await)
+        })
+    })
+}
+
+/// Capture a monitor into a caller-provided RGBA buffer
+///
+/// The buffer is moved in and always handed back, even on failure, so its
+/// allocation can be reused by the caller.
+pub fn capture_monitor_into_sync(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    buf: Vec<u8>,
+) -> XCapResult<(Vec<u8>, XCapResult<(u32, u32)>)> {
+    run_blocking(move || {
+        let mut buf = buf;
+        let result = block_on(async {
+            let mut image_buf = monitor_image_buf_async(monitor_id, width, height, &CaptureOptions::default()).await?;
+            safe_convert(|| image_buf_to_rgba_into(&mut image_buf, None, &mut buf))
+        });
+        Ok((buf, result))
+    })
+}
+
+/// Capture a monitor, skipping the RGBA conversion if it is identical to `last_hash`
+///
+/// Returns the hash of the captured frame and the image, which is `None` when unchanged.
+pub fn capture_monitor_if_changed_sync(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    last_hash: Option<u64>,
+) -> XCapResult<(u64, Option<RgbaImage>)> {
+    run_blocking(move || {
+        block_on(async move {
+            let mut image_buf = monitor_image_buf_async(monitor_id, width, height, &CaptureOptions::default()).await?;
+            let crop = CropRect::full(image_buf.width() as u32, image_buf.height() as u32);
+            convert_if_changed(&mut image_buf, crop, last_hash)
+        })
+    })
+}
+
+/// Capture a monitor and compute its perceptual hash during conversion
+pub fn capture_monitor_phash_sync(monitor_id: u32, width: u32, height: u32) -> XCapResult<(RgbaImage, u64)> {
+    run_blocking(move || {
+        block_on(async move {
+            let mut image_buf = monitor_image_buf_async(monitor_id, width, height, &CaptureOptions::default()).await?;
+            safe_convert(|| image_buf_to_rgba_phash(&mut image_buf, None))
+        })
+    })
+}
+
+/// Async version of monitor capture
+async fn capture_monitor_async(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    // Get shareable content
+    let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+        .await
+        .map_err(map_content_error)?;
+
+    capture_monitor_in_async(&content, monitor_id, width, height, options).await
+}
+
+/// Capture a monitor from an already-fetched ShareableContent
+pub fn capture_monitor_in_sync(
+    content: cidre::arc::R<sc::ShareableContent>,
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    run_blocking(move || block_on(capture_monitor_in_async(&content, monitor_id, width, height, options)))
+}
+
+/// Capture a monitor, looking it up in `content`
+async fn capture_monitor_in_async(
+    content: &sc::ShareableContent,
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: CaptureOptions,
+) -> XCapResult<RgbaImage> {
+    let prepared = prepare_monitor(content, monitor_id, width, height, options)?;
+    let result = capture_prepared_async(&prepared).await?;
+
+    debug!(
+        "Captured image: {}x{} (requested {}x{})",
+        result.width(), result.height(), width, height
+    );
+
+    Ok(result)
+}
+
+/// Capture the raw pixel buffer of a monitor
+async fn monitor_image_buf_async(
+    monitor_id: u32,
+    width: u32,
+    height: u32,
+    options: &CaptureOptions,
+) -> XCapResult<cidre::arc::R<cv::ImageBuf>> {
+    // Get shareable content
+    let content = timed_async("get_shareable_content", sc::ShareableContent::current())
+        .await
+        .map_err(map_content_error)?;
+
+    // Find the display
+    let displays = content.displays();
+    let display = displays
+        .iter()
+        .find(|d| d.display_id().0 == monitor_id)
+        .ok_or_else(|| XCapError::monitor_not_found(monitor_id))?;
+
+    display_image_buf_async(&content, display, width, height, options).await
+}
+
+/// Capture the raw pixel buffer of an already-resolved display
+async fn display_image_buf_async(
+    content: &sc::ShareableContent,
+    display: &sc::Display,
+    width: u32,
+    height: u32,
+    options: &CaptureOptions,
+) -> XCapResult<cidre::arc::R<cv::ImageBuf>> {
+    let (filter, cfg) = display_capture_setup(content, display, width, height, options);
+    let image_buf = capture_image_buf(&filter, &cfg).await?;
+
+    Ok(image_buf)
+}
+
+/// Build the filter and configuration for capturing a whole display
+fn display_capture_setup(
+    content: &sc::ShareableContent,
+    display: &sc::Display,
+    width: u32,
+    height: u32,
+    options: &CaptureOptions,
+) -> (cidre::arc::R<sc::ContentFilter>, cidre::arc::R<sc::StreamCfg>) {
+    let monitor_id = display.display_id().0;
+
+    // Create content filter for this display (excluding our own windows if configured)
+    let filter = if options.desktop_only {
+        desktop_filter(content, display)
+    } else {
+        display_filter(content, display)
+    };
+
+    let native_scale = options.output_scale(monitor_id, width, height);
+    let scale = options.capped_scale(native_scale, width, height);
+    let downscaled = scale < native_scale;
+    let width = (width as f64 * scale).round() as u32;
+    let height = (height as f64 * scale).round() as u32;
+
+    // Create stream configuration
+    // Use the physical pixel dimensions passed in (from CGDisplayPixelsWide/High)
+    // This ensures we capture at the actual native resolution of the display
+    let mut cfg = sc::StreamCfg::new();
+    cfg.set_width(width as usize);
+    cfg.set_height(height as usize);
+    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+    cfg.set_shows_cursor(true);
+    // IMPORTANT: Don't scale to fit - capture at native resolution
+    // This prevents distortion on ultrawide monitors (32:9 aspect ratio like 5120x1440)
+    // When scales_to_fit is true and dimensions don't match exactly, content gets cropped
+    cfg.set_scales_to_fit(false);
+    options.apply(&mut cfg);
+    if downscaled {
+        // Shrink the whole display into the capped size instead of cropping it
+        cfg.set_scales_to_fit(true);
+        debug!("Monitor {} downscaled to fit max_bytes, scale {}", monitor_id, scale);
+    }
+
+    debug!(
+        "Capturing monitor {} at {}x{} (scales_to_fit={})",
+        monitor_id, width, height, downscaled
+    );
+
+    (filter, cfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_shareable_content() {
+        // This test will fail if screen recording permission is not granted
+        let content = get_shareable_content();
+        // We just verify the API works
+        assert!(content.is_ok() || content.is_err());
+    }
+
+    #[test]
+    fn test_letterbox_rect() {
+        // 16:10 display in a 16:9 frame: bars left and right
+        let rect = letterbox_rect(cg::Size { width: 1440.0, height: 900.0 }, 1920, 1080);
+        assert_eq!(rect, PixelRect { x: 96, y: 0, width: 1728, height: 1080 });
+        // 16:9 display in a 16:10 frame: bars top and bottom
+        let rect = letterbox_rect(cg::Size { width: 1920.0, height: 1080.0 }, 1440, 900);
+        assert_eq!(rect, PixelRect { x: 0, y: 45, width: 1440, height: 810 });
+        // Same aspect ratio: no bars
+        let rect = letterbox_rect(cg::Size { width: 2560.0, height: 1440.0 }, 1280, 720);
+        assert_eq!(rect, PixelRect { x: 0, y: 0, width: 1280, height: 720 });
+    }
+
+    #[test]
+    fn test_crop_to_aspect() {
+        // 16:10 display cropped to 16:9 loses rows at the top and bottom
+        let rect = crop_to_aspect(cg::Size { width: 1440.0, height: 900.0 }, 1920, 1080);
+        assert_eq!(rect.size.width, 1440.0);
+        assert!((rect.size.height - 810.0).abs() < 1e-9);
+        assert_eq!(rect.origin.x, 0.0);
+        assert!((rect.origin.y - 45.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_check_texture_size() {
+        assert!(check_texture_size(5120, 1440).is_ok());
+        assert!(check_texture_size(MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE).is_ok());
+        let err = check_texture_size(15360 * 2, 2160).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::TooLarge);
+        assert!(err.to_string().contains("16384"));
+    }
+
+    #[test]
+    fn test_native_rect_size() {
+        assert_eq!(native_scale(2880, 1440), 2.0);
+        assert_eq!(native_scale(0, 1440), 1.0);
+        assert_eq!(native_rect_size(100, 50, 2.0), (200, 100));
+        assert_eq!(native_rect_size(3, 3, 1.5), (5, 5));
+        assert_eq!(native_rect_size(1, 1, 0.25), (1, 1));
+    }
+
+    #[test]
+    fn test_probe_dimensions() {
+        assert_eq!(probe_dimensions(1280, 800), (64, 40));
+        assert_eq!(probe_dimensions(300, 1200), (16, 64));
+        assert_eq!(probe_dimensions(40, 20), (40, 20));
+        assert_eq!(probe_dimensions(10000, 10), (64, 1));
+    }
+
+    #[test]
+    fn test_block_on_after_shutdown() {
+        assert_eq!(block_on(async { 1 }), 1);
+        shutdown();
+        // The runtime is recreated on demand
+        assert_eq!(block_on(async { 2 }), 2);
+    }
+
+    #[test]
+    fn test_capture_from_buffer_skips_row_padding() {
+        // 2x2 BGRA with 8 bytes of padding per row
+        let raw = [
+            1, 2, 3, 255, 4, 5, 6, 255, 0, 0, 0, 0, 0, 0, 0, 0, //
+            7, 8, 9, 128, 10, 11, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let image = capture_from_buffer(&raw, 2, 2, 16, BufferFormat::Bgra8).unwrap();
+        assert_eq!(image.as_raw(), &vec![3, 2, 1, 255, 6, 5, 4, 255, 9, 8, 7, 128, 12, 11, 10, 0]);
+    }
+
+    #[test]
+    fn test_bgra_to_rgb_crops_and_drops_alpha() {
+        // 3x1 BGRA, converting the last two pixels
+        let pixels = [1, 2, 3, 255, 4, 5, 6, 128, 7, 8, 9, 0];
+        let mut out = Vec::new();
+        bgra_to_rgb_into(&pixels, 12, CropRect { x: 1, y: 0, width: 2, height: 1 }, &mut out);
+        assert_eq!(out, vec![6, 5, 4, 9, 8, 7]);
+    }
+
+    #[test]
+    fn test_capture_from_buffer_rejects_bad_layout() {
+        let raw = [0u8; 16];
+        // Stride shorter than a row
+        assert!(capture_from_buffer(&raw, 2, 2, 4, BufferFormat::Bgra8).is_err());
+        // Buffer shorter than height * stride
+        assert!(capture_from_buffer(&raw, 2, 2, 16, BufferFormat::Bgra8).is_err());
+        assert!(capture_from_buffer(&raw, 0, 2, 8, BufferFormat::Bgra8).is_err());
+    }
+
+    #[test]
+    fn test_cancellable() {
+        let token = CancellationToken::new();
+        assert_eq!(block_on(cancellable(&token, async { Ok(1) })).unwrap(), 1);
+
+        // A capture that never finishes is abandoned once the token fires
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            canceller.cancel();
+        });
+        let result = block_on(cancellable(&token, std::future::pending::<XCapResult<()>>()));
+        assert_eq!(result.unwrap_err().kind(), crate::error::ErrorKind::Cancelled);
+
+        // Already cancelled tokens fail without running the capture
+        let result = block_on(cancellable(&token, async { Ok(2) }));
+        assert_eq!(result.unwrap_err().kind(), crate::error::ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn test_check_physical_size() {
+        assert!(check_physical_size((2880, 1800), (2880, 1800)).is_ok());
+        let err = check_physical_size((1440, 900), (2880, 1800)).unwrap_err();
+        assert!(err.to_string().contains("2880x1800"));
+    }
+
+    #[test]
+    fn test_capped_scale() {
+        // 8K at 1x is ~132 MB; a 32 MB cap needs roughly half the size per side
+        let scale = capped_scale(1.0, 7680, 4320, 32 << 20);
+        assert!(scale < 0.5 && scale > 0.49);
+        assert!(estimated_rgba_bytes(7680, 4320, scale) <= 32 << 20);
+        // Already under the cap
+        assert_eq!(capped_scale(2.0, 1440, 900, 64 << 20), 2.0);
+        // Never below one pixel on the short side
+        let tiny = capped_scale(1.0, 1920, 1080, 1);
+        assert_eq!((1080.0 * tiny).round(), 1.0);
+    }
+
+    #[test]
+    fn test_estimated_rgba_bytes() {
+        assert_eq!(estimated_rgba_bytes(800, 600, 1.0), 800 * 600 * 4);
+        // Retina quadruples the pixel count
+        assert_eq!(estimated_rgba_bytes(800, 600, 2.0), 1600 * 1200 * 4);
+        assert_eq!(estimated_rgba_bytes(1680, 1050, 1.5), 2520 * 1575 * 4);
+    }
+
+    #[test]
+    fn test_frame_contains() {
+        // Secondary display to the left of the primary one
+        assert!(frame_contains(-1920.0, 0.0, 1920.0, 1080.0, -1.0, 0.0));
+        assert!(frame_contains(-1920.0, 0.0, 1920.0, 1080.0, -1920.0, 1079.0));
+        // Far edges belong to the neighbouring display
+        assert!(!frame_contains(-1920.0, 0.0, 1920.0, 1080.0, 0.0, 0.0));
+        assert!(!frame_contains(0.0, 0.0, 1920.0, 1080.0, 100.0, 1080.0));
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> cg::Rect {
+        cg::Rect {
+            origin: cg::Point { x, y },
+            size: cg::Size { width, height },
+        }
+    }
+
+    #[test]
+    fn test_union_rect_covers_popover() {
+        // Popover hanging off the left edge of its window
+        let union = union_rect(rect(100.0, 100.0, 800.0, 600.0), rect(40.0, 300.0, 200.0, 150.0));
+        assert_eq!(union, rect(40.0, 100.0, 860.0, 600.0));
+    }
+
+    #[test]
+    fn test_rects_touch() {
+        let window = rect(100.0, 100.0, 800.0, 600.0);
+        // A sheet attached below the title bar overlaps
+        assert!(rects_touch(window, rect(200.0, 128.0, 400.0, 300.0)));
+        // Sharing an edge counts
+        assert!(rects_touch(window, rect(900.0, 100.0, 100.0, 100.0)));
+        assert!(!rects_touch(window, rect(901.0, 100.0, 100.0, 100.0)));
+    }
+
+    #[test]
+    fn test_window_crop_clamps_to_frame() {
+        let crop = window_crop(100.0, 50.0, 800, 600, 1440, 900);
+        assert_eq!((crop.x, crop.y, crop.width, crop.height), (100, 50, 800, 600));
+        // Window hanging off the bottom-right corner
+        let crop = window_crop(1000.0, 700.0, 800, 600, 1440, 900);
+        assert_eq!((crop.x, crop.y, crop.width, crop.height), (1000, 700, 440, 200));
+        // Window hanging off the top-left corner
+        let crop = window_crop(-100.0, -20.0, 800, 600, 1440, 900);
+        assert_eq!((crop.x, crop.y, crop.width, crop.height), (0, 0, 700, 580));
+    }
+
+    #[test]
+    fn test_pad_to_frame() {
+        // The visible 700x580 of an 800x600 window hanging off the top-left corner
+        let visible = RgbaImage::from_pixel(700, 580, image::Rgba([255, 0, 0, 255]));
+        let frame = pad_to_frame(visible, 100, 20, 800, 600);
+        assert_eq!(frame.dimensions(), (800, 600));
+        assert_eq!(frame.get_pixel(99, 19).0, [0, 0, 0, 0]);
+        assert_eq!(frame.get_pixel(100, 20).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(799, 599).0, [255, 0, 0, 255]);
+
+        let placement = FramePlacement { x: 0, y: 0, width: 4, height: 2 };
+        let rgb = RgbImage::from_pixel(3, 2, image::Rgb([9, 9, 9]));
+        let padded = placement.apply(rgb);
+        if cfg!(feature = "xcap-compat") {
+            assert_eq!(padded.dimensions(), (4, 2));
+            assert_eq!(padded.get_pixel(3, 0).0, [0, 0, 0]);
+        } else {
+            assert_eq!(padded.dimensions(), (3, 2));
+        }
+
+        let mut buf = vec![7u8; 3 * 2 * 4];
+        let size = placement.apply_into(&mut buf, 3, 2);
+        assert_eq!(buf.len(), size.0 as usize * size.1 as usize * 4);
+    }
+
+    #[test]
+    fn test_crop_rect_sub_rect() {
+        let window = CropRect { x: 100, y: 50, width: 800, height: 600 };
+        let toolbar = window.sub_rect(0, 0, 800, 40).unwrap();
+        assert_eq!((toolbar.x, toolbar.y, toolbar.width, toolbar.height), (100, 50, 800, 40));
+        // Clipped when the window itself was clipped by the display edge
+        let clipped = window.sub_rect(700, 500, 200, 200).unwrap();
+        assert_eq!((clipped.x, clipped.y, clipped.width, clipped.height), (800, 550, 100, 100));
+        assert!(window.sub_rect(800, 0, 10, 10).is_none());
+    }
+
+    #[test]
+    fn test_build_pyramid() {
+        let image = RgbaImage::new(1920, 1080);
+        let levels = build_pyramid(image, &[4096, 480, 96]).unwrap();
+        let dims: Vec<_> = levels.iter().map(|l| l.dimensions()).collect();
+        assert_eq!(dims, vec![(1920, 1080), (480, 270), (96, 54)]);
+    }
+
+    #[test]
+    fn test_build_pyramid_rejects_zero() {
+        assert!(build_pyramid(RgbaImage::new(10, 10), &[5, 0]).is_err());
+    }
+
+    #[test]
+    fn test_crop_rect_full_is_noop() {
+        let image = RgbaImage::from_pixel(4, 3, image::Rgba([1, 2, 3, 255]));
+        let cropped = CropRect::full(4, 3).apply(image.clone());
+        assert_eq!(cropped, image);
+    }
+
+    #[test]
+    fn test_crop_rect_apply() {
+        let mut image = RgbaImage::new(4, 4);
+        image.put_pixel(2, 1, image::Rgba([9, 9, 9, 255]));
+        let crop = CropRect { x: 2, y: 1, width: 2, height: 2 };
+        let cropped = crop.apply(image);
+        assert_eq!(cropped.dimensions(), (2, 2));
+        assert_eq!(cropped.get_pixel(0, 0), &image::Rgba([9, 9, 9, 255]));
+    }
+
+    #[test]
+    fn test_bgra_to_rgba_into_swizzles_and_skips_padding() {
+        // 2x2 BGRA with 4 bytes of row padding
+        let pixels = [
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, //
+            9, 10, 11, 12, 13, 14, 15, 16, 0, 0, 0, 0,
+        ];
+        let mut out = Vec::new();
+        bgra_to_rgba_into(&pixels, 12, CropRect::full(2, 2), &mut out);
+        assert_eq!(out, vec![3, 2, 1, 4, 7, 6, 5, 8, 11, 10, 9, 12, 15, 14, 13, 16]);
+    }
+
+    #[test]
+    fn test_bgra_to_rgba_into_crop_reuses_buffer() {
+        let pixels = [
+            1, 2, 3, 4, 5, 6, 7, 8, //
+            9, 10, 11, 12, 13, 14, 15, 16,
+        ];
+        let mut out = Vec::with_capacity(64);
+        out.extend_from_slice(&[0xff; 8]);
+        let crop = CropRect { x: 1, y: 1, width: 1, height: 1 };
+        bgra_to_rgba_into(&pixels, 8, crop, &mut out);
+        assert_eq!(out, vec![15, 14, 13, 16]);
+        assert!(out.capacity() >= 64);
+    }
+
+    #[test]
+    fn test_bgra_to_rgba_phash_matches_separate_passes() {
+        // 40x30 BGRA gradient with 16 bytes of row padding, cropped to 32x20
+        let bytes_per_row = 40 * 4 + 16;
+        let mut pixels = vec![0u8; bytes_per_row * 30];
+        for y in 0..30 {
+            for x in 0..40 {
+                let i = y * bytes_per_row + x * 4;
+                pixels[i..i + 4].copy_from_slice(&[(x * 6) as u8, (y * 8) as u8, ((x + y) * 3) as u8, 255]);
+            }
+        }
+        let crop = CropRect { x: 4, y: 5, width: 32, height: 20 };
+
+        let mut expected = Vec::new();
+        bgra_to_rgba_into(&pixels, bytes_per_row, crop, &mut expected);
+        let mut out = Vec::new();
+        let hash = bgra_to_rgba_phash_into(&pixels, bytes_per_row, crop, &mut out);
+        assert_eq!(out, expected);
+
+        let image = RgbaImage::from_raw(32, 20, expected).unwrap();
+        assert_eq!(hash, crate::phash::hash_image(&image));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_in_async_context() {
+        let _ = runtime();
+        shutdown();
+        // Twice is a no-op
+        shutdown();
     }
 
     #[tokio::test]
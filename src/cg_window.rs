@@ -0,0 +1,231 @@
+//! CGWindowList queries for window metadata not exposed by ScreenCaptureKit
+
+use std::ffi::c_void;
+
+use cidre::cg;
+
+use crate::ffi::{
+    from_cf_string, CFArrayGetCount, CFArrayGetValueAtIndex, CFBooleanGetValue, CFDataGetBytePtr, CFDataGetLength,
+    i64_value, CFDictionaryGetValue, CFIndex, CFNumberGetValue, CFRelease, CFTypeRef,
+};
+
+/// List every window (`kCGWindowListOptionAll`)
+pub const LIST_OPTION_ALL: u32 = 0;
+/// List only windows currently on screen, front to back
+pub const LIST_OPTION_ON_SCREEN_ONLY: u32 = 1 << 0;
+/// Include only the given window (`kCGWindowListOptionIncludingWindow`)
+pub const LIST_OPTION_INCLUDING_WINDOW: u32 = 1 << 3;
+
+/// `kCGWindowImageBoundsIgnoreFraming`: leave out the window shadow
+const IMAGE_OPTION_BOUNDS_IGNORE_FRAMING: u32 = 1 << 0;
+/// `kCGWindowImageNominalResolution`: one pixel per point, matching SCK captures
+const IMAGE_OPTION_NOMINAL_RESOLUTION: u32 = 1 << 4;
+
+/// `kCGBitmapByteOrderMask` and `kCGBitmapByteOrder32Little`
+const BITMAP_BYTE_ORDER_MASK: u32 = 0x7000;
+const BITMAP_BYTE_ORDER_32_LITTLE: u32 = 2 << 12;
+
+/// `kCGNullWindowID`
+const NULL_WINDOW_ID: u32 = 0;
+
+const CF_NUMBER_DOUBLE_TYPE: CFIndex = 13;
+
+// FFI bindings for CGWindowList and the CoreFoundation accessors needed to read it
+extern "C" {
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> CFTypeRef;
+    fn CGRectMakeWithDictionaryRepresentation(dict: CFTypeRef, rect: *mut cg::Rect) -> bool;
+    fn CGWindowListCreateImage(screen_bounds: cg::Rect, list_option: u32, window_id: u32, image_option: u32) -> CFTypeRef;
+
+    fn CGImageGetWidth(image: CFTypeRef) -> usize;
+    fn CGImageGetHeight(image: CFTypeRef) -> usize;
+    fn CGImageGetBytesPerRow(image: CFTypeRef) -> usize;
+    fn CGImageGetBitsPerPixel(image: CFTypeRef) -> usize;
+    fn CGImageGetBitmapInfo(image: CFTypeRef) -> u32;
+    fn CGImageGetDataProvider(image: CFTypeRef) -> CFTypeRef;
+    fn CGDataProviderCopyData(provider: CFTypeRef) -> CFTypeRef;
+
+    static kCGWindowNumber: CFTypeRef;
+    static kCGWindowOwnerPID: CFTypeRef;
+    static kCGWindowLayer: CFTypeRef;
+    static kCGWindowAlpha: CFTypeRef;
+    static kCGWindowIsOnscreen: CFTypeRef;
+    static kCGWindowBounds: CFTypeRef;
+    static kCGWindowOwnerName: CFTypeRef;
+    static kCGWindowName: CFTypeRef;
+
+    static CGRectNull: cg::Rect;
+}
+
+/// One entry of `CGWindowListCopyWindowInfo`
+///
+/// Each window query reads the fields it needs, so not every field is used everywhere.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgWindowInfo {
+    /// `kCGWindowNumber`, the same id ScreenCaptureKit uses
+    pub window_id: u32,
+    /// `kCGWindowOwnerPID`
+    pub pid: i32,
+    /// `kCGWindowLayer`
+    pub layer: i32,
+    /// `kCGWindowAlpha` (0.0 = fully transparent)
+    pub alpha: f64,
+    /// `kCGWindowIsOnscreen`
+    pub is_on_screen: bool,
+    /// `kCGWindowBounds` in global points, top-left origin
+    pub bounds: cg::Rect,
+    /// `kCGWindowOwnerName`
+    pub owner_name: String,
+    /// `kCGWindowName` (empty without screen recording permission)
+    pub name: String,
+}
+
+/// BGRA pixels of a window image copied out of a `CGImage`
+pub struct WindowImage {
+    pub width: u32,
+    pub height: u32,
+    pub bytes_per_row: usize,
+    pub bgra: Vec<u8>,
+}
+
+fn zero_rect() -> cg::Rect {
+    cg::Rect {
+        origin: cg::Point { x: 0.0, y: 0.0 },
+        size: cg::Size { width: 0.0, height: 0.0 },
+    }
+}
+
+unsafe fn get_i64(dict: CFTypeRef, key: CFTypeRef) -> Option<i64> {
+    i64_value(CFDictionaryGetValue(dict, key))
+}
+
+unsafe fn get_f64(dict: CFTypeRef, key: CFTypeRef) -> Option<f64> {
+    let value = CFDictionaryGetValue(dict, key);
+    if value.is_null() {
+        return None;
+    }
+    let mut out: f64 = 0.0;
+    CFNumberGetValue(value, CF_NUMBER_DOUBLE_TYPE, &mut out as *mut f64 as *mut c_void).then_some(out)
+}
+
+unsafe fn get_bool(dict: CFTypeRef, key: CFTypeRef) -> Option<bool> {
+    let value = CFDictionaryGetValue(dict, key);
+    if value.is_null() {
+        return None;
+    }
+    Some(CFBooleanGetValue(value))
+}
+
+unsafe fn get_string(dict: CFTypeRef, key: CFTypeRef) -> Option<String> {
+    from_cf_string(CFDictionaryGetValue(dict, key))
+}
+
+unsafe fn get_rect(dict: CFTypeRef, key: CFTypeRef) -> Option<cg::Rect> {
+    let value = CFDictionaryGetValue(dict, key);
+    if value.is_null() {
+        return None;
+    }
+    let mut rect = zero_rect();
+    CGRectMakeWithDictionaryRepresentation(value, &mut rect).then_some(rect)
+}
+
+/// Query CGWindowList with the given `LIST_OPTION_*` flags
+///
+/// Entries are returned in the order the window server reports them, which is
+/// front to back for on-screen windows.
+pub fn get_cgwindow_info(option: u32) -> Vec<CgWindowInfo> {
+    unsafe {
+        let list = CGWindowListCopyWindowInfo(option, NULL_WINDOW_ID);
+        if list.is_null() {
+            return Vec::new();
+        }
+
+        let count = CFArrayGetCount(list);
+        let mut windows = Vec::with_capacity(count.max(0) as usize);
+        for i in 0..count {
+            let dict = CFArrayGetValueAtIndex(list, i);
+            if dict.is_null() {
+                continue;
+            }
+            let Some(window_id) = get_i64(dict, kCGWindowNumber) else {
+                continue;
+            };
+            windows.push(CgWindowInfo {
+                window_id: window_id as u32,
+                pid: get_i64(dict, kCGWindowOwnerPID).unwrap_or(-1) as i32,
+                layer: get_i64(dict, kCGWindowLayer).unwrap_or(0) as i32,
+                alpha: get_f64(dict, kCGWindowAlpha).unwrap_or(1.0),
+                is_on_screen: get_bool(dict, kCGWindowIsOnscreen).unwrap_or(false),
+                bounds: get_rect(dict, kCGWindowBounds).unwrap_or_else(zero_rect),
+                owner_name: get_string(dict, kCGWindowOwnerName).unwrap_or_default(),
+                name: get_string(dict, kCGWindowName).unwrap_or_default(),
+            });
+        }
+
+        CFRelease(list);
+        windows
+    }
+}
+
+/// Get window ids in front-to-back stacking order
+///
+/// Desktop elements (wallpaper, Finder desktop icons) are included, at the back.
+pub fn window_stacking_order() -> Vec<u32> {
+    get_cgwindow_info(LIST_OPTION_ON_SCREEN_ONLY)
+        .into_iter()
+        .map(|w| w.window_id)
+        .collect()
+}
+
+/// Get the window server's composited image of a single window
+///
+/// Unlike ScreenCaptureKit this also works for minimized windows, for which the
+/// window server keeps the last rendered contents. Returns `None` if the window
+/// does not exist or the image is not 32-bit BGRA.
+pub fn create_window_image(window_id: u32) -> Option<WindowImage> {
+    unsafe {
+        let image = CGWindowListCreateImage(
+            CGRectNull,
+            LIST_OPTION_INCLUDING_WINDOW,
+            window_id,
+            IMAGE_OPTION_BOUNDS_IGNORE_FRAMING | IMAGE_OPTION_NOMINAL_RESOLUTION,
+        );
+        if image.is_null() {
+            return None;
+        }
+
+        let result = copy_bgra(image);
+        CFRelease(image);
+        result
+    }
+}
+
+unsafe fn copy_bgra(image: CFTypeRef) -> Option<WindowImage> {
+    let width = CGImageGetWidth(image);
+    let height = CGImageGetHeight(image);
+    if width == 0
+        || height == 0
+        || CGImageGetBitsPerPixel(image) != 32
+        || CGImageGetBitmapInfo(image) & BITMAP_BYTE_ORDER_MASK != BITMAP_BYTE_ORDER_32_LITTLE
+    {
+        return None;
+    }
+
+    let provider = CGImageGetDataProvider(image);
+    if provider.is_null() {
+        return None;
+    }
+    let data = CGDataProviderCopyData(provider);
+    if data.is_null() {
+        return None;
+    }
+    let bytes = std::slice::from_raw_parts(CFDataGetBytePtr(data), CFDataGetLength(data).max(0) as usize).to_vec();
+    CFRelease(data);
+
+    Some(WindowImage {
+        width: width as u32,
+        height: height as u32,
+        bytes_per_row: CGImageGetBytesPerRow(image),
+        bgra: bytes,
+    })
+}
@@ -0,0 +1,189 @@
+//! Frame change detection helpers
+
+use image::RgbaImage;
+
+/// FNV-1a 64-bit offset basis
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+/// FNV-1a 64-bit prime
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Incremental hasher for frame contents
+///
+/// Rows are fed one at a time so stride padding in pixel buffers can be skipped.
+/// The hash works on 8-byte words, which is fast enough to run on every frame
+/// while still catching single-pixel changes.
+#[derive(Debug, Clone)]
+pub(crate) struct FrameHasher {
+    state: u64,
+}
+
+impl FrameHasher {
+    pub(crate) fn new() -> Self {
+        Self { state: FNV_OFFSET }
+    }
+
+    /// Feed one row of pixel data
+    pub(crate) fn write_row(&mut self, row: &[u8]) {
+        let mut chunks = row.chunks_exact(8);
+        for chunk in &mut chunks {
+            let word = u64::from_le_bytes(chunk.try_into().unwrap());
+            self.state = (self.state ^ word).wrapping_mul(FNV_PRIME);
+        }
+        for &byte in chunks.remainder() {
+            self.state = (self.state ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Feed the frame dimensions so equal bytes at different sizes don't collide
+    pub(crate) fn write_dimensions(&mut self, width: u32, height: u32) {
+        self.state = (self.state ^ ((width as u64) << 32 | height as u64)).wrapping_mul(FNV_PRIME);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+/// A rectangle in image pixels, with the origin at the top-left of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelRect {
+    /// Left edge
+    pub x: u32,
+    /// Top edge
+    pub y: u32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+/// Find the areas that changed between two consecutive frames
+///
+/// Both frames are split into `block` x `block` tiles (smaller at the right
+/// and bottom edges) and the tiles whose pixels differ are returned, with
+/// runs of changed tiles in the same row merged into one rectangle. The
+/// result is ordered top to bottom, then left to right, and empty if the
+/// frames are identical. If the frames differ in size, the whole of `cur` is
+/// returned. A `block` of 0 is treated as 1.
+pub fn diff_regions(prev: &RgbaImage, cur: &RgbaImage, block: u32) -> Vec<PixelRect> {
+    let (width, height) = cur.dimensions();
+    if prev.dimensions() != (width, height) {
+        return vec![PixelRect { x: 0, y: 0, width, height }];
+    }
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let block = block.max(1);
+    let stride = width as usize * 4;
+    let block_bytes = block as usize * 4;
+    let columns = width.div_ceil(block) as usize;
+    let mut regions = Vec::new();
+    let mut dirty = vec![false; columns];
+
+    for top in (0..height).step_by(block as usize) {
+        let rows = block.min(height - top);
+        dirty.fill(false);
+        for y in top..top + rows {
+            let start = y as usize * stride;
+            let prev_row = &prev.as_raw()[start..start + stride];
+            let cur_row = &cur.as_raw()[start..start + stride];
+            for (column, (a, b)) in prev_row.chunks(block_bytes).zip(cur_row.chunks(block_bytes)).enumerate() {
+                dirty[column] = dirty[column] || a != b;
+            }
+        }
+
+        // Merge runs of dirty tiles in this row
+        let mut column = 0;
+        while column < columns {
+            if !dirty[column] {
+                column += 1;
+                continue;
+            }
+            let first = column;
+            while column < columns && dirty[column] {
+                column += 1;
+            }
+            let x = first as u32 * block;
+            let right = (column as u32 * block).min(width);
+            regions.push(PixelRect {
+                x,
+                y: top,
+                width: right - x,
+                height: rows,
+            });
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn hash_rows(rows: &[&[u8]], width: u32, height: u32) -> u64 {
+        let mut hasher = FrameHasher::new();
+        hasher.write_dimensions(width, height);
+        for row in rows {
+            hasher.write_row(row);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_identical_frames_hash_equal() {
+        let row = [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        assert_eq!(hash_rows(&[&row, &row], 3, 2), hash_rows(&[&row, &row], 3, 2));
+    }
+
+    #[test]
+    fn test_single_byte_change_detected() {
+        let a = [0u8; 16];
+        let mut b = [0u8; 16];
+        b[13] = 1;
+        assert_ne!(hash_rows(&[&a], 4, 1), hash_rows(&[&b], 4, 1));
+    }
+
+    #[test]
+    fn test_diff_regions() {
+        let prev = RgbaImage::new(10, 6);
+        assert!(diff_regions(&prev, &prev.clone(), 4).is_empty());
+
+        // One pixel in the bottom-right edge tile, which is clipped to 2x2
+        let mut cur = prev.clone();
+        cur.put_pixel(9, 5, Rgba([255, 0, 0, 255]));
+        assert_eq!(
+            diff_regions(&prev, &cur, 4),
+            vec![PixelRect { x: 8, y: 4, width: 2, height: 2 }]
+        );
+
+        // Adjacent tiles in a row merge; tiles in different rows do not
+        cur.put_pixel(0, 0, Rgba([0, 255, 0, 255]));
+        cur.put_pixel(5, 1, Rgba([0, 255, 0, 255]));
+        assert_eq!(
+            diff_regions(&prev, &cur, 4),
+            vec![
+                PixelRect { x: 0, y: 0, width: 8, height: 4 },
+                PixelRect { x: 8, y: 4, width: 2, height: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_regions_size_change() {
+        let prev = RgbaImage::new(4, 4);
+        let cur = RgbaImage::new(8, 2);
+        assert_eq!(
+            diff_regions(&prev, &cur, 16),
+            vec![PixelRect { x: 0, y: 0, width: 8, height: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_dimensions_affect_hash() {
+        let row = [0u8; 16];
+        assert_ne!(hash_rows(&[&row], 4, 1), hash_rows(&[&row], 2, 2));
+    }
+}
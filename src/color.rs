@@ -0,0 +1,456 @@
+//! Color profile handling and encoding for captures
+
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use cidre::cv;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, RgbaImage};
+use tracing::debug;
+
+use crate::error::{XCapError, XCapResult};
+use crate::ffi::{CFDataGetBytePtr, CFDataGetLength, CFRelease, CFTypeRef};
+
+/// JPEG quality used by [`write_tagged`] and [`encode`]
+const JPEG_QUALITY: u8 = 90;
+
+/// `kCGImageAlphaNoneSkipLast`: RGBX, alpha byte ignored
+const IMAGE_ALPHA_NONE_SKIP_LAST: u32 = 5;
+
+/// `kCGRenderingIntentDefault`
+const RENDERING_INTENT_DEFAULT: i32 = 0;
+
+#[repr(C)]
+struct CGRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+// FFI bindings for reading color profiles and converting between color spaces
+extern "C" {
+    static kCGColorSpaceSRGB: CFTypeRef;
+    static kCVImageBufferAlphaChannelModeKey: CFTypeRef;
+    static kCVImageBufferAlphaChannelMode_StraightAlpha: CFTypeRef;
+    static kCVImageBufferAlphaChannelMode_PremultipliedAlpha: CFTypeRef;
+
+    fn CGDisplayCopyColorSpace(display: u32) -> CFTypeRef;
+    fn CGColorSpaceCopyICCData(space: CFTypeRef) -> CFTypeRef;
+    fn CGColorSpaceCreateWithName(name: CFTypeRef) -> CFTypeRef;
+    fn CGColorSpaceCopyName(space: CFTypeRef) -> CFTypeRef;
+    fn CGDataProviderCreateWithData(
+        info: *mut c_void,
+        data: *const c_void,
+        size: usize,
+        release: Option<extern "C" fn(*mut c_void, *const c_void, usize)>,
+    ) -> CFTypeRef;
+    fn CGDataProviderRelease(provider: CFTypeRef);
+    #[allow(clippy::too_many_arguments)]
+    fn CGImageCreate(
+        width: usize,
+        height: usize,
+        bits_per_component: usize,
+        bits_per_pixel: usize,
+        bytes_per_row: usize,
+        space: CFTypeRef,
+        bitmap_info: u32,
+        provider: CFTypeRef,
+        decode: *const f64,
+        should_interpolate: bool,
+        intent: i32,
+    ) -> CFTypeRef;
+    fn CGImageRelease(image: CFTypeRef);
+    fn CGBitmapContextCreate(
+        data: *mut c_void,
+        width: usize,
+        height: usize,
+        bits_per_component: usize,
+        bytes_per_row: usize,
+        space: CFTypeRef,
+        bitmap_info: u32,
+    ) -> CFTypeRef;
+    fn CGContextDrawImage(context: CFTypeRef, rect: CGRect, image: CFTypeRef);
+    fn CGContextRelease(context: CFTypeRef);
+    fn CGColorSpaceRelease(space: CFTypeRef);
+    fn CVImageBufferGetColorSpace(image_buf: CFTypeRef) -> CFTypeRef;
+    fn CVBufferCopyAttachment(buffer: CFTypeRef, key: CFTypeRef, attachment_mode: *mut u32) -> CFTypeRef;
+    fn CFEqual(a: CFTypeRef, b: CFTypeRef) -> bool;
+}
+
+/// Get the ICC profile of a display's color space
+///
+/// Captured pixels are in the display's color space (e.g. Display P3 on
+/// recent MacBooks), so this is the profile to embed in saved files.
+pub fn display_icc_profile(display_id: u32) -> Option<Vec<u8>> {
+    unsafe {
+        let space = CGDisplayCopyColorSpace(display_id);
+        if space.is_null() {
+            return None;
+        }
+        let data = CGColorSpaceCopyICCData(space);
+        CFRelease(space);
+        if data.is_null() {
+            return None;
+        }
+
+        let len = CFDataGetLength(data).max(0) as usize;
+        let profile = std::slice::from_raw_parts(CFDataGetBytePtr(data), len).to_vec();
+        CFRelease(data);
+        Some(profile)
+    }
+}
+
+/// How the alpha channel of a captured image relates to its color channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// Color channels are already multiplied by alpha, as the window server composites them
+    Premultiplied,
+    /// Color channels are independent of alpha
+    Straight,
+    /// Every pixel is fully opaque, so both interpretations agree
+    Opaque,
+}
+
+/// Read the alpha mode a pixel buffer is tagged with, if any
+fn image_buf_alpha_attachment(image_buf: &cv::ImageBuf) -> Option<AlphaMode> {
+    unsafe {
+        let value = CVBufferCopyAttachment(
+            image_buf as *const _ as CFTypeRef,
+            kCVImageBufferAlphaChannelModeKey,
+            std::ptr::null_mut(),
+        );
+        if value.is_null() {
+            return None;
+        }
+        let mode = if CFEqual(value, kCVImageBufferAlphaChannelMode_StraightAlpha) {
+            Some(AlphaMode::Straight)
+        } else if CFEqual(value, kCVImageBufferAlphaChannelMode_PremultipliedAlpha) {
+            Some(AlphaMode::Premultiplied)
+        } else {
+            None
+        };
+        CFRelease(value);
+        mode
+    }
+}
+
+/// Determine the alpha mode of an image converted from `image_buf`
+pub fn alpha_mode(image_buf: &cv::ImageBuf, image: &RgbaImage) -> AlphaMode {
+    classify_alpha(image, image_buf_alpha_attachment(image_buf))
+}
+
+/// Classify an image's alpha given the mode its buffer was tagged with
+///
+/// Untagged buffers with transparent pixels come straight from the window
+/// server's surfaces, which are premultiplied.
+fn classify_alpha(image: &RgbaImage, tagged: Option<AlphaMode>) -> AlphaMode {
+    if image.pixels().all(|p| p[3] == u8::MAX) {
+        AlphaMode::Opaque
+    } else {
+        tagged.unwrap_or(AlphaMode::Premultiplied)
+    }
+}
+
+/// Composite an image over an opaque backdrop of the same size, in place
+///
+/// `mode` says how `image`'s alpha is encoded. The result is fully opaque.
+/// Pixels outside the backdrop are composited over black.
+pub fn composite_over(image: &mut RgbaImage, backdrop: &RgbaImage, mode: AlphaMode) {
+    if mode == AlphaMode::Opaque {
+        return;
+    }
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let under = backdrop.get_pixel_checked(x, y).map_or([0, 0, 0], |p| [p[0], p[1], p[2]]);
+        let alpha = pixel[3] as u32;
+        for (channel, under) in pixel.0[..3].iter_mut().zip(under) {
+            let over = match mode {
+                AlphaMode::Straight => *channel as u32 * alpha,
+                _ => *channel as u32 * 255,
+            };
+            *channel = ((over + under as u32 * (255 - alpha) + 127) / 255).min(255) as u8;
+        }
+        pixel[3] = u8::MAX;
+    }
+}
+
+/// Convert premultiplied pixels to straight alpha in place
+///
+/// Fully transparent pixels become transparent black.
+pub fn unpremultiply(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            pixel.0 = [0, 0, 0, 0];
+        } else if alpha < 255 {
+            for channel in &mut pixel.0[..3] {
+                *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Check whether a captured pixel buffer is tagged as sRGB
+///
+/// Buffers without a color space attachment are treated as not sRGB, since
+/// they hold the display's native colors.
+fn image_buf_is_srgb(image_buf: &cv::ImageBuf) -> bool {
+    unsafe {
+        let space = CVImageBufferGetColorSpace(image_buf as *const _ as CFTypeRef);
+        if space.is_null() {
+            return false;
+        }
+        let name = CGColorSpaceCopyName(space);
+        if name.is_null() {
+            return false;
+        }
+        let is_srgb = CFEqual(name, kCGColorSpaceSRGB);
+        CFRelease(name);
+        is_srgb
+    }
+}
+
+/// Make sure an image converted from `image_buf` holds sRGB colors
+///
+/// If ScreenCaptureKit already delivered sRGB this does nothing; otherwise the
+/// image is converted from the display's color space.
+pub fn ensure_srgb(image_buf: &cv::ImageBuf, image: &mut RgbaImage, display_id: u32) -> XCapResult<()> {
+    if image_buf_is_srgb(image_buf) {
+        return Ok(());
+    }
+    debug!("Frame from display {} is not sRGB, converting on the CPU", display_id);
+    convert_to_srgb(image, display_id)
+}
+
+/// Convert an image from a display's color space to sRGB in place
+///
+/// CoreGraphics does the color matching by drawing the image into an sRGB
+/// bitmap. Alpha is carried over unchanged.
+pub fn convert_to_srgb(image: &mut RgbaImage, display_id: u32) -> XCapResult<()> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let (width, height) = (width as usize, height as usize);
+    let bytes_per_row = width * 4;
+    let mut converted = vec![0u8; bytes_per_row * height];
+
+    unsafe {
+        let source_space = CGDisplayCopyColorSpace(display_id);
+        if source_space.is_null() {
+            return Err(XCapError::new(format!("No color space for display {}", display_id)));
+        }
+        let srgb = CGColorSpaceCreateWithName(kCGColorSpaceSRGB);
+        let provider = CGDataProviderCreateWithData(
+            std::ptr::null_mut(),
+            image.as_raw().as_ptr() as *const c_void,
+            image.as_raw().len(),
+            None,
+        );
+        let source = CGImageCreate(
+            width,
+            height,
+            8,
+            32,
+            bytes_per_row,
+            source_space,
+            IMAGE_ALPHA_NONE_SKIP_LAST,
+            provider,
+            std::ptr::null(),
+            false,
+            RENDERING_INTENT_DEFAULT,
+        );
+        let context = CGBitmapContextCreate(
+            converted.as_mut_ptr() as *mut c_void,
+            width,
+            height,
+            8,
+            bytes_per_row,
+            srgb,
+            IMAGE_ALPHA_NONE_SKIP_LAST,
+        );
+
+        let ok = !source.is_null() && !context.is_null();
+        if ok {
+            let rect = CGRect {
+                x: 0.0,
+                y: 0.0,
+                width: width as f64,
+                height: height as f64,
+            };
+            CGContextDrawImage(context, rect, source);
+        }
+
+        if !context.is_null() {
+            CGContextRelease(context);
+        }
+        if !source.is_null() {
+            CGImageRelease(source);
+        }
+        CGDataProviderRelease(provider);
+        CGColorSpaceRelease(srgb);
+        CGColorSpaceRelease(source_space);
+
+        if !ok {
+            return Err(XCapError::new("Failed to set up sRGB conversion"));
+        }
+    }
+
+    copy_rgb(&converted, image);
+    Ok(())
+}
+
+/// Copy the color channels of tightly packed RGBX bytes into an image, keeping its alpha
+fn copy_rgb(rgbx: &[u8], image: &mut RgbaImage) {
+    for (dst, src) in image.chunks_exact_mut(4).zip(rgbx.chunks_exact(4)) {
+        dst[..3].copy_from_slice(&src[..3]);
+    }
+}
+
+/// Encode an image to a file, embedding an ICC profile when given
+///
+/// Supports PNG and JPEG. JPEG has no alpha channel, so alpha is dropped.
+pub fn write_tagged(image: &RgbaImage, path: &Path, format: ImageFormat, icc_profile: Option<Vec<u8>>) -> XCapResult<()> {
+    if !matches!(format, ImageFormat::Png | ImageFormat::Jpeg) {
+        return Err(XCapError::new(format!("Unsupported format {:?}, expected PNG or JPEG", format)));
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    encode_tagged(image, file, format, icc_profile)
+}
+
+/// Encode an image in memory
+///
+/// Any format the `image` crate can write is accepted. PNG and JPEG use the
+/// same settings as [`crate::Monitor::save_capture`] (JPEG at quality 90,
+/// alpha dropped); other formats use the `image` crate's defaults. Formats
+/// without an alpha channel get the RGB pixels only.
+pub fn encode(image: &RgbaImage, format: ImageFormat) -> XCapResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if matches!(format, ImageFormat::Png | ImageFormat::Jpeg) {
+        encode_tagged(image, &mut bytes, format, None)?;
+        return Ok(bytes);
+    }
+
+    let dynamic = DynamicImage::ImageRgba8(image.clone());
+    let result = match dynamic.write_to(&mut Cursor::new(&mut bytes), format) {
+        Err(image::ImageError::Unsupported(_)) => {
+            // e.g. formats with no alpha channel
+            bytes.clear();
+            let rgb = DynamicImage::ImageRgb8(dynamic.into_rgb8());
+            rgb.write_to(&mut Cursor::new(&mut bytes), format)
+        }
+        result => result,
+    };
+    result.map_err(|e| XCapError::with_source(format!("Failed to encode image as {:?}", format), e))?;
+    Ok(bytes)
+}
+
+/// Encode an image as PNG or JPEG into a writer, embedding an ICC profile when given
+fn encode_tagged<W: Write>(image: &RgbaImage, writer: W, format: ImageFormat, icc_profile: Option<Vec<u8>>) -> XCapResult<()> {
+    let (width, height) = image.dimensions();
+
+    let result = match format {
+        ImageFormat::Png => {
+            let mut encoder = PngEncoder::new(writer);
+            embed_profile(&mut encoder, icc_profile);
+            encoder.write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+        }
+        ImageFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+            let mut encoder = JpegEncoder::new_with_quality(writer, JPEG_QUALITY);
+            embed_profile(&mut encoder, icc_profile);
+            encoder.write_image(rgb.as_raw(), width, height, ExtendedColorType::Rgb8)
+        }
+        _ => unreachable!("format checked by the caller"),
+    };
+
+    result.map_err(|e| XCapError::with_source("Failed to encode image", e))
+}
+
+fn embed_profile<E: ImageEncoder>(encoder: &mut E, icc_profile: Option<Vec<u8>>) {
+    if let Some(profile) = icc_profile {
+        if let Err(e) = encoder.set_icc_profile(profile) {
+            debug!("Encoder cannot embed ICC profile, saving untagged: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_format_rejected() {
+        let path = std::env::temp_dir().join("sck-rs-color-test.gif");
+        let result = write_tagged(&RgbaImage::new(2, 2), &path, ImageFormat::Gif, None);
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_classify_alpha() {
+        let opaque = RgbaImage::from_pixel(2, 2, image::Rgba([10, 20, 30, 255]));
+        assert_eq!(classify_alpha(&opaque, Some(AlphaMode::Straight)), AlphaMode::Opaque);
+
+        let mut shadow = opaque.clone();
+        shadow.put_pixel(0, 0, image::Rgba([0, 0, 0, 64]));
+        assert_eq!(classify_alpha(&shadow, None), AlphaMode::Premultiplied);
+        assert_eq!(classify_alpha(&shadow, Some(AlphaMode::Straight)), AlphaMode::Straight);
+    }
+
+    #[test]
+    fn test_composite_over() {
+        let backdrop = RgbaImage::from_pixel(2, 1, image::Rgba([200, 100, 0, 255]));
+
+        // Transparent, half-covered (premultiplied) and opaque pixels
+        let mut image = RgbaImage::from_raw(2, 1, vec![0, 0, 0, 0, 50, 50, 50, 128]).unwrap();
+        composite_over(&mut image, &backdrop, AlphaMode::Premultiplied);
+        assert_eq!(image.as_raw(), &vec![200, 100, 0, 255, 150, 100, 50, 255]);
+
+        let mut image = RgbaImage::from_raw(1, 1, vec![100, 100, 100, 128]).unwrap();
+        composite_over(&mut image, &backdrop, AlphaMode::Straight);
+        assert_eq!(image.as_raw(), &vec![150, 100, 50, 255]);
+    }
+
+    #[test]
+    fn test_unpremultiply() {
+        let mut image = RgbaImage::from_raw(3, 1, vec![64, 32, 0, 128, 9, 9, 9, 0, 1, 2, 3, 255]).unwrap();
+        unpremultiply(&mut image);
+        assert_eq!(image.as_raw(), &vec![128, 64, 0, 128, 0, 0, 0, 0, 1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn test_copy_rgb_keeps_alpha() {
+        let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([0, 0, 0, 128]));
+        copy_rgb(&[1, 2, 3, 255, 4, 5, 6, 255], &mut image);
+        assert_eq!(image.as_raw(), &vec![1, 2, 3, 128, 4, 5, 6, 128]);
+    }
+
+    #[test]
+    fn test_encode_formats() {
+        let image = RgbaImage::from_pixel(4, 4, image::Rgba([10, 20, 30, 255]));
+
+        let png = encode(&image, ImageFormat::Png).unwrap();
+        assert_eq!(image::load_from_memory(&png).unwrap().into_rgba8(), image);
+
+        let jpeg = encode(&image, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image::guess_format(&jpeg).unwrap(), ImageFormat::Jpeg);
+
+        let bmp = encode(&image, ImageFormat::Bmp).unwrap();
+        assert_eq!(image::load_from_memory(&bmp).unwrap().into_rgba8(), image);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let path = std::env::temp_dir().join("sck-rs-color-test.png");
+        let image = RgbaImage::from_pixel(3, 2, image::Rgba([10, 20, 30, 255]));
+        write_tagged(&image, &path, ImageFormat::Png, None).unwrap();
+        let decoded = image::open(&path).unwrap().into_rgba8();
+        assert_eq!(decoded, image);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,102 @@
+//! Reusable snapshot of the capturable displays and windows
+
+use cidre::{arc, sc};
+use image::RgbaImage;
+
+use crate::capture;
+use crate::error::XCapResult;
+use crate::monitor::Monitor;
+use crate::options::CaptureOptions;
+use crate::window::{ListOptions, Window};
+
+/// One fetch of ScreenCaptureKit's shareable content
+///
+/// Every [`Window::all`], [`Monitor::all`] and `capture_image` call asks
+/// ScreenCaptureKit for the current displays and windows, which takes tens of
+/// milliseconds. When enumerating and capturing in one logical operation,
+/// fetch a `Content` once and go through it instead:
+///
+/// ```rust,no_run
+/// use sck_rs::Content;
+///
+/// let content = Content::current().unwrap();
+/// for monitor in content.monitors().unwrap() {
+///     let image = content.capture_monitor(&monitor).unwrap();
+/// }
+/// ```
+///
+/// The snapshot does not update: windows opened afterwards are missing, and
+/// capturing a window that has since closed fails with
+/// [`crate::ErrorKind::WindowNotFound`].
+#[derive(Clone)]
+pub struct Content {
+    inner: arc::R<sc::ShareableContent>,
+}
+
+impl Content {
+    /// Fetch the current shareable content
+    ///
+    /// Requires screen recording permission.
+    pub fn current() -> XCapResult<Content> {
+        Ok(Content {
+            inner: capture::get_shareable_content()?,
+        })
+    }
+
+    /// Get the underlying ScreenCaptureKit content
+    pub(crate) fn raw(&self) -> &sc::ShareableContent {
+        &self.inner
+    }
+
+    /// Get the windows in this snapshot, filtered like [`Window::all`]
+    pub fn windows(&self) -> XCapResult<Vec<Window>> {
+        Window::from_content(&self.inner, &ListOptions::default())
+    }
+
+    /// Get the windows in this snapshot using a custom filtering policy
+    ///
+    /// See [`Window::all_with_options`].
+    pub fn windows_with_options(&self, options: &ListOptions) -> XCapResult<Vec<Window>> {
+        Window::from_content(&self.inner, options)
+    }
+
+    /// Get the monitors in this snapshot
+    pub fn monitors(&self) -> XCapResult<Vec<Monitor>> {
+        Monitor::from_content(&self.inner)
+    }
+
+    /// Capture a monitor without fetching the shareable content again
+    pub fn capture_monitor(&self, monitor: &Monitor) -> XCapResult<RgbaImage> {
+        self.capture_monitor_with(monitor, &CaptureOptions::default())
+    }
+
+    /// Capture a monitor with explicit options
+    pub fn capture_monitor_with(&self, monitor: &Monitor, options: &CaptureOptions) -> XCapResult<RgbaImage> {
+        capture::capture_monitor_in_sync(
+            self.inner.clone(),
+            monitor.id(),
+            monitor.raw_width(),
+            monitor.raw_height(),
+            options.clone(),
+        )
+    }
+
+    /// Capture a window without fetching the shareable content again
+    pub fn capture_window(&self, window: &Window) -> XCapResult<RgbaImage> {
+        self.capture_window_with(window, &CaptureOptions::default())
+    }
+
+    /// Capture a window with explicit options
+    pub fn capture_window_with(&self, window: &Window, options: &CaptureOptions) -> XCapResult<RgbaImage> {
+        capture::capture_window_in_sync(self.inner.clone(), window.raw_id(), options.clone())
+    }
+}
+
+impl std::fmt::Debug for Content {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Content")
+            .field("displays", &self.inner.displays().len())
+            .field("windows", &self.inner.windows().len())
+            .finish()
+    }
+}
@@ -0,0 +1,104 @@
+//! Mouse cursor location helpers
+
+use std::ffi::c_void;
+
+use cidre::cg;
+use image::RgbaImage;
+
+use crate::ffi::CFRelease;
+
+// FFI bindings for reading the cursor location (not exposed by cidre)
+extern "C" {
+    fn CGEventCreate(source: *const c_void) -> *mut c_void;
+    fn CGEventGetLocation(event: *const c_void) -> cg::Point;
+}
+
+/// Get the current mouse location in global coordinates (top-left origin, points)
+pub fn location() -> Option<(f64, f64)> {
+    unsafe {
+        let event = CGEventCreate(std::ptr::null());
+        if event.is_null() {
+            return None;
+        }
+        let point = CGEventGetLocation(event);
+        CFRelease(event);
+        Some((point.x, point.y))
+    }
+}
+
+/// Convert a global cursor location to pixel coordinates within a captured region
+///
+/// `region` is the captured area in global points and `image_size` the size of the
+/// resulting image in pixels. Returns `None` if the cursor is outside the region.
+pub fn to_image_pixels(cursor: (f64, f64), region: cg::Rect, image_size: (u32, u32)) -> Option<(u32, u32)> {
+    let (cx, cy) = cursor;
+    let rx = cx - region.origin.x;
+    let ry = cy - region.origin.y;
+    if rx < 0.0 || ry < 0.0 || rx >= region.size.width || ry >= region.size.height {
+        return None;
+    }
+
+    let scale_x = image_size.0 as f64 / region.size.width;
+    let scale_y = image_size.1 as f64 / region.size.height;
+    let px = ((rx * scale_x) as u32).min(image_size.0.saturating_sub(1));
+    let py = ((ry * scale_y) as u32).min(image_size.1.saturating_sub(1));
+    Some((px, py))
+}
+
+/// Draw a cursor bitmap at the current mouse location within a captured region
+///
+/// `region` is the area `image` covers in global points. Does nothing if the
+/// mouse is outside the region.
+pub fn composite(image: &mut RgbaImage, cursor_image: &RgbaImage, region: cg::Rect) {
+    if let Some(position) = location().and_then(|location| to_image_pixels(location, region, image.dimensions())) {
+        draw_at(image, cursor_image, position);
+    }
+}
+
+/// Alpha-blend a cursor bitmap with its top-left corner at `position`, clipped to the image
+fn draw_at(image: &mut RgbaImage, cursor_image: &RgbaImage, position: (u32, u32)) {
+    image::imageops::overlay(image, cursor_image, position.0 as i64, position.1 as i64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, w: f64, h: f64) -> cg::Rect {
+        cg::Rect {
+            origin: cg::Point { x, y },
+            size: cg::Size { width: w, height: h },
+        }
+    }
+
+    #[test]
+    fn test_draw_at_blends_and_clips() {
+        let mut image = RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 0, 255]));
+        let cursor = RgbaImage::from_pixel(2, 2, image::Rgba([255, 255, 255, 255]));
+        draw_at(&mut image, &cursor, (3, 1));
+        assert_eq!(image.get_pixel(3, 1), &image::Rgba([255, 255, 255, 255]));
+        assert_eq!(image.get_pixel(3, 2), &image::Rgba([255, 255, 255, 255]));
+        assert_eq!(image.get_pixel(2, 1), &image::Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_to_image_pixels_scaled() {
+        let region = rect(0.0, 0.0, 1440.0, 900.0);
+        assert_eq!(to_image_pixels((100.0, 50.0), region, (2880, 1800)), Some((200, 100)));
+    }
+
+    #[test]
+    fn test_to_image_pixels_offset_display() {
+        // Secondary display to the left of the primary
+        let region = rect(-1920.0, 0.0, 1920.0, 1080.0);
+        assert_eq!(to_image_pixels((-1920.0, 0.0), region, (1920, 1080)), Some((0, 0)));
+        assert_eq!(to_image_pixels((10.0, 10.0), region, (1920, 1080)), None);
+    }
+
+    #[test]
+    fn test_to_image_pixels_outside() {
+        let region = rect(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(to_image_pixels((100.0, 50.0), region, (100, 100)), None);
+        assert_eq!(to_image_pixels((-1.0, 50.0), region, (100, 100)), None);
+    }
+}
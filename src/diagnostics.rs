@@ -0,0 +1,119 @@
+//! Environment report for bug reports
+
+use std::fmt;
+
+use cidre::ns;
+use tracing::debug;
+
+use crate::capture;
+use crate::content::Content;
+use crate::options::{CaptureOptions, ScalingMode};
+use crate::permission::{permission_status, PermissionStatus};
+
+/// Result of [`self_test`]
+///
+/// The [`Display`](fmt::Display) output is one `key: value` line per field,
+/// meant to be pasted into a bug report as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// macOS version, e.g. `14.5.0`
+    pub os_version: String,
+    /// Result of [`crate::is_supported`]
+    pub supported: bool,
+    /// Screen recording permission, as reported by [`permission_status`]
+    pub permission: PermissionStatus,
+    /// Whether `SCScreenshotManager` (macOS 14.0+) is used for single frames
+    pub screenshot_manager: bool,
+    /// Number of displays ScreenCaptureKit reported, if the content fetch succeeded
+    pub monitor_count: Option<usize>,
+    /// Whether a 1x1 capture of the first monitor succeeded
+    pub capture_ok: bool,
+    /// Errors hit along the way, in the order they occurred
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "sck-rs {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(f, "os_version: {}", self.os_version)?;
+        writeln!(f, "supported: {}", self.supported)?;
+        writeln!(f, "permission: {:?}", self.permission)?;
+        writeln!(f, "screenshot_manager: {}", self.screenshot_manager)?;
+        match self.monitor_count {
+            Some(count) => writeln!(f, "monitor_count: {}", count)?,
+            None => writeln!(f, "monitor_count: unknown")?,
+        }
+        writeln!(f, "capture_ok: {}", self.capture_ok)?;
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the capture environment and report what works
+///
+/// Collects the OS version, permission state and API availability, lists the
+/// displays and captures the first one scaled down to a single pixel. Never
+/// fails: problems are recorded in [`SelfTestReport::errors`]. Requesting
+/// permission is left to the caller, so this never shows a system prompt.
+pub fn self_test() -> SelfTestReport {
+    let version = ns::ProcessInfo::current().os_version();
+    let mut report = SelfTestReport {
+        os_version: format!("{}.{}.{}", version.major, version.minor, version.patch),
+        supported: crate::is_supported(),
+        permission: permission_status(),
+        screenshot_manager: capture::screenshot_manager_available(),
+        monitor_count: None,
+        capture_ok: false,
+        errors: Vec::new(),
+    };
+
+    let monitors = match Content::current().and_then(|content| content.monitors()) {
+        Ok(monitors) => monitors,
+        Err(e) => {
+            report.errors.push(format!("list monitors: {}", e));
+            debug!("Self test: {}", report);
+            return report;
+        }
+    };
+    report.monitor_count = Some(monitors.len());
+
+    if let Some(monitor) = monitors.first() {
+        let options = CaptureOptions {
+            scaling: Some(ScalingMode::Stretch),
+            ..Default::default()
+        };
+        match capture::capture_monitor_scaled_sync(monitor.id(), 1, 1, options) {
+            Ok(_) => report.capture_ok = true,
+            Err(e) => report.errors.push(format!("capture monitor {}: {}", monitor.id(), e)),
+        }
+    }
+
+    debug!("Self test: {}", report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_display() {
+        let report = SelfTestReport {
+            os_version: "14.5.0".to_string(),
+            supported: true,
+            permission: PermissionStatus::Denied,
+            screenshot_manager: true,
+            monitor_count: None,
+            capture_ok: false,
+            errors: vec!["list monitors: Permission denied".to_string()],
+        };
+
+        let text = report.to_string();
+        assert!(text.contains("os_version: 14.5.0\n"));
+        assert!(text.contains("permission: Denied\n"));
+        assert!(text.contains("monitor_count: unknown\n"));
+        assert!(text.ends_with("error: list monitors: Permission denied\n"));
+    }
+}
@@ -2,9 +2,101 @@
 
 use std::fmt;
 
+use cidre::{arc, ns};
+
+/// `SCStreamErrorUserDeclined`, returned when screen recording is not allowed
+pub(crate) const USER_DECLINED: isize = -3801;
+
+/// Details of an `NSError` returned by ScreenCaptureKit or another Apple framework
+///
+/// Attached as the [`std::error::Error::source`] of an [`XCapError`] so the
+/// original domain and code survive the conversion. `NSError` itself is not
+/// `Send`, so its fields are copied out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsError {
+    domain: String,
+    code: isize,
+    description: String,
+}
+
+impl OsError {
+    /// Create error details from their parts
+    pub fn new<D: Into<String>, S: Into<String>>(domain: D, code: isize, description: S) -> Self {
+        Self {
+            domain: domain.into(),
+            code,
+            description: description.into(),
+        }
+    }
+
+    /// Copy the details out of an `NSError`
+    pub(crate) fn from_ns(error: &ns::Error) -> Self {
+        Self::new(
+            error.domain().to_string(),
+            error.code(),
+            error.localized_description().to_string(),
+        )
+    }
+
+    /// Get the error domain (e.g. `com.apple.ScreenCaptureKit.SCStreamErrorDomain`)
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Get the error code within its domain (e.g. -3801 for a declined permission)
+    pub fn code(&self) -> isize {
+        self.code
+    }
+
+    /// Get the localized description
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Display for OsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} {})", self.description, self.domain, self.code)
+    }
+}
+
+impl std::error::Error for OsError {}
+
+/// Category of an [`XCapError`], for callers that need to branch on the cause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Screen recording permission is missing
+    PermissionDenied,
+    /// ScreenCaptureKit failed to produce a frame
+    CaptureFailed,
+    /// The requested window no longer exists
+    WindowNotFound,
+    /// The requested monitor no longer exists
+    MonitorNotFound,
+    /// No capturable windows were found
+    NoWindows,
+    /// No capturable monitors were found
+    NoMonitors,
+    /// Displays are connected but none is active, e.g. a laptop with its lid
+    /// closed and no external display
+    NoActiveDisplay,
+    /// An AppKit call needed the main thread and could not be run there
+    ThreadingViolation,
+    /// An operation did not complete in time
+    Timeout,
+    /// The operation was cancelled through a [`crate::CancellationToken`]
+    Cancelled,
+    /// The requested output is larger than the GPU can render in one frame
+    TooLarge,
+    /// Any other error
+    Other,
+}
+
 /// Error type for xcap-sck operations
 #[derive(Debug)]
 pub struct XCapError {
+    kind: ErrorKind,
     message: String,
     source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
@@ -12,7 +104,13 @@ pub struct XCapError {
 impl XCapError {
     /// Create a new error with a message
     pub fn new<S: Into<String>>(message: S) -> Self {
+        Self::with_kind(ErrorKind::Other, message)
+    }
+
+    /// Create a new error of a specific kind
+    pub fn with_kind<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
         Self {
+            kind,
             message: message.into(),
             source: None,
         }
@@ -25,39 +123,128 @@ impl XCapError {
         E: std::error::Error + Send + Sync + 'static,
     {
         Self {
+            kind: ErrorKind::Other,
             message: message.into(),
             source: Some(Box::new(source)),
         }
     }
 
+    /// Get the category of this error
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Get the code of the underlying OS error, if there is one
+    ///
+    /// This is the `NSError` code ScreenCaptureKit reported (e.g. -3801 when
+    /// the user declined screen recording), kept even when the error was
+    /// mapped to a broader [`ErrorKind`] such as [`ErrorKind::PermissionDenied`].
+    /// The full details are available through [`OsError`] as the error's source.
+    pub fn os_code(&self) -> Option<isize> {
+        self.os_error().map(OsError::code)
+    }
+
+    /// Get the underlying OS error, if there is one
+    pub fn os_error(&self) -> Option<&OsError> {
+        self.source.as_ref().and_then(|source| source.downcast_ref::<OsError>())
+    }
+
+    /// Check whether retrying the same operation may succeed
+    ///
+    /// True for transient failures such as a dropped frame or a timeout. False
+    /// for terminal ones like missing permission or a monitor that no longer
+    /// exists, where retrying only repeats the error. A declined permission is
+    /// never retryable, even when it surfaced as a capture failure.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.kind, ErrorKind::CaptureFailed | ErrorKind::Timeout) && self.os_code() != Some(USER_DECLINED)
+    }
+
     /// Create an error for when no windows are found
     pub fn no_windows() -> Self {
-        Self::new("No windows found")
+        Self::with_kind(ErrorKind::NoWindows, "No windows found")
     }
 
     /// Create an error for when no monitors are found
     pub fn no_monitors() -> Self {
-        Self::new("No monitors found")
+        Self::with_kind(ErrorKind::NoMonitors, "No monitors found")
+    }
+
+    /// Create an error for when displays are connected but none is active
+    pub fn no_active_display() -> Self {
+        Self::with_kind(
+            ErrorKind::NoActiveDisplay,
+            "No active display: the built-in display is off (lid closed?) and no external display is connected. Open the lid or connect a display",
+        )
     }
 
     /// Create an error for permission denied
     pub fn permission_denied() -> Self {
-        Self::new("Screen recording permission not granted. Grant access in System Settings > Privacy & Security > Screen Recording")
+        Self::with_kind(
+            ErrorKind::PermissionDenied,
+            "Screen recording permission not granted. Grant access in System Settings > Privacy & Security > Screen Recording",
+        )
+    }
+
+    /// Create an error for permission denied, keeping the OS error that revealed it
+    pub(crate) fn permission_denied_os(error: OsError) -> Self {
+        let mut err = Self::permission_denied();
+        err.source = Some(Box::new(error));
+        err
     }
 
     /// Create an error for capture failure
     pub fn capture_failed<S: Into<String>>(details: S) -> Self {
-        Self::new(format!("Capture failed: {}", details.into()))
+        Self::with_kind(ErrorKind::CaptureFailed, format!("Capture failed: {}", details.into()))
+    }
+
+    /// Create an error for capture failure caused by an `NSError`
+    ///
+    /// The `NSError` is preserved as the error source.
+    pub fn capture_failed_ns<S: Into<String>>(details: S, error: &ns::Error) -> Self {
+        Self::capture_failed_os(details, OsError::from_ns(error))
+    }
+
+    /// Create an error for capture failure caused by an already copied `NSError`
+    pub(crate) fn capture_failed_os<S: Into<String>>(details: S, error: OsError) -> Self {
+        let mut err = Self::with_source(format!("Capture failed: {}", details.into()), error);
+        err.kind = ErrorKind::CaptureFailed;
+        err
     }
 
     /// Create an error for window not found
     pub fn window_not_found(window_id: u32) -> Self {
-        Self::new(format!("Window with id {} not found", window_id))
+        Self::with_kind(ErrorKind::WindowNotFound, format!("Window with id {} not found", window_id))
     }
 
     /// Create an error for monitor not found
     pub fn monitor_not_found(monitor_id: u32) -> Self {
-        Self::new(format!("Monitor with id {} not found", monitor_id))
+        Self::with_kind(ErrorKind::MonitorNotFound, format!("Monitor with id {} not found", monitor_id))
+    }
+
+    /// Create an error for an operation that did not complete in time
+    pub fn timeout<S: Into<String>>(details: S) -> Self {
+        Self::with_kind(ErrorKind::Timeout, format!("Timed out: {}", details.into()))
+    }
+
+    /// Create an error for an operation cancelled by the caller
+    pub fn cancelled() -> Self {
+        Self::with_kind(ErrorKind::Cancelled, "Capture cancelled")
+    }
+
+    /// Create an error for an output size above the GPU texture limit
+    pub fn too_large(width: u32, height: u32, limit: u32) -> Self {
+        Self::with_kind(
+            ErrorKind::TooLarge,
+            format!(
+                "Capture size {}x{} exceeds the maximum texture size of {}x{}",
+                width, height, limit, limit
+            ),
+        )
+    }
+
+    /// Create an error for an AppKit call that could not run on the main thread
+    pub fn threading_violation<S: Into<String>>(details: S) -> Self {
+        Self::with_kind(ErrorKind::ThreadingViolation, format!("Threading violation: {}", details.into()))
     }
 }
 
@@ -95,6 +282,18 @@ impl From<std::io::Error> for XCapError {
     }
 }
 
+impl From<OsError> for XCapError {
+    fn from(e: OsError) -> Self {
+        Self::with_source("System error", e)
+    }
+}
+
+impl From<arc::R<ns::Error>> for XCapError {
+    fn from(e: arc::R<ns::Error>) -> Self {
+        OsError::from_ns(&e).into()
+    }
+}
+
 /// Result type for xcap-sck operations
 pub type XCapResult<T> = Result<T, XCapError>;
 
@@ -123,6 +322,71 @@ mod tests {
         assert!(format!("{}", err).contains("permission"));
     }
 
+    #[test]
+    fn test_os_error_preserved_as_source() {
+        let os_err = OsError::new("com.apple.ScreenCaptureKit.SCStreamErrorDomain", USER_DECLINED, "User declined");
+        let err = XCapError::with_source("Capture failed: start", os_err.clone());
+        let source = err.source().unwrap().downcast_ref::<OsError>().unwrap();
+        assert_eq!(source, &os_err);
+        assert_eq!(source.code(), USER_DECLINED);
+        assert!(format!("{}", err).contains("-3801"));
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        assert_eq!(XCapError::new("x").kind(), ErrorKind::Other);
+        assert_eq!(XCapError::permission_denied().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(XCapError::capture_failed("x").kind(), ErrorKind::CaptureFailed);
+        assert_eq!(XCapError::window_not_found(1).kind(), ErrorKind::WindowNotFound);
+        assert_eq!(XCapError::monitor_not_found(1).kind(), ErrorKind::MonitorNotFound);
+        assert_eq!(XCapError::no_windows().kind(), ErrorKind::NoWindows);
+        assert_eq!(XCapError::no_monitors().kind(), ErrorKind::NoMonitors);
+        assert_eq!(XCapError::no_active_display().kind(), ErrorKind::NoActiveDisplay);
+        assert_eq!(XCapError::threading_violation("x").kind(), ErrorKind::ThreadingViolation);
+        assert_eq!(XCapError::timeout("x").kind(), ErrorKind::Timeout);
+        assert_eq!(XCapError::cancelled().kind(), ErrorKind::Cancelled);
+        assert_eq!(XCapError::too_large(20000, 1080, 16384).kind(), ErrorKind::TooLarge);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(XCapError::capture_failed("dropped frame").is_retryable());
+        assert!(XCapError::capture_failed_os("start", OsError::new("d", -3805, "x")).is_retryable());
+        assert!(XCapError::timeout("x").is_retryable());
+
+        assert!(!XCapError::permission_denied().is_retryable());
+        assert!(!XCapError::monitor_not_found(1).is_retryable());
+        assert!(!XCapError::window_not_found(1).is_retryable());
+        assert!(!XCapError::no_windows().is_retryable());
+        assert!(!XCapError::no_monitors().is_retryable());
+        assert!(!XCapError::no_active_display().is_retryable());
+        assert!(!XCapError::threading_violation("x").is_retryable());
+        assert!(!XCapError::cancelled().is_retryable());
+        assert!(!XCapError::new("x").is_retryable());
+
+        // A permission revoked mid-capture comes back from SCK as a stream error
+        let declined = OsError::new("com.apple.ScreenCaptureKit.SCStreamErrorDomain", USER_DECLINED, "User declined");
+        let err = XCapError::capture_failed_os("start", declined);
+        assert_eq!(err.kind(), ErrorKind::CaptureFailed);
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_os_code() {
+        let declined = OsError::new("com.apple.ScreenCaptureKit.SCStreamErrorDomain", USER_DECLINED, "User declined");
+        let err = XCapError::permission_denied_os(declined);
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(err.os_code(), Some(USER_DECLINED));
+        assert_eq!(err.os_error().unwrap().domain(), "com.apple.ScreenCaptureKit.SCStreamErrorDomain");
+
+        let err = XCapError::capture_failed_os("start", OsError::new("d", -3805, "x"));
+        assert_eq!(err.os_code(), Some(-3805));
+
+        assert_eq!(XCapError::permission_denied().os_code(), None);
+        let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(XCapError::with_source("read", io_err).os_code(), None);
+    }
+
     #[test]
     fn test_from_string() {
         let err: XCapError = "test error".into();
@@ -0,0 +1,164 @@
+//! Objective-C runtime and CoreFoundation bindings for the APIs cidre does not wrap
+//!
+//! Modules that talk to AppKit or read CoreFoundation values directly share
+//! these declarations and helpers instead of declaring their own.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+pub(crate) type Id = *mut c_void;
+pub(crate) type Sel = *const c_void;
+pub(crate) type CFTypeRef = *const c_void;
+pub(crate) type CFIndex = isize;
+
+pub(crate) const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+pub(crate) const CF_NUMBER_SINT64_TYPE: CFIndex = 4;
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {}
+
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Sel;
+    /// Cast to the concrete signature before calling
+    pub(crate) fn objc_msgSend();
+    fn objc_autoreleasePoolPush() -> *mut c_void;
+    fn objc_autoreleasePoolPop(pool: *mut c_void);
+
+    pub(crate) fn CFRetain(cf: CFTypeRef) -> CFTypeRef;
+    pub(crate) fn CFRelease(cf: CFTypeRef);
+    pub(crate) fn CFArrayGetCount(array: CFTypeRef) -> CFIndex;
+    pub(crate) fn CFArrayGetValueAtIndex(array: CFTypeRef, index: CFIndex) -> CFTypeRef;
+    pub(crate) fn CFBooleanGetValue(boolean: CFTypeRef) -> bool;
+    pub(crate) fn CFDataGetBytePtr(data: CFTypeRef) -> *const u8;
+    pub(crate) fn CFDataGetLength(data: CFTypeRef) -> CFIndex;
+    pub(crate) fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+    pub(crate) fn CFNumberGetValue(number: CFTypeRef, number_type: CFIndex, value: *mut c_void) -> bool;
+    pub(crate) fn CFGetTypeID(cf: CFTypeRef) -> usize;
+    pub(crate) fn CFBooleanGetTypeID() -> usize;
+    pub(crate) fn CFStringCreateWithCString(allocator: CFTypeRef, c_str: *const c_char, encoding: u32) -> CFTypeRef;
+    fn CFStringGetLength(string: CFTypeRef) -> CFIndex;
+    fn CFStringGetMaximumSizeForEncoding(length: CFIndex, encoding: u32) -> CFIndex;
+    fn CFStringGetCString(string: CFTypeRef, buffer: *mut c_char, size: CFIndex, encoding: u32) -> bool;
+}
+
+/// Lock a mutex shared with an Objective-C callback, ignoring poisoning
+///
+/// Callbacks must not panic across the FFI boundary, and the state these
+/// mutexes protect stays usable even if a holder panicked.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Run `f` inside an autorelease pool, draining it afterwards
+pub(crate) fn autorelease_pool<T>(f: impl FnOnce() -> T) -> T {
+    unsafe {
+        let pool = objc_autoreleasePoolPush();
+        let result = f();
+        objc_autoreleasePoolPop(pool);
+        result
+    }
+}
+
+/// Look up an Objective-C class by its nul-terminated name
+pub(crate) unsafe fn class(name: &[u8]) -> Id {
+    objc_getClass(name.as_ptr() as *const c_char)
+}
+
+/// Register a selector from its nul-terminated name
+pub(crate) unsafe fn sel(name: &[u8]) -> Sel {
+    sel_registerName(name.as_ptr() as *const c_char)
+}
+
+pub(crate) unsafe fn send_id(obj: Id, sel: Sel) -> Id {
+    let f: unsafe extern "C" fn(Id, Sel) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel)
+}
+
+pub(crate) unsafe fn send_id_usize(obj: Id, sel: Sel, arg: usize) -> Id {
+    let f: unsafe extern "C" fn(Id, Sel, usize) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel, arg)
+}
+
+pub(crate) unsafe fn send_id_i32(obj: Id, sel: Sel, arg: i32) -> Id {
+    let f: unsafe extern "C" fn(Id, Sel, i32) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel, arg)
+}
+
+pub(crate) unsafe fn send_id_id(obj: Id, sel: Sel, arg: Id) -> Id {
+    let f: unsafe extern "C" fn(Id, Sel, Id) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel, arg)
+}
+
+pub(crate) unsafe fn send_usize(obj: Id, sel: Sel) -> usize {
+    let f: unsafe extern "C" fn(Id, Sel) -> usize = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel)
+}
+
+pub(crate) unsafe fn send_u32(obj: Id, sel: Sel) -> u32 {
+    let f: unsafe extern "C" fn(Id, Sel) -> u32 = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel)
+}
+
+pub(crate) unsafe fn send_f64(obj: Id, sel: Sel) -> f64 {
+    let f: unsafe extern "C" fn(Id, Sel) -> f64 = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(obj, sel)
+}
+
+/// Create an autoreleased NSString from a nul-terminated byte string
+pub(crate) unsafe fn ns_string(bytes: &[u8]) -> Id {
+    let f: unsafe extern "C" fn(Id, Sel, *const c_char) -> Id =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    f(class(b"NSString\0"), sel(b"stringWithUTF8String:\0"), bytes.as_ptr() as *const c_char)
+}
+
+/// Copy an NSString into a Rust string
+pub(crate) unsafe fn from_ns_string(ns_string: Id) -> Option<String> {
+    if ns_string.is_null() {
+        return None;
+    }
+    let f: unsafe extern "C" fn(Id, Sel) -> *const c_char = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    let utf8 = f(ns_string, sel(b"UTF8String\0"));
+    if utf8.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(utf8).to_string_lossy().into_owned())
+}
+
+/// Look up a dictionary value by a nul-terminated string key, without retaining it
+pub(crate) unsafe fn dictionary_value(dict: CFTypeRef, key: &[u8]) -> CFTypeRef {
+    let key = CFStringCreateWithCString(std::ptr::null(), key.as_ptr() as *const c_char, CF_STRING_ENCODING_UTF8);
+    if key.is_null() {
+        return std::ptr::null();
+    }
+    let value = CFDictionaryGetValue(dict, key);
+    CFRelease(key);
+    value
+}
+
+/// Read a CFBoolean, treating a missing or differently typed value as `false`
+pub(crate) unsafe fn bool_value(value: CFTypeRef) -> bool {
+    !value.is_null() && CFGetTypeID(value) == CFBooleanGetTypeID() && CFBooleanGetValue(value)
+}
+
+/// Read a CFNumber as an `i64`, or `None` if it is missing
+pub(crate) unsafe fn i64_value(number: CFTypeRef) -> Option<i64> {
+    if number.is_null() {
+        return None;
+    }
+    let mut out: i64 = 0;
+    CFNumberGetValue(number, CF_NUMBER_SINT64_TYPE, &mut out as *mut i64 as *mut c_void).then_some(out)
+}
+
+/// Copy a CFString into a Rust string
+pub(crate) unsafe fn from_cf_string(string: CFTypeRef) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    let size = CFStringGetMaximumSizeForEncoding(CFStringGetLength(string), CF_STRING_ENCODING_UTF8) + 1;
+    let mut buffer = vec![0 as c_char; size.max(1) as usize];
+    if !CFStringGetCString(string, buffer.as_mut_ptr(), buffer.len() as CFIndex, CF_STRING_ENCODING_UTF8) {
+        return None;
+    }
+    Some(CStr::from_ptr(buffer.as_ptr()).to_string_lossy().into_owned())
+}
@@ -0,0 +1,75 @@
+//! Waiting for an application to become frontmost
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use cidre::ns;
+use tracing::debug;
+
+use crate::error::{XCapError, XCapResult};
+
+/// Get the PID of the frontmost application using cidre's NSWorkspace API
+///
+/// Returns -1 if no application is active.
+pub fn frontmost_pid() -> i32 {
+    let workspace = ns::Workspace::shared();
+    let apps = workspace.running_apps();
+    for i in 0..apps.len() {
+        if let Ok(app) = apps.get(i) {
+            if app.is_active() {
+                return app.pid();
+            }
+        }
+    }
+    -1
+}
+
+/// Check whether the application with `pid` is hidden (Cmd-H)
+///
+/// Returns false if no running application has that PID.
+pub fn is_app_hidden(pid: i32) -> bool {
+    let workspace = ns::Workspace::shared();
+    let apps = workspace.running_apps();
+    for i in 0..apps.len() {
+        if let Ok(app) = apps.get(i) {
+            if app.pid() == pid {
+                return app.is_hidden();
+            }
+        }
+    }
+    false
+}
+
+/// Block until the application with `pid` is frontmost
+///
+/// Listens for `NSWorkspaceDidActivateApplicationNotification` instead of
+/// polling. Returns immediately if the application is already frontmost, and a
+/// [`crate::ErrorKind::Timeout`] error if `timeout` elapses first (`None` waits
+/// forever).
+pub fn wait_until_frontmost(pid: i32, timeout: Option<Duration>) -> XCapResult<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut center = ns::Workspace::shared().notification_center();
+    // Registered before the first check so an activation in between is not missed
+    let _observer = center.add_observer_guard(ns::workspace::notification::did_activate_app(), None, None, move |_| {
+        let _ = tx.send(());
+    });
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        if frontmost_pid() == pid {
+            return Ok(());
+        }
+
+        let received = match deadline {
+            None => rx.recv().is_ok(),
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                rx.recv_timeout(remaining).is_ok()
+            }
+        };
+        if !received {
+            return Err(XCapError::timeout(format!("Application {} did not become frontmost", pid)));
+        }
+        debug!("An application was activated, checking for {}", pid);
+    }
+}
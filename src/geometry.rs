@@ -0,0 +1,173 @@
+//! Rectangles with an explicit coordinate convention
+//!
+//! ScreenCaptureKit and `CGWindowList` report global coordinates in points with
+//! the origin at the top-left of the primary display and y growing downwards.
+//! AppKit (`NSScreen`, `NSWindow`) uses the bottom-left of the primary display
+//! with y growing upwards. Mixing the two silently shifts crops by a monitor.
+
+/// Which corner of the primary display global coordinates are measured from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Origin {
+    /// Top-left of the primary display, y grows downwards (ScreenCaptureKit, CoreGraphics window lists)
+    TopLeft,
+    /// Bottom-left of the primary display, y grows upwards (AppKit)
+    BottomLeft,
+}
+
+/// A rectangle in global points, tagged with its coordinate convention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds {
+    /// Left edge
+    pub x: i32,
+    /// Edge nearest the origin: the top for [`Origin::TopLeft`], the bottom for [`Origin::BottomLeft`]
+    pub y: i32,
+    /// Width in points
+    pub width: u32,
+    /// Height in points
+    pub height: u32,
+    /// Convention `x` and `y` are expressed in
+    pub origin: Origin,
+}
+
+impl Bounds {
+    /// Create bounds with a top-left origin
+    pub fn top_left(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            origin: Origin::TopLeft,
+        }
+    }
+
+    /// Convert to a top-left origin
+    ///
+    /// `primary_height` is the height in points of the primary display (the
+    /// one at global 0,0), which both conventions are anchored to.
+    pub fn to_top_left(self, primary_height: u32) -> Self {
+        match self.origin {
+            Origin::TopLeft => self,
+            Origin::BottomLeft => Self {
+                y: self.flip_y(primary_height),
+                origin: Origin::TopLeft,
+                ..self
+            },
+        }
+    }
+
+    /// Convert to a bottom-left origin
+    ///
+    /// See [`Bounds::to_top_left`] for `primary_height`.
+    pub fn to_bottom_left(self, primary_height: u32) -> Self {
+        match self.origin {
+            Origin::BottomLeft => self,
+            Origin::TopLeft => Self {
+                y: self.flip_y(primary_height),
+                origin: Origin::BottomLeft,
+                ..self
+            },
+        }
+    }
+
+    /// Mirror the near edge across the primary display's horizontal axis
+    fn flip_y(&self, primary_height: u32) -> i32 {
+        primary_height as i32 - self.y - self.height as i32
+    }
+
+    /// Check whether a point in the same convention lies inside the bounds
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && (x as i64) < self.x as i64 + self.width as i64
+            && (y as i64) < self.y as i64 + self.height as i64
+    }
+
+    /// Express these bounds relative to a containing rectangle, clipped to it
+    ///
+    /// The result is in the container's local coordinates with a top-left
+    /// origin, which is what cropping a captured image needs. Returns `None`
+    /// if the rectangles do not overlap or use different conventions.
+    pub fn relative_to(&self, container: &Bounds) -> Option<Bounds> {
+        if self.origin != container.origin {
+            return None;
+        }
+
+        let left = self.x.max(container.x) as i64;
+        let right = (self.x as i64 + self.width as i64).min(container.x as i64 + container.width as i64);
+        let near = self.y.max(container.y) as i64;
+        let far = (self.y as i64 + self.height as i64).min(container.y as i64 + container.height as i64);
+        if left >= right || near >= far {
+            return None;
+        }
+
+        let local_y = match self.origin {
+            Origin::TopLeft => near - container.y as i64,
+            // Image rows count from the top, i.e. from the container's far edge
+            Origin::BottomLeft => container.y as i64 + container.height as i64 - far,
+        };
+
+        Some(Bounds::top_left(
+            (left - container.x as i64) as i32,
+            local_y as i32,
+            (right - left) as u32,
+            (far - near) as u32,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flip_round_trip() {
+        // 1440x900 primary display, window 100pt from the top
+        let window = Bounds::top_left(10, 100, 400, 300);
+        let flipped = window.to_bottom_left(900);
+        assert_eq!(flipped.origin, Origin::BottomLeft);
+        assert_eq!(flipped.y, 500);
+        assert_eq!(flipped.to_top_left(900), window);
+        // Converting to the same convention is a no-op
+        assert_eq!(window.to_top_left(900), window);
+    }
+
+    #[test]
+    fn test_flip_monitor_above_primary() {
+        // A 1080pt-tall monitor stacked above a 900pt primary has negative top-left y
+        let monitor = Bounds::top_left(0, -1080, 1920, 1080);
+        assert_eq!(monitor.to_bottom_left(900).y, 900);
+    }
+
+    #[test]
+    fn test_contains() {
+        let bounds = Bounds::top_left(-1920, 0, 1920, 1080);
+        assert!(bounds.contains(-1920, 0));
+        assert!(bounds.contains(-1, 1079));
+        assert!(!bounds.contains(0, 0));
+    }
+
+    #[test]
+    fn test_relative_to_secondary_monitor() {
+        let monitor = Bounds::top_left(-1920, -200, 1920, 1080);
+        let window = Bounds::top_left(-100, 800, 300, 200);
+        // Clipped at the monitor's right and bottom edges
+        assert_eq!(window.relative_to(&monitor), Some(Bounds::top_left(1820, 1000, 100, 80)));
+    }
+
+    #[test]
+    fn test_relative_to_bottom_left() {
+        let monitor = Bounds::top_left(0, 0, 1440, 900).to_bottom_left(900);
+        let window = Bounds::top_left(10, 100, 400, 300).to_bottom_left(900);
+        assert_eq!(window.relative_to(&monitor), Some(Bounds::top_left(10, 100, 400, 300)));
+    }
+
+    #[test]
+    fn test_relative_to_mismatched_or_disjoint() {
+        let monitor = Bounds::top_left(0, 0, 1440, 900);
+        assert_eq!(Bounds::top_left(2000, 0, 10, 10).relative_to(&monitor), None);
+        assert_eq!(monitor.to_bottom_left(900).relative_to(&monitor), None);
+    }
+}
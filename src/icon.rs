@@ -0,0 +1,116 @@
+//! Application icons via NSRunningApplication
+
+use std::ffi::c_void;
+
+use image::RgbaImage;
+
+use crate::color;
+use crate::error::{XCapError, XCapResult};
+use crate::ffi::{self, class, objc_msgSend, sel, send_id, send_id_i32, CFTypeRef, Id, Sel};
+
+/// `kCGImageAlphaPremultipliedLast`: RGBA byte order with premultiplied alpha
+const BITMAP_ALPHA_PREMULTIPLIED_LAST: u32 = 1;
+
+#[repr(C)]
+struct NsRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+// FFI bindings for the CoreGraphics calls used to rasterize the icon
+extern "C" {
+    static kCGColorSpaceSRGB: CFTypeRef;
+    fn CGColorSpaceCreateWithName(name: CFTypeRef) -> CFTypeRef;
+    fn CGColorSpaceRelease(space: CFTypeRef);
+    fn CGBitmapContextCreate(
+        data: *mut c_void,
+        width: usize,
+        height: usize,
+        bits_per_component: usize,
+        bytes_per_row: usize,
+        space: CFTypeRef,
+        bitmap_info: u32,
+    ) -> CFTypeRef;
+    fn CGContextDrawImage(context: CFTypeRef, rect: NsRect, image: CFTypeRef);
+    fn CGContextRelease(context: CFTypeRef);
+}
+
+/// Render the icon of the running application `pid` as a `size` x `size` image
+///
+/// Uses `NSRunningApplication.icon`, which covers apps without a bundle too,
+/// and lets AppKit pick the best representation for the size before drawing
+/// it into an sRGB bitmap. The result has straight alpha.
+pub fn app_icon(pid: i32, size: u32) -> XCapResult<RgbaImage> {
+    if size == 0 {
+        return Err(XCapError::new("Icon size must be at least 1"));
+    }
+
+    ffi::autorelease_pool(|| unsafe { render_app_icon(pid, size) })
+}
+
+unsafe fn render_app_icon(pid: i32, size: u32) -> XCapResult<RgbaImage> {
+    let cls = class(b"NSRunningApplication\0");
+    if cls.is_null() {
+        return Err(XCapError::new("NSRunningApplication is unavailable"));
+    }
+    let app = send_id_i32(cls, sel(b"runningApplicationWithProcessIdentifier:\0"), pid);
+    if app.is_null() {
+        return Err(XCapError::new(format!("No running application with pid {}", pid)));
+    }
+    let icon = send_id(app, sel(b"icon\0"));
+    if icon.is_null() {
+        return Err(XCapError::new(format!("Application {} has no icon", pid)));
+    }
+
+    // CGImageForProposedRect:context:hints: returns an image owned by the NSImage
+    let mut proposed = NsRect {
+        x: 0.0,
+        y: 0.0,
+        width: size as f64,
+        height: size as f64,
+    };
+    let f: unsafe extern "C" fn(Id, Sel, *mut NsRect, Id, Id) -> CFTypeRef =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    let cg_image = f(
+        icon,
+        sel(b"CGImageForProposedRect:context:hints:\0"),
+        &mut proposed,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+    );
+    if cg_image.is_null() {
+        return Err(XCapError::new(format!("Failed to rasterize the icon of application {}", pid)));
+    }
+
+    let side = size as usize;
+    let mut pixels = vec![0u8; side * side * 4];
+    let space = CGColorSpaceCreateWithName(kCGColorSpaceSRGB);
+    let context = CGBitmapContextCreate(
+        pixels.as_mut_ptr() as *mut c_void,
+        side,
+        side,
+        8,
+        side * 4,
+        space,
+        BITMAP_ALPHA_PREMULTIPLIED_LAST,
+    );
+    CGColorSpaceRelease(space);
+    if context.is_null() {
+        return Err(XCapError::new("Failed to create a bitmap context for the icon"));
+    }
+    let rect = NsRect {
+        x: 0.0,
+        y: 0.0,
+        width: size as f64,
+        height: size as f64,
+    };
+    CGContextDrawImage(context, rect, cg_image);
+    CGContextRelease(context);
+
+    let mut image =
+        RgbaImage::from_raw(size, size, pixels).ok_or_else(|| XCapError::new("Icon bitmap has an unexpected size"))?;
+    color::unpremultiply(&mut image);
+    Ok(image)
+}
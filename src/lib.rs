@@ -33,14 +33,59 @@
 
 #![cfg(target_os = "macos")]
 
+mod ax;
 mod error;
+mod focus;
 mod window;
+mod main_thread;
 mod monitor;
+mod options;
+mod cancel;
 mod capture;
+mod cg_window;
+mod change;
+mod color;
+mod content;
+mod cursor;
+mod diagnostics;
+mod ffi;
+mod geometry;
+mod icon;
+mod permission;
+mod phash;
+mod recording;
+mod ring;
+mod screen;
+mod shared;
+mod stream;
+mod target;
+mod timing;
+mod touch_bar;
+mod watcher;
+mod yuv;
 
-pub use error::{XCapError, XCapResult};
-pub use window::Window;
-pub use monitor::Monitor;
+pub use ax::AxElement;
+pub use cancel::CancellationToken;
+pub use change::{diff_regions, PixelRect};
+pub use capture::{set_exclude_current_process, set_runtime_handle, shutdown, CgImage};
+pub use color::{encode, AlphaMode};
+pub use content::Content;
+pub use diagnostics::{self_test, SelfTestReport};
+#[cfg(feature = "synthetic-capture")]
+pub use capture::{capture_from_buffer, BufferFormat};
+pub use error::{ErrorKind, OsError, XCapError, XCapResult};
+pub use window::{capture_active_window, ListOptions, Window, WindowDiff, WindowOrder};
+pub use geometry::{Bounds, Origin};
+pub use monitor::{BudgetedCapture, DisplayKind, Monitor, ScaledCapture};
+pub use options::{BackgroundMode, CaptureOptions, CoordinateSpace, CursorMode, FlipMode, LetterboxPolicy, ScalingMode};
+pub use permission::{permission_status, probe_capture, request_permission, PermissionStatus};
+pub use recording::{Recording, RecordingConfig, VideoCodec};
+pub use ring::{RingConfig, RingHandle};
+pub use shared::{FrameHeader, SharedRegion, SHARED_HEADER_SIZE};
+pub use target::{ArmedCapture, CaptureTarget};
+pub use stream::{CaptureEvent, CaptureStats, CaptureStream, DropReason, DroppedFrame, Frame, StreamConfig};
+pub use watcher::{WindowEvent, WindowWatcher};
+pub use yuv::YuvFrame;
 
 /// Check if ScreenCaptureKit is available on this system (macOS 12.3+)
 pub fn is_supported() -> bool {
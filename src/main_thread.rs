@@ -0,0 +1,100 @@
+//! Running AppKit calls on the main thread
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::error::{XCapError, XCapResult};
+
+/// How long to wait for the main thread before giving up
+///
+/// Command-line tools often never run a main run loop, so a dispatched call may
+/// never execute. Waiting boundedly turns that case into an error instead of a hang.
+const MAIN_THREAD_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Set when a dispatched call timed out, cleared when the main queue next runs one
+///
+/// Without a main run loop every dispatch would wait out the full timeout, so
+/// once one has, later calls fail immediately. The timed-out call is still
+/// queued; if the main thread was only busy, it runs eventually and clears
+/// this flag again.
+static MAIN_UNRESPONSIVE: AtomicBool = AtomicBool::new(false);
+
+// FFI bindings for pthread and libdispatch (not exposed by cidre)
+extern "C" {
+    fn pthread_main_np() -> i32;
+    static _dispatch_main_q: c_void;
+    fn dispatch_async_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+}
+
+/// Check whether the current thread is the process's main thread
+pub fn is_main_thread() -> bool {
+    unsafe { pthread_main_np() != 0 }
+}
+
+extern "C" fn trampoline(context: *mut c_void) {
+    // SAFETY: context was created by Box::into_raw in run_on_main and is consumed exactly once
+    let work: Box<Box<dyn FnOnce() + Send>> = unsafe { Box::from_raw(context as *mut Box<dyn FnOnce() + Send>) };
+    work();
+}
+
+/// Run `f` on the main thread and return its result
+///
+/// Runs `f` directly when already on the main thread. Otherwise it is dispatched
+/// to the main queue; if the main thread does not pick it up within a short
+/// timeout (no run loop, or the main thread is blocked), a `ThreadingViolation`
+/// error is returned rather than calling the API from the wrong thread. After
+/// such a timeout, calls fail without waiting until the main queue catches up.
+pub fn run_on_main<F, T>(api: &str, f: F) -> XCapResult<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    if is_main_thread() {
+        return Ok(f());
+    }
+
+    if MAIN_UNRESPONSIVE.load(Ordering::Relaxed) {
+        return Err(XCapError::threading_violation(format!(
+            "{} must run on the main thread, and the main thread is not processing its queue",
+            api
+        )));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let work: Box<dyn FnOnce() + Send> = Box::new(move || {
+        MAIN_UNRESPONSIVE.store(false, Ordering::Relaxed);
+        let _ = tx.send(f());
+    });
+    let context = Box::into_raw(Box::new(work)) as *mut c_void;
+    unsafe {
+        dispatch_async_f(&_dispatch_main_q as *const c_void, context, trampoline);
+    }
+
+    rx.recv_timeout(MAIN_THREAD_TIMEOUT).map_err(|_| {
+        MAIN_UNRESPONSIVE.store(true, Ordering::Relaxed);
+        XCapError::threading_violation(format!(
+            "{} must run on the main thread, and the main thread did not respond within {:?}",
+            api, MAIN_THREAD_TIMEOUT
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_unresponsive_main_thread_fails_fast() {
+        // The test harness never runs the main queue
+        assert!(!is_main_thread());
+        assert!(run_on_main("test", || ()).is_err());
+
+        let start = Instant::now();
+        let err = run_on_main("test", || ()).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::ThreadingViolation);
+        assert!(start.elapsed() < MAIN_THREAD_TIMEOUT);
+    }
+}
@@ -1,16 +1,339 @@
 //! Monitor/Display capture using ScreenCaptureKit via cidre
 
-use cidre::cg;
-use image::RgbaImage;
+use std::time::{Duration, Instant};
+
+use cidre::{cg, sc};
+use image::{RgbImage, RgbaImage};
 use tracing::debug;
 
-use crate::capture;
-use crate::error::{XCapError, XCapResult};
+use crate::cancel::CancellationToken;
+use crate::capture::{self, CgImage};
+use crate::change::PixelRect;
+use crate::color;
+use crate::content::Content;
+use crate::error::{ErrorKind, XCapError, XCapResult};
+use crate::ffi::{bool_value, dictionary_value, from_cf_string, CFRelease, CFTypeRef};
+use crate::geometry::Bounds;
+use crate::options::{CaptureOptions, LetterboxPolicy, ScalingMode};
+use crate::shared::{FrameHeader, SharedRegion};
+use crate::recording::{Recording, RecordingConfig};
+use crate::ring::{RingConfig, RingHandle};
+use crate::screen;
+use crate::stream::{CaptureStream, StreamConfig};
+use crate::touch_bar;
+use crate::window::Window;
+use crate::yuv::YuvFrame;
+
+extern "C" {
+    fn CGDisplayIsBuiltin(display: u32) -> u32;
+    fn CGDisplayVendorNumber(display: u32) -> u32;
+    fn CGGetOnlineDisplayList(max_displays: u32, displays: *mut u32, display_count: *mut u32) -> i32;
+    fn CGGetActiveDisplayList(max_displays: u32, displays: *mut u32, display_count: *mut u32) -> i32;
+    fn CGDisplayCopyDisplayMode(display: u32) -> CFTypeRef;
+    fn CGDisplayModeGetRefreshRate(mode: CFTypeRef) -> f64;
+    fn CGDisplayModeRelease(mode: CFTypeRef);
+    fn CGDisplayModeCopyPixelEncoding(mode: CFTypeRef) -> CFTypeRef;
+    fn CGDisplayCreateUUIDFromDisplayID(display: u32) -> CFTypeRef;
+    fn CFUUIDCreateString(allocator: CFTypeRef, uuid: CFTypeRef) -> CFTypeRef;
+}
+
+#[link(name = "CoreDisplay", kind = "framework")]
+extern "C" {
+    // Private but long-stable; the only place macOS says how a display is attached
+    fn CoreDisplay_DisplayCreateInfoDictionary(display: u32) -> CFTypeRef;
+}
+
+/// Explain why ScreenCaptureKit returned no displays
+///
+/// With the lid closed and no external display (clamshell mode without a
+/// monitor), the built-in panel stays online but inactive, and SCK lists
+/// nothing. That case gets [`XCapError::no_active_display`] so it is not
+/// mistaken for a permission or enumeration problem.
+pub(crate) fn no_displays_error() -> XCapError {
+    let (mut online, mut active) = (0u32, 0u32);
+    unsafe {
+        if CGGetOnlineDisplayList(0, std::ptr::null_mut(), &mut online) != 0 {
+            online = 0;
+        }
+        if CGGetActiveDisplayList(0, std::ptr::null_mut(), &mut active) != 0 {
+            active = 0;
+        }
+    }
+    debug!("No displays from ScreenCaptureKit: {} online, {} active", online, active);
+    no_displays_error_for(online, active)
+}
+
+fn no_displays_error_for(online: u32, active: u32) -> XCapError {
+    if online > 0 && active == 0 {
+        XCapError::no_active_display()
+    } else {
+        XCapError::no_monitors()
+    }
+}
+
+/// Get the refresh rate of a display's current mode, in Hz
+///
+/// CoreGraphics reports 0 for some panels (notably built-in LCDs), in which
+/// case this returns `None`.
+pub(crate) fn display_refresh_rate(display_id: u32) -> Option<f64> {
+    unsafe {
+        let mode = CGDisplayCopyDisplayMode(display_id);
+        if mode.is_null() {
+            return None;
+        }
+        let rate = CGDisplayModeGetRefreshRate(mode);
+        CGDisplayModeRelease(mode);
+        (rate > 0.0).then_some(rate)
+    }
+}
+
+/// Get the pixel encoding of a display's current mode, e.g. `--RRRRRRRRRRGGGGGGGGGGBBBBBBBBBB`
+fn display_pixel_encoding(display_id: u32) -> Option<String> {
+    unsafe {
+        let mode = CGDisplayCopyDisplayMode(display_id);
+        if mode.is_null() {
+            return None;
+        }
+        let encoding = CGDisplayModeCopyPixelEncoding(mode);
+        CGDisplayModeRelease(mode);
+        if encoding.is_null() {
+            return None;
+        }
+        let value = from_cf_string(encoding);
+        CFRelease(encoding);
+        value
+    }
+}
+
+/// Get the bits per color component from an IOKit pixel encoding string
+///
+/// Integer encodings spell out one letter per bit (`IO30BitDirectPixels` is
+/// `--RRRRRRRRRRGGGGGGGGGGBBBBBBBBBB`), float encodings give the width before
+/// an `F` (`IO16BitFloatPixels` is `-16FR16FG16FB16`).
+fn bits_per_component(encoding: &str) -> Option<u32> {
+    if let Some(end) = encoding.find("FR") {
+        let start = encoding[..end]
+            .rfind(|c: char| !c.is_ascii_digit())
+            .map_or(0, |i| i + 1);
+        return encoding[start..end].parse().ok();
+    }
+    let red = encoding.chars().filter(|&c| c == 'R').count() as u32;
+    (red > 0).then_some(red)
+}
+
+/// `kDisplayVendorIDUnknown`
+const DISPLAY_VENDOR_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// What kind of screen a [`Monitor`] is, as returned by [`Monitor::display_kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisplayKind {
+    /// The built-in panel of a laptop or iMac
+    Builtin,
+    /// A physical monitor connected by cable or adapter
+    External,
+    /// An AirPlay receiver used as a display, e.g. an Apple TV
+    AirPlay,
+    /// An iPad extending the desktop through Sidecar
+    Sidecar,
+    /// A software display with no hardware behind it, e.g. one created by a
+    /// screen-sharing or display-management app
+    Virtual,
+}
+
+/// How a display is attached, from its CoreDisplay info dictionary
+#[derive(Debug, Clone, Default, PartialEq)]
+struct DisplayTransport {
+    /// `kCGDisplayIsAirPlay`, set for AirPlay receivers and Sidecar iPads
+    airplay: bool,
+    /// `kCGDisplayIsVirtualDevice`, set for displays created in software
+    virtual_device: bool,
+    /// `IODisplayPrefsKey`, the IORegistry path of the service driving the display
+    prefs_key: String,
+}
+
+/// Read how a display is attached, or `None` if CoreDisplay does not know it
+fn display_transport(display_id: u32) -> Option<DisplayTransport> {
+    unsafe {
+        let info = CoreDisplay_DisplayCreateInfoDictionary(display_id);
+        if info.is_null() {
+            return None;
+        }
+        let transport = DisplayTransport {
+            airplay: bool_value(dictionary_value(info, b"kCGDisplayIsAirPlay\0")),
+            virtual_device: bool_value(dictionary_value(info, b"kCGDisplayIsVirtualDevice\0")),
+            prefs_key: from_cf_string(dictionary_value(info, b"IODisplayPrefsKey\0")).unwrap_or_default(),
+        };
+        CFRelease(info);
+        Some(transport)
+    }
+}
+
+/// Classify a display from its built-in flag, transport, EDID vendor ID and name
+///
+/// Sidecar iPads are driven by a Sidecar service, which shows in their
+/// `IODisplayPrefsKey`, and are flagged as AirPlay like real AirPlay
+/// receivers. Virtual displays have no EDID, so CoreGraphics reports no
+/// vendor for them. The name, e.g. "Sidecar Display (AirPlay)", is localized
+/// and can be renamed, so it is only used when CoreDisplay has no info
+/// dictionary for the display.
+fn classify_display(is_builtin: bool, transport: Option<&DisplayTransport>, vendor: u32, name: &str) -> DisplayKind {
+    if is_builtin {
+        return DisplayKind::Builtin;
+    }
+    match transport {
+        Some(transport) if transport.prefs_key.contains("Sidecar") => return DisplayKind::Sidecar,
+        Some(transport) if transport.airplay => return DisplayKind::AirPlay,
+        Some(transport) if transport.virtual_device => return DisplayKind::Virtual,
+        Some(_) => {}
+        None if name.contains("Sidecar") => return DisplayKind::Sidecar,
+        None if name.contains("AirPlay") => return DisplayKind::AirPlay,
+        None => {}
+    }
+    if vendor == 0 || vendor == DISPLAY_VENDOR_UNKNOWN {
+        DisplayKind::Virtual
+    } else {
+        DisplayKind::External
+    }
+}
+
+/// Get the persistent UUID of a display, e.g. `37D8832A-2D66-02CA-B9F7-8F30A301B230`
+fn display_uuid(display_id: u32) -> Option<String> {
+    unsafe {
+        let uuid = CGDisplayCreateUUIDFromDisplayID(display_id);
+        if uuid.is_null() {
+            return None;
+        }
+        let string = CFUUIDCreateString(std::ptr::null(), uuid);
+        CFRelease(uuid);
+        if string.is_null() {
+            return None;
+        }
+        let value = from_cf_string(string);
+        CFRelease(string);
+        value
+    }
+}
+
+/// Compute the ratio of physical pixels to SCK (logical) pixels for a display
+fn compute_scale_factor(sck_width: u32, sck_height: u32, pixels_width: u32, pixels_height: u32) -> f64 {
+    if sck_width > 0 && sck_height > 0 {
+        let width_scale = pixels_width as f64 / sck_width as f64;
+        let height_scale = pixels_height as f64 / sck_height as f64;
+        ((width_scale + height_scale) / 2.0).max(1.0)
+    } else {
+        1.0
+    }
+}
+
+/// Pick a monitor's [`Monitor::scale_factor`], falling back to the pixel ratio
+///
+/// The pixel ratio from [`compute_scale_factor`] reflects the current display
+/// mode, which in scaled ("More Space") modes is fractional. NSScreen's
+/// backing scale factor is what AppKit renders at, so it is preferred
+/// whenever AppKit found the display.
+fn resolve_scale_factor(backing_scale_factor: Option<f64>, computed: f64) -> f64 {
+    match backing_scale_factor {
+        Some(backing) if backing.is_finite() && backing > 0.0 => backing,
+        _ => computed,
+    }
+}
+
+/// Get the scale factor of a display from its SCK dimensions
+pub(crate) fn display_scale_factor(display_id: u32, sck_width: u32, sck_height: u32) -> f64 {
+    let cg_id = cg::DirectDisplayId(display_id);
+    compute_scale_factor(
+        sck_width,
+        sck_height,
+        cg_id.pixels_wide() as u32,
+        cg_id.pixels_high() as u32,
+    )
+}
+
+/// Largest relative difference between aspect ratios accepted as "the same"
+///
+/// Covers rounding in modes like 1366x768 against a 16:9 panel.
+const ASPECT_TOLERANCE: f64 = 0.01;
+
+/// Check that a requested output size keeps the display's aspect ratio
+fn check_aspect(display_width: u32, display_height: u32, width: u32, height: u32) -> XCapResult<()> {
+    if width == 0 || height == 0 {
+        return Err(XCapError::new(format!("Invalid capture resolution {}x{}", width, height)));
+    }
+    if display_width == 0 || display_height == 0 {
+        return Ok(());
+    }
+
+    let display_aspect = display_width as f64 / display_height as f64;
+    let aspect = width as f64 / height as f64;
+    if ((aspect - display_aspect) / display_aspect).abs() > ASPECT_TOLERANCE {
+        return Err(XCapError::new(format!(
+            "Resolution {}x{} does not match the display aspect ratio ({}x{}) and would distort the capture",
+            width, height, display_width, display_height
+        )));
+    }
+    Ok(())
+}
+
+/// Outline colors for [`Monitor::capture_with_window_overlay`], picked by pid
+const OVERLAY_COLORS: [[u8; 3]; 6] = [
+    [255, 59, 48],
+    [52, 199, 89],
+    [0, 122, 255],
+    [255, 204, 0],
+    [175, 82, 222],
+    [90, 200, 250],
+];
+
+/// Draw the outline of a rect, clipped to the image
+///
+/// The border grows inwards from the rect's edges.
+fn draw_outline(image: &mut RgbaImage, rect: PixelRect, color: image::Rgba<u8>, thickness: u32) {
+    let (rect_right, rect_bottom) = (rect.x.saturating_add(rect.width), rect.y.saturating_add(rect.height));
+    let right = rect_right.min(image.width());
+    let bottom = rect_bottom.min(image.height());
+    for y in rect.y..bottom {
+        for x in rect.x..right {
+            let on_border = x - rect.x < thickness
+                || y - rect.y < thickness
+                || rect_right - 1 - x < thickness
+                || rect_bottom - 1 - y < thickness;
+            if on_border {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Place images on one canvas at their global origins
+///
+/// The canvas spans the bounding box of all images, so negative origins (a
+/// display left of or above the primary one) shift everything right or down.
+/// Gaps between displays stay transparent.
+fn stitch(parts: &[(i32, i32, &RgbaImage)]) -> RgbaImage {
+    let left = parts.iter().map(|(x, _, _)| *x as i64).min().unwrap_or(0);
+    let top = parts.iter().map(|(_, y, _)| *y as i64).min().unwrap_or(0);
+    let right = parts.iter().map(|(x, _, img)| *x as i64 + img.width() as i64).max().unwrap_or(0);
+    let bottom = parts.iter().map(|(_, y, img)| *y as i64 + img.height() as i64).max().unwrap_or(0);
+
+    let mut canvas = RgbaImage::new((right - left) as u32, (bottom - top) as u32);
+    for (x, y, image) in parts {
+        image::imageops::replace(&mut canvas, *image, *x as i64 - left, *y as i64 - top);
+    }
+    canvas
+}
 
 /// Represents a capturable monitor/display
 ///
 /// This type provides an API compatible with xcap::Monitor
+///
+/// Equality and hashing use only the display ID, so two snapshots of the same
+/// display compare equal even if its resolution or arrangement changed.
+///
+/// With the `serde` feature, monitors can be serialized. A deserialized
+/// `Monitor` is a metadata-only snapshot: its getters work, but capturing it
+/// only succeeds if a display with that ID is still connected.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Monitor {
     /// The display ID
     display_id: u32,
@@ -28,10 +351,54 @@ pub struct Monitor {
     logical_width: u32,
     /// Logical height (what macOS reports for UI layout)
     logical_height: u32,
-    /// Scale factor (for Retina displays) - ratio of physical to logical pixels
+    /// Scale factor (for Retina displays): AppKit's backing scale factor when
+    /// available, integral even in scaled display modes
     scale_factor: f64,
+    /// Ratio of captured pixels to points in the current display mode
+    capture_scale_factor: f64,
     /// Whether this is the primary display
     is_primary: bool,
+    /// Whether this is the built-in panel (e.g. a laptop screen)
+    is_builtin: bool,
+    /// Persistent display UUID (empty if unavailable)
+    uuid: String,
+}
+
+/// Image and content area returned by [`Monitor::capture_scaled`]
+#[derive(Debug, Clone)]
+pub struct ScaledCapture {
+    /// The captured image, at the requested size
+    pub image: RgbaImage,
+    /// Part of the image covered by the display's content
+    ///
+    /// Smaller than the image when [`crate::LetterboxPolicy::Pad`] added black
+    /// bars; map coordinates through this rect, not the full image.
+    pub content_rect: PixelRect,
+}
+
+/// Outcome of [`Monitor::capture_all_within`]
+#[derive(Debug, Default)]
+pub struct BudgetedCapture {
+    /// Monitors captured within the budget, with their images
+    pub captured: Vec<(Monitor, RgbaImage)>,
+    /// Monitors whose capture did not finish before the budget ran out
+    pub skipped: Vec<Monitor>,
+    /// Monitors whose capture failed before the budget ran out
+    pub failed: Vec<(Monitor, XCapError)>,
+}
+
+impl PartialEq for Monitor {
+    fn eq(&self, other: &Self) -> bool {
+        self.display_id == other.display_id
+    }
+}
+
+impl Eq for Monitor {}
+
+impl std::hash::Hash for Monitor {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.display_id.hash(state);
+    }
 }
 
 impl Monitor {
@@ -40,12 +407,23 @@ impl Monitor {
     /// Returns a list of all displays that can be captured.
     /// Requires screen recording permission.
     pub fn all() -> XCapResult<Vec<Monitor>> {
-        let content = capture::get_shareable_content()?;
+        Content::current()?.monitors()
+    }
+
+    /// Count the displays that can be captured
+    ///
+    /// Cheaper than `Monitor::all()?.len()`: only the display list is fetched,
+    /// without the per-display CoreGraphics and AppKit lookups.
+    pub fn count() -> XCapResult<usize> {
+        Ok(capture::get_shareable_content()?.displays().len())
+    }
 
+    /// Build monitors from an already-fetched ShareableContent
+    pub(crate) fn from_content(content: &sc::ShareableContent) -> XCapResult<Vec<Monitor>> {
         let displays = content.displays();
 
         if displays.is_empty() {
-            return Err(XCapError::no_monitors());
+            return Err(no_displays_error());
         }
 
         // Find the primary display (usually the first one, or has origin at 0,0)
@@ -79,14 +457,8 @@ impl Monitor {
                 // Use SCK dimensions for capture
                 let (capture_width, capture_height) = (sck_width, sck_height);
 
-                // Calculate scale factor
-                let scale_factor = if sck_width > 0 && sck_height > 0 {
-                    let width_scale = cg_pixels_width as f64 / sck_width as f64;
-                    let height_scale = cg_pixels_height as f64 / sck_height as f64;
-                    ((width_scale + height_scale) / 2.0).max(1.0)
-                } else {
-                    1.0
-                };
+                // Pixel ratio of captures, the same way Window::scale_factor does
+                let capture_scale_factor = compute_scale_factor(sck_width, sck_height, cg_pixels_width, cg_pixels_height);
 
                 debug!(
                     "Display {} dimensions - SCK: {}x{}, CGPixels: {}x{}, CGBounds: {}x{}, using: {}x{}",
@@ -97,9 +469,27 @@ impl Monitor {
                     capture_width, capture_height
                 );
 
+                // Real product name from AppKit, e.g. "Built-in Retina Display"
+                // Fails fast once the main thread has been found unresponsive
+                let info = screen::screen_info(display_id).unwrap_or_else(|e| {
+                    debug!("Display {} name unavailable: {}", display_id, e);
+                    None
+                });
+                let scale_factor =
+                    resolve_scale_factor(info.as_ref().map(|info| info.backing_scale_factor), capture_scale_factor);
+                if scale_factor != capture_scale_factor {
+                    debug!(
+                        "Display {} backing scale {} differs from pixel ratio {}",
+                        display_id, scale_factor, capture_scale_factor
+                    );
+                }
+                let name = info
+                    .and_then(|info| info.name)
+                    .unwrap_or_else(|| format!("Display {}", display_id));
+
                 Monitor {
                     display_id,
-                    name: format!("Display {}", display_id),
+                    name,
                     x: frame.origin.x as i32,
                     y: frame.origin.y as i32,
                     width: capture_width,
@@ -107,7 +497,10 @@ impl Monitor {
                     logical_width: cg_bounds_width,
                     logical_height: cg_bounds_height,
                     scale_factor,
+                    capture_scale_factor,
                     is_primary: display_id == primary_id,
+                    is_builtin: unsafe { CGDisplayIsBuiltin(display_id) } != 0,
+                    uuid: display_uuid(display_id).unwrap_or_default(),
                 }
             })
             .collect();
@@ -124,12 +517,165 @@ impl Monitor {
             .ok_or_else(|| XCapError::new("No primary monitor found"))
     }
 
+    /// Get the built-in monitor (e.g. a MacBook's panel)
+    ///
+    /// Fails with [`ErrorKind::MonitorNotFound`]
+    /// on Macs without a built-in display, or when the lid is closed.
+    pub fn builtin() -> XCapResult<Monitor> {
+        Self::all()?
+            .into_iter()
+            .find(|m| m.is_builtin)
+            .ok_or_else(|| XCapError::with_kind(ErrorKind::MonitorNotFound, "No built-in monitor found"))
+    }
+
+    /// Get the monitor with a UUID previously returned by [`Monitor::uuid`]
+    ///
+    /// The comparison ignores case. Fails with [`ErrorKind::MonitorNotFound`]
+    /// if that display is not connected.
+    pub fn from_uuid(uuid: &str) -> XCapResult<Monitor> {
+        Self::all()?
+            .into_iter()
+            .find(|m| !m.uuid.is_empty() && m.uuid.eq_ignore_ascii_case(uuid))
+            .ok_or_else(|| XCapError::with_kind(ErrorKind::MonitorNotFound, format!("Monitor with UUID {} not found", uuid)))
+    }
+
+    /// Get all external monitors, i.e. every monitor except the built-in one
+    ///
+    /// Returns an empty list when only the built-in display is connected.
+    pub fn externals() -> XCapResult<Vec<Monitor>> {
+        Ok(Self::all()?.into_iter().filter(|m| !m.is_builtin).collect())
+    }
+
+    /// Get the monitor a window is on
+    ///
+    /// Uses the same rule as window capture: the monitor containing the
+    /// window's origin, or the first monitor if no monitor contains it.
+    pub fn for_window(window: &Window) -> XCapResult<Monitor> {
+        let mut monitors = Self::all()?;
+        let index = monitors
+            .iter()
+            .position(|m| m.display_id == window.display_id())
+            .or_else(|| monitors.iter().position(|m| m.contains_window(window)))
+            .unwrap_or(0);
+        Ok(monitors.swap_remove(index))
+    }
+
+    /// Capture every monitor at the same moment
+    ///
+    /// Fetches ShareableContent once and captures all displays concurrently, so
+    /// the frames are synchronized far better than calling
+    /// [`Monitor::capture_image`] on each monitor in turn. Fails if any display
+    /// fails to capture; see [`Monitor::capture_all_with`] to keep partial results.
+    pub fn capture_all() -> XCapResult<Vec<(Monitor, RgbaImage)>> {
+        Self::capture_all_with(false)
+    }
+
+    /// Capture every monitor at the same moment, optionally tolerating failures
+    ///
+    /// With `allow_partial` set, displays that fail to capture are skipped and
+    /// only the successful captures are returned; an error is returned only if
+    /// every display fails. Without it, the first failure is returned.
+    pub fn capture_all_with(allow_partial: bool) -> XCapResult<Vec<(Monitor, RgbaImage)>> {
+        let content = capture::get_shareable_content()?;
+        let monitors = Self::from_content(&content)?;
+        let targets = monitors
+            .iter()
+            .map(|m| (m.display_id, m.width, m.height))
+            .collect();
+
+        let results = capture::capture_monitors_sync(content, targets)?;
+
+        let mut captures = Vec::with_capacity(monitors.len());
+        let mut last_error = None;
+        for (monitor, result) in monitors.into_iter().zip(results) {
+            match result {
+                Ok(image) => captures.push((monitor, image)),
+                Err(e) if allow_partial => {
+                    debug!("Skipping monitor {} in capture_all: {}", monitor.display_id, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match last_error {
+            Some(e) if captures.is_empty() => Err(e),
+            _ => Ok(captures),
+        }
+    }
+
+    /// Capture as many monitors as possible within a time budget
+    ///
+    /// All displays are captured concurrently against one shared deadline, so
+    /// a slow display only costs its own frame: it is reported as skipped once
+    /// the budget runs out, while the others are returned as soon as they are
+    /// ready. The budget includes fetching the display list. Only an error
+    /// fetching the display list is returned as `Err`.
+    pub fn capture_all_within(budget: Duration) -> XCapResult<BudgetedCapture> {
+        let deadline = Instant::now() + budget;
+        let content = capture::get_shareable_content()?;
+        let monitors = Self::from_content(&content)?;
+        let targets = monitors
+            .iter()
+            .map(|m| (m.display_id, m.width, m.height))
+            .collect();
+
+        let results = capture::capture_monitors_within_sync(content, targets, deadline)?;
+
+        let mut outcome = BudgetedCapture::default();
+        for (monitor, result) in monitors.into_iter().zip(results) {
+            match result {
+                Some(Ok(image)) => outcome.captured.push((monitor, image)),
+                Some(Err(e)) => outcome.failed.push((monitor, e)),
+                None => {
+                    debug!("Monitor {} did not finish within {:?}", monitor.display_id, budget);
+                    outcome.skipped.push(monitor);
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Capture the whole desktop as one image
+    ///
+    /// Captures every monitor at the same moment (see [`Monitor::capture_all`])
+    /// and places each capture at its global origin, one pixel per point. The
+    /// image covers the bounding box of all displays, with its top-left corner
+    /// at the leftmost and topmost display edges; areas no display covers are
+    /// transparent.
+    pub fn capture_desktop() -> XCapResult<RgbaImage> {
+        let captures = Self::capture_all()?;
+        let parts: Vec<_> = captures
+            .iter()
+            .map(|(monitor, image)| (monitor.x, monitor.y, image))
+            .collect();
+        Ok(stitch(&parts))
+    }
+
+    /// Capture the Touch Bar on MacBook Pro models that have one
+    ///
+    /// The Touch Bar is not a display ScreenCaptureKit or CoreGraphics can
+    /// see, so it never appears in [`Monitor::all`]; this runs the system
+    /// `screencapture -b` tool instead, which is slower than a display
+    /// capture. Fails with [`ErrorKind::MonitorNotFound`] on Macs without a
+    /// Touch Bar, and also when screen recording permission is missing,
+    /// since `screencapture` then writes no image either.
+    pub fn capture_touch_bar() -> XCapResult<RgbaImage> {
+        touch_bar::capture()
+    }
+
     /// Get the monitor ID
     pub fn id(&self) -> u32 {
         self.display_id
     }
 
     /// Get the monitor name
+    ///
+    /// This is the localized product name shown in System Settings (e.g.
+    /// "Built-in Retina Display", "LG UltraFine"), or "Display {id}" when
+    /// macOS does not report one. The name comes from AppKit, which must be
+    /// queried on the main thread; if [`Monitor::all`] runs on another thread
+    /// while the main thread has no run loop, the fallback name is used.
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -175,20 +721,424 @@ impl Monitor {
     }
 
     /// Get the scale factor (for Retina displays)
+    ///
+    /// NSScreen's backing scale factor: 2.0 on Retina displays even in scaled
+    /// ("More Space") modes, where AppKit renders at 2x and the result is
+    /// resampled to the panel. Falls back to [`Monitor::capture_scale_factor`]
+    /// when AppKit is unavailable.
     pub fn scale_factor(&self) -> f64 {
         self.scale_factor
     }
 
+    /// Get the ratio of captured pixels to points in the current display mode
+    ///
+    /// This is the scale of [`Monitor::capture_image`] and matches
+    /// [`Window::scale_factor`]. Fractional in scaled modes.
+    pub fn capture_scale_factor(&self) -> f64 {
+        self.capture_scale_factor
+    }
+
+    /// Convert a point in global logical coordinates to pixels in this monitor's capture
+    ///
+    /// Takes a position in points with a top-left origin, as used by mouse
+    /// events and [`Window::bounds`], and returns where it falls in an image
+    /// from [`Monitor::capture_image`]: relative to the monitor's origin and
+    /// scaled by the capture's pixels per point on each axis. The result is not
+    /// clamped, so points off this monitor map outside the image.
+    pub fn logical_to_physical(&self, x: f64, y: f64) -> (f64, f64) {
+        let (scale_x, scale_y) = self.capture_scale();
+        ((x - self.x as f64) * scale_x, (y - self.y as f64) * scale_y)
+    }
+
+    /// Convert pixels in this monitor's capture to a point in global logical coordinates
+    ///
+    /// The inverse of [`Monitor::logical_to_physical`].
+    pub fn physical_to_logical(&self, x: f64, y: f64) -> (f64, f64) {
+        let (scale_x, scale_y) = self.capture_scale();
+        (x / scale_x + self.x as f64, y / scale_y + self.y as f64)
+    }
+
+    /// Pixels per point of [`Monitor::capture_image`] on each axis
+    ///
+    /// Captures are `width` x `height` pixels covering `logical_width` x
+    /// `logical_height` points, which need not match the backing scale in
+    /// scaled display modes.
+    fn capture_scale(&self) -> (f64, f64) {
+        let ratio = |pixels: u32, points: u32| if points > 0 { pixels as f64 / points as f64 } else { 1.0 };
+        (
+            ratio(self.width, self.logical_width),
+            ratio(self.height, self.logical_height),
+        )
+    }
+
     /// Check if this is the primary monitor
     pub fn is_primary(&self) -> bool {
         self.is_primary
     }
 
+    /// Get the display's persistent UUID
+    ///
+    /// Unlike [`Monitor::id`], which can change across reboots and reconnects,
+    /// the UUID stays the same for a physical display, so it is the identifier
+    /// to store in config files. Look the monitor up again with
+    /// [`Monitor::from_uuid`]. Empty if macOS did not report one.
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// Get the refresh rate of the display's current mode, in Hz
+    ///
+    /// Returns `None` when macOS does not report one, which is common for
+    /// built-in panels; 60 Hz is the usual value then. Read live, so it
+    /// reflects mode changes made after the monitor was listed.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        display_refresh_rate(self.display_id)
+    }
+
+    /// Get the bits per color component of the display's current mode
+    ///
+    /// 8 for ordinary displays, 10 for panels driven in 30-bit mode, 16 for
+    /// half-float modes. Parsed from the mode's pixel encoding and read live;
+    /// falls back to 8 when macOS does not report an encoding.
+    pub fn bit_depth(&self) -> u32 {
+        display_pixel_encoding(self.display_id)
+            .as_deref()
+            .and_then(bits_per_component)
+            .unwrap_or(8)
+    }
+
+    /// Check whether the display can show HDR content
+    ///
+    /// A heuristic: true if AppKit reports extended dynamic range headroom
+    /// for the screen (as on XDR and HDR-enabled external displays) or the
+    /// current mode has more than 8 bits per component. Use it to decide
+    /// whether a wider pixel format is worth requesting.
+    pub fn is_hdr(&self) -> bool {
+        let edr = match screen::screen_info(self.display_id) {
+            Ok(info) => info.is_some_and(|info| info.max_edr > 1.0),
+            Err(e) => {
+                debug!("Display {} EDR headroom unavailable: {}", self.display_id, e);
+                false
+            }
+        };
+        edr || self.bit_depth() > 8
+    }
+
+    /// Check if this is the built-in display (e.g. a laptop panel) rather than an external one
+    pub fn is_builtin(&self) -> bool {
+        self.is_builtin
+    }
+
+    /// Get what kind of screen this is, e.g. to find the iPad in a Sidecar session
+    ///
+    /// Sidecar, AirPlay and virtual displays are recognized from how
+    /// CoreDisplay says they are attached; only if it has nothing on the
+    /// display does this fall back to the name macOS gives wireless displays,
+    /// which misses renamed ones and "Display N" fallbacks. All kinds are captured like any other monitor, as long as
+    /// ScreenCaptureKit lists them in [`Monitor::all`]; a display that
+    /// disconnects mid-capture (e.g. the iPad went to sleep) fails with
+    /// [`ErrorKind::MonitorNotFound`] or [`ErrorKind::CaptureFailed`].
+    pub fn display_kind(&self) -> DisplayKind {
+        let vendor = unsafe { CGDisplayVendorNumber(self.display_id) };
+        classify_display(self.is_builtin, display_transport(self.display_id).as_ref(), vendor, &self.name)
+    }
+
+    /// Get the monitor frame in global points with a top-left origin
+    ///
+    /// The size is the logical size, so the bounds line up with
+    /// [`Window::bounds`] regardless of the scale factor.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::top_left(self.x, self.y, self.logical_width, self.logical_height)
+    }
+
+    /// Estimate the size in bytes of a native-resolution capture of the monitor
+    ///
+    /// See [`Window::estimated_capture_bytes`].
+    pub fn estimated_capture_bytes(&self) -> usize {
+        capture::estimated_rgba_bytes(self.width, self.height, self.capture_scale_factor)
+    }
+
+    /// Check whether a window is on this monitor
+    ///
+    /// A window belongs to the monitor containing its origin, which is also the
+    /// display window capture crops from.
+    pub fn contains_window(&self, window: &Window) -> bool {
+        let (x, y) = window.origin();
+        capture::frame_contains(
+            self.x as f64,
+            self.y as f64,
+            self.logical_width as f64,
+            self.logical_height as f64,
+            x as f64,
+            y as f64,
+        )
+    }
+
     /// Capture an image of the monitor
     ///
     /// Returns an RGBA image of the entire monitor.
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
-        capture::capture_monitor_sync(self.display_id, self.width, self.height)
+        capture::capture_monitor_sync(self.display_id, self.width, self.height, CaptureOptions::default())
+    }
+
+    /// Capture the monitor into shared memory for another process to read
+    ///
+    /// Same image as [`Monitor::capture_image`], written to `region` with
+    /// [`SharedRegion::write_frame`]. Size the region with
+    /// `SharedRegion::size_for(monitor.raw_width(), monitor.raw_height())`.
+    pub fn capture_into_shared(&self, region: &mut SharedRegion) -> XCapResult<FrameHeader> {
+        region.write_frame(&self.capture_image()?)
+    }
+
+    /// Capture an image of the monitor with explicit options
+    ///
+    /// See [`CaptureOptions`] for what can be configured.
+    pub fn capture_image_with(&self, options: &CaptureOptions) -> XCapResult<RgbaImage> {
+        capture::capture_monitor_sync(self.display_id, self.width, self.height, options.clone())
+    }
+
+    /// Capture the monitor with explicit options and report the scale applied
+    ///
+    /// Returns the image and its output pixels per point: 1.0 for a logical
+    /// capture, the scale factor for a physical one, or less when
+    /// [`CaptureOptions::max_bytes`] made ScreenCaptureKit downscale it.
+    pub fn capture_image_with_scale(&self, options: &CaptureOptions) -> XCapResult<(RgbaImage, f64)> {
+        let image = self.capture_image_with(options)?;
+        let scale = image.width() as f64 / self.width.max(1) as f64;
+        Ok((image, scale))
+    }
+
+    /// Capture the monitor and save it with the display's color profile
+    ///
+    /// Unlike `capture_image()?.save(path)`, which writes untagged pixels that
+    /// color-managed viewers read as sRGB, this embeds the display's ICC profile
+    /// (e.g. Display P3) so colors look the same as on screen. `format` must be
+    /// [`image::ImageFormat::Png`] or [`image::ImageFormat::Jpeg`].
+    pub fn save_capture<P: AsRef<std::path::Path>>(&self, path: P, format: image::ImageFormat) -> XCapResult<()> {
+        let image = self.capture_image()?;
+        let profile = color::display_icc_profile(self.display_id);
+        if profile.is_none() {
+            debug!("No ICC profile for display {}, saving untagged", self.display_id);
+        }
+        color::write_tagged(&image, path.as_ref(), format, profile)
+    }
+
+    /// Capture the monitor as a CoreGraphics image
+    ///
+    /// Skips the conversion to [`RgbaImage`], for callers that hand the image
+    /// to Vision or CoreImage. Requires macOS 14.0 or later.
+    pub fn capture_cg_image(&self) -> XCapResult<CgImage> {
+        capture::capture_monitor_cg_image_sync(self.display_id, self.width, self.height)
+    }
+
+    /// Capture the monitor rendered at a specific resolution
+    ///
+    /// Produces a `width` x `height` image as if the display were running in
+    /// that scaled mode, without changing the user's actual display mode. The
+    /// resolution must have the same aspect ratio as the display (every scaled
+    /// mode of a panel does); a mismatched aspect ratio would distort the image
+    /// and is rejected with an error. Uses [`crate::ScalingMode::Fit`].
+    pub fn capture_at_resolution(&self, width: u32, height: u32) -> XCapResult<RgbaImage> {
+        check_aspect(self.logical_width, self.logical_height, width, height)?;
+        let (image, _) = capture::capture_monitor_scaled_sync(self.display_id, width, height, CaptureOptions::default())?;
+        Ok(image)
+    }
+
+    /// Capture the monitor rendered at any resolution, with options
+    ///
+    /// Unlike [`Monitor::capture_at_resolution`], the aspect ratio may differ
+    /// from the display's; `options.scaling` decides whether the image is
+    /// letterboxed ([`crate::ScalingMode::Fit`], the default), distorted
+    /// ([`crate::ScalingMode::Stretch`]) or cropped ([`crate::ScalingMode::None`]).
+    /// With `Fit`, `options.letterbox` chooses between black bars, cropping
+    /// and an error when the aspect ratios differ.
+    /// `options.coordinate_space` is ignored and a custom cursor is not drawn.
+    pub fn capture_at_resolution_with(&self, width: u32, height: u32, options: &CaptureOptions) -> XCapResult<RgbaImage> {
+        Ok(self.capture_scaled(width, height, options)?.image)
+    }
+
+    /// Like [`Monitor::capture_at_resolution_with`], also reporting where the content lies
+    ///
+    /// [`ScaledCapture::content_rect`] is the area inside any letterbox bars,
+    /// so coordinates in the image can be mapped back to the display.
+    pub fn capture_scaled(&self, width: u32, height: u32, options: &CaptureOptions) -> XCapResult<ScaledCapture> {
+        if width == 0 || height == 0 {
+            return Err(XCapError::new(format!("Invalid capture resolution {}x{}", width, height)));
+        }
+        let fits = matches!(options.scaling, None | Some(ScalingMode::Fit));
+        if fits && options.letterbox == LetterboxPolicy::Error {
+            check_aspect(self.logical_width, self.logical_height, width, height)?;
+        }
+        let (image, content_rect) =
+            capture::capture_monitor_scaled_sync(self.display_id, width, height, options.clone())?;
+        Ok(ScaledCapture { image, content_rect })
+    }
+
+    /// Capture a region of the monitor at native resolution
+    ///
+    /// `x`, `y`, `width` and `height` are in points relative to the monitor's
+    /// top-left corner, and the rect must lie within the monitor. The region is
+    /// passed to ScreenCaptureKit as the stream's source rect, so only that
+    /// area is rendered, which is much cheaper than capturing the full frame and
+    /// cropping it. The image has one pixel per physical pixel.
+    pub fn capture_content_rect(&self, x: u32, y: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
+        let fits = width > 0
+            && height > 0
+            && x.checked_add(width).is_some_and(|right| right <= self.logical_width)
+            && y.checked_add(height).is_some_and(|bottom| bottom <= self.logical_height);
+        if !fits {
+            return Err(XCapError::new(format!(
+                "Rect {}x{} at ({}, {}) is outside the {}x{} monitor",
+                width, height, x, y, self.logical_width, self.logical_height
+            )));
+        }
+        capture::capture_monitor_content_rect_sync(self.display_id, x, y, width, height)
+    }
+
+    /// Capture an image of the monitor unless `token` is cancelled first
+    ///
+    /// Returns an error of kind [`crate::ErrorKind::Cancelled`] as soon as the
+    /// token is cancelled, without waiting for the capture to finish.
+    pub fn capture_image_cancellable(&self, token: CancellationToken) -> XCapResult<RgbaImage> {
+        capture::capture_monitor_cancellable_sync(self.display_id, self.width, self.height, token)
+    }
+
+    /// Capture an image of the monitor without an alpha channel
+    ///
+    /// Same pixels as [`Monitor::capture_image`], converted straight to RGB,
+    /// which saves a quarter of the memory and suits formats without alpha
+    /// such as JPEG.
+    pub fn capture_rgb(&self) -> XCapResult<RgbImage> {
+        capture::capture_monitor_rgb_sync(self.display_id, self.width, self.height)
+    }
+
+    /// Capture the monitor with the bounds of its windows drawn on top
+    ///
+    /// A debugging aid: every window whose origin is on this monitor (see
+    /// [`Monitor::contains_window`]) is outlined, frontmost last so its
+    /// outline is on top, with one color per application. Titles are not
+    /// drawn, since the crate ships no font; pair the image with
+    /// [`Window::all`] to label it.
+    pub fn capture_with_window_overlay(&self) -> XCapResult<RgbaImage> {
+        let content = Content::current()?;
+        let windows = content.windows()?;
+        let mut image = content.capture_monitor(self)?;
+
+        let monitor_bounds = self.bounds();
+        let scale = image.width() as f64 / self.logical_width.max(1) as f64;
+        let thickness = (2.0 * scale).round().max(1.0) as u32;
+        for window in windows.iter().rev().filter(|w| self.contains_window(w)) {
+            let Some(local) = window.bounds().relative_to(&monitor_bounds) else {
+                continue;
+            };
+            let rect = PixelRect {
+                x: (local.x as f64 * scale).round() as u32,
+                y: (local.y as f64 * scale).round() as u32,
+                width: (local.width as f64 * scale).round() as u32,
+                height: (local.height as f64 * scale).round() as u32,
+            };
+            let [r, g, b] = OVERLAY_COLORS[window.pid().unwrap_or(0) as usize % OVERLAY_COLORS.len()];
+            draw_outline(&mut image, rect, image::Rgba([r, g, b, 255]), thickness);
+        }
+        Ok(image)
+    }
+
+    /// Capture the monitor as an NV12 frame for a video encoder
+    ///
+    /// ScreenCaptureKit renders straight into biplanar YUV 4:2:0, so there is
+    /// no RGBA round-trip; the Y and UV planes are returned with their strides.
+    /// The size matches [`Monitor::capture_image`]. The cursor is drawn.
+    pub fn capture_yuv(&self) -> XCapResult<YuvFrame> {
+        capture::capture_monitor_yuv_sync(self.display_id, self.width, self.height)
+    }
+
+    /// Capture the monitor at the exact pixel size of its current display mode
+    ///
+    /// [`Monitor::capture_image`] uses ScreenCaptureKit's preferred capture
+    /// size, which on scaled displays differs from the framebuffer. This
+    /// captures at `CGDisplayPixelsWide` x `CGDisplayPixelsHigh` without
+    /// scaling, and fails if the returned image has any other size. The image
+    /// can be much larger than the default capture (four times the pixels on
+    /// a Retina display), with matching memory and conversion cost.
+    pub fn capture_image_physical(&self) -> XCapResult<RgbaImage> {
+        capture::capture_monitor_physical_sync(self.display_id)
+    }
+
+    /// Capture the monitor once and downscale it to several sizes
+    ///
+    /// Each entry of `sizes` is the longest side of one output image, e.g.
+    /// `&[u32::MAX, 320]` for the full frame plus a thumbnail. All images come
+    /// from the same captured frame, unlike separate captures.
+    pub fn capture_pyramid(&self, sizes: &[u32]) -> XCapResult<Vec<RgbaImage>> {
+        capture::build_pyramid(self.capture_image()?, sizes)
+    }
+
+    /// Capture an image of the monitor only if it changed since the last call
+    ///
+    /// `last_hash` holds the hash of the previous frame and is updated on every
+    /// successful capture. Returns `None` when the frame is identical, in which
+    /// case the RGBA conversion is skipped entirely. Start with `None` to always
+    /// get the first frame.
+    pub fn capture_if_changed(&self, last_hash: &mut Option<u64>) -> XCapResult<Option<RgbaImage>> {
+        let (hash, image) = capture::capture_monitor_if_changed_sync(
+            self.display_id,
+            self.width,
+            self.height,
+            *last_hash,
+        )?;
+        *last_hash = Some(hash);
+        Ok(image)
+    }
+
+    /// Capture an image of the monitor together with its perceptual hash
+    ///
+    /// The hash is a 64-bit DCT pHash, computed from the pixels as they are
+    /// converted, so it costs no second pass over the frame. Near-identical
+    /// frames differ in only a few bits: compare two hashes with
+    /// `(a ^ b).count_ones()` and treat a small distance (e.g. under 5) as a
+    /// duplicate. The hash is unrelated to the exact one used by
+    /// [`Monitor::capture_if_changed`].
+    pub fn capture_phash(&self) -> XCapResult<(RgbaImage, u64)> {
+        capture::capture_monitor_phash_sync(self.display_id, self.width, self.height)
+    }
+
+    /// Capture the monitor into a caller-provided buffer
+    ///
+    /// Writes tightly packed RGBA bytes into `buf`, resizing it as needed and
+    /// reusing its allocation, and returns the image width and height. Useful in
+    /// capture loops that recycle one buffer across frames.
+    pub fn capture_into(&self, buf: &mut Vec<u8>) -> XCapResult<(u32, u32)> {
+        let (returned, result) = capture::capture_monitor_into_sync(self.display_id, self.width, self.height, std::mem::take(buf))?;
+        *buf = returned;
+        result
+    }
+
+    /// Start a continuous capture stream of the monitor
+    ///
+    /// Frames are delivered at up to `config.fps` and can be read with
+    /// [`CaptureStream::recv`]. The stream stops when the handle is dropped.
+    pub fn stream(&self, config: StreamConfig) -> XCapResult<CaptureStream> {
+        CaptureStream::start_display(self.display_id, self.width, self.height, config)
+    }
+
+    /// Record the monitor into an in-memory ring of the most recent frames
+    ///
+    /// Keeps the last `config.capacity_frames` frames captured at up to
+    /// `config.fps`, evicting the oldest as new ones arrive. Use
+    /// [`RingHandle::snapshot`] to read them, e.g. to "rewind" the last few
+    /// seconds. Recording stops when the handle is dropped.
+    pub fn record_ring(&self, config: RingConfig) -> XCapResult<RingHandle> {
+        RingHandle::start_display(self.display_id, self.width, self.height, config)
+    }
+
+    /// Record the monitor to an `.mp4` or `.mov` file
+    ///
+    /// Frames are encoded with VideoToolbox as `config.codec` while they
+    /// arrive, without a round trip through RGBA. Call [`Recording::stop`] to
+    /// finish the file; dropping the handle finishes it too. Fails if `path`
+    /// already exists.
+    pub fn record_to_file<P: AsRef<std::path::Path>>(&self, path: P, config: RecordingConfig) -> XCapResult<Recording> {
+        Recording::start_display(self.display_id, self.width, self.height, path.as_ref(), config)
     }
 }
 
@@ -208,7 +1158,10 @@ mod tests {
             logical_width: 1920,
             logical_height: 1080,
             scale_factor: 2.0,
+            capture_scale_factor: 2.0,
             is_primary: true,
+            is_builtin: true,
+            uuid: "37D8832A-2D66-02CA-B9F7-8F30A301B230".to_string(),
         };
 
         assert_eq!(monitor.id(), 1);
@@ -222,7 +1175,174 @@ mod tests {
         assert_eq!(monitor.logical_width(), 1920);
         assert_eq!(monitor.logical_height(), 1080);
         assert_eq!(monitor.scale_factor(), 2.0);
+        assert_eq!(monitor.capture_scale_factor(), 2.0);
         assert!(monitor.is_primary());
+        assert!(monitor.is_builtin());
+        assert_eq!(monitor.uuid(), "37D8832A-2D66-02CA-B9F7-8F30A301B230");
+    }
+
+    #[test]
+    fn test_logical_physical_conversion() {
+        let monitor = Monitor {
+            display_id: 2,
+            name: "Right Display".to_string(),
+            x: 1920,
+            y: -200,
+            width: 5120,
+            height: 2880,
+            logical_width: 2560,
+            logical_height: 1440,
+            scale_factor: 2.0,
+            capture_scale_factor: 2.0,
+            is_primary: false,
+            is_builtin: false,
+            uuid: String::new(),
+        };
+
+        assert_eq!(monitor.logical_to_physical(1920.0, -200.0), (0.0, 0.0));
+        assert_eq!(monitor.logical_to_physical(2020.5, 0.0), (201.0, 400.0));
+        assert_eq!(monitor.physical_to_logical(201.0, 400.0), (2020.5, 0.0));
+        // Points on another monitor land outside the image
+        assert_eq!(monitor.logical_to_physical(100.0, 100.0), (-3640.0, 600.0));
+
+        // "More Space" on a 2560x1600 panel: captures are 2560x1600 pixels for
+        // 1680x1050 points, although AppKit's backing scale is 2.0
+        let scaled = Monitor {
+            width: 2560,
+            height: 1600,
+            logical_width: 1680,
+            logical_height: 1050,
+            scale_factor: 2.0,
+            capture_scale_factor: 2560.0 / 1680.0,
+            x: 0,
+            y: 0,
+            ..monitor
+        };
+        assert_eq!(scaled.logical_to_physical(1680.0, 1050.0), (2560.0, 1600.0));
+        assert_eq!(scaled.logical_to_physical(840.0, 525.0), (1280.0, 800.0));
+        assert_eq!(scaled.physical_to_logical(1280.0, 800.0), (840.0, 525.0));
+    }
+
+    #[test]
+    fn test_no_displays_error() {
+        // Lid closed, no external display: the panel is online but inactive
+        assert_eq!(no_displays_error_for(1, 0).kind(), ErrorKind::NoActiveDisplay);
+        assert_eq!(no_displays_error_for(0, 0).kind(), ErrorKind::NoMonitors);
+        // Displays are active, so SCK hid them for another reason
+        assert_eq!(no_displays_error_for(2, 1).kind(), ErrorKind::NoMonitors);
+    }
+
+    #[test]
+    fn test_classify_display() {
+        let wired = DisplayTransport {
+            prefs_key: "IOService:/AppleARMPE/arm-io/AppleT811xIO/dispext0@38000000/IOMobileFramebufferShim".into(),
+            ..Default::default()
+        };
+        let sidecar = DisplayTransport {
+            airplay: true,
+            prefs_key: "IOService:/IOResources/AppleSidecarDisplay".into(),
+            ..Default::default()
+        };
+        let airplay = DisplayTransport {
+            airplay: true,
+            ..Default::default()
+        };
+        let virtual_device = DisplayTransport {
+            virtual_device: true,
+            ..Default::default()
+        };
+
+        assert_eq!(classify_display(true, Some(&wired), 0x610, "Built-in Retina Display"), DisplayKind::Builtin);
+        // Renamed or localized names do not matter when CoreDisplay knows the display
+        assert_eq!(classify_display(false, Some(&sidecar), 0x610, "Alex's iPad"), DisplayKind::Sidecar);
+        assert_eq!(classify_display(false, Some(&airplay), 0x610, "Wohnzimmer"), DisplayKind::AirPlay);
+        assert_eq!(classify_display(false, Some(&virtual_device), 0x610, "Display 3"), DisplayKind::Virtual);
+        assert_eq!(classify_display(false, Some(&wired), 0x10ac, "Sidecar Display"), DisplayKind::External);
+        assert_eq!(classify_display(false, Some(&wired), DISPLAY_VENDOR_UNKNOWN, "Virtual 16:9"), DisplayKind::Virtual);
+        // Without an info dictionary the name is the last resort
+        assert_eq!(classify_display(false, None, 0x610, "Sidecar Display (AirPlay)"), DisplayKind::Sidecar);
+        assert_eq!(classify_display(false, None, 0x610, "Living Room (AirPlay)"), DisplayKind::AirPlay);
+        assert_eq!(classify_display(false, None, 0x10ac, "DELL U2720Q"), DisplayKind::External);
+    }
+
+    #[test]
+    fn test_bits_per_component() {
+        assert_eq!(bits_per_component("IIIIIIIIRRRRRRRRGGGGGGGGBBBBBBBB"), Some(8));
+        assert_eq!(bits_per_component("--RRRRRRRRRRGGGGGGGGGGBBBBBBBBBB"), Some(10));
+        assert_eq!(bits_per_component("-16FR16FG16FB16"), Some(16));
+        assert_eq!(bits_per_component("-32FR32FG32FB32"), Some(32));
+        assert_eq!(bits_per_component(""), None);
+    }
+
+    #[test]
+    fn test_compute_scale_factor() {
+        assert_eq!(compute_scale_factor(1920, 1080, 3840, 2160), 2.0);
+        assert_eq!(compute_scale_factor(1920, 1080, 1920, 1080), 1.0);
+        assert_eq!(compute_scale_factor(0, 0, 3840, 2160), 1.0);
+        // Never below 1.0
+        assert_eq!(compute_scale_factor(3840, 2160, 1920, 1080), 1.0);
+    }
+
+    #[test]
+    fn test_resolve_scale_factor() {
+        // 1x external display
+        let computed = compute_scale_factor(1920, 1080, 1920, 1080);
+        assert_eq!(resolve_scale_factor(Some(1.0), computed), 1.0);
+
+        // 2x Retina at its default mode
+        let computed = compute_scale_factor(1440, 900, 2880, 1800);
+        assert_eq!(resolve_scale_factor(Some(2.0), computed), 2.0);
+
+        // "More Space" on a 2560x1600 panel: looks like 1680x1050, pixel ratio ~1.52,
+        // but AppKit still renders at 2x
+        let computed = compute_scale_factor(1680, 1050, 2560, 1600);
+        assert!((computed - 1.5238).abs() < 0.001);
+        assert_eq!(resolve_scale_factor(Some(2.0), computed), 2.0);
+
+        // AppKit unavailable or bogus: fall back to the pixel ratio
+        assert_eq!(resolve_scale_factor(None, computed), computed);
+        assert_eq!(resolve_scale_factor(Some(0.0), computed), computed);
+    }
+
+    #[test]
+    fn test_draw_outline() {
+        let mut image = RgbaImage::new(6, 5);
+        let red = image::Rgba([255, 0, 0, 255]);
+        draw_outline(&mut image, PixelRect { x: 1, y: 1, width: 4, height: 3 }, red, 1);
+        assert_eq!(image.get_pixel(1, 1), &red);
+        assert_eq!(image.get_pixel(4, 3), &red);
+        // Inside and outside the outline are untouched
+        assert_eq!(image.get_pixel(2, 2)[3], 0);
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert_eq!(image.get_pixel(5, 4)[3], 0);
+
+        // Clipped at the image edge
+        draw_outline(&mut image, PixelRect { x: 4, y: 3, width: 10, height: 10 }, red, 1);
+        assert_eq!(image.get_pixel(5, 3), &red);
+    }
+
+    #[test]
+    fn test_check_aspect() {
+        // Scaled modes of a 16:10 MacBook panel
+        assert!(check_aspect(1440, 900, 1680, 1050).is_ok());
+        assert!(check_aspect(1440, 900, 2880, 1800).is_ok());
+        // 1366x768 is not exactly 16:9
+        assert!(check_aspect(1920, 1080, 1366, 768).is_ok());
+        assert!(check_aspect(1440, 900, 1920, 1080).is_err());
+        assert!(check_aspect(1440, 900, 0, 900).is_err());
+    }
+
+    #[test]
+    fn test_stitch_negative_origin() {
+        let primary = RgbaImage::from_pixel(4, 3, image::Rgba([255, 0, 0, 255]));
+        let left = RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 255, 255]));
+        let desktop = stitch(&[(0, 0, &primary), (-2, 1, &left)]);
+
+        assert_eq!(desktop.dimensions(), (6, 3));
+        assert_eq!(desktop.get_pixel(2, 0), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(desktop.get_pixel(0, 1), &image::Rgba([0, 0, 255, 255]));
+        // Above the left display nothing is captured
+        assert_eq!(desktop.get_pixel(0, 0), &image::Rgba([0, 0, 0, 0]));
     }
 
     #[test]
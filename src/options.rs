@@ -0,0 +1,292 @@
+//! Options for single-frame captures
+
+use cidre::{cg, sc};
+use image::RgbaImage;
+use tracing::debug;
+
+use crate::capture;
+use crate::monitor;
+
+/// Pixel density of captured images
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CoordinateSpace {
+    /// One pixel per point, so a window capture matches [`crate::Window::width`]
+    /// and [`crate::Window::height`] on any display
+    #[default]
+    Logical,
+    /// Native pixels, i.e. logical size times the display's scale factor
+    /// (twice as wide and tall on Retina displays)
+    Physical,
+}
+
+/// How the mouse cursor appears in a capture
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorMode {
+    /// No cursor
+    Hidden,
+    /// The system cursor, drawn by ScreenCaptureKit
+    System,
+    /// A custom bitmap drawn at the mouse location after capture
+    ///
+    /// The image's top-left corner is placed at the pointer location (the
+    /// hotspot of the standard arrow) and alpha-blended over the capture. It is
+    /// drawn at its own pixel size, so supply a larger bitmap to make the
+    /// cursor stand out in high-DPI recordings.
+    Custom(RgbaImage),
+}
+
+/// How a capture is scaled when the output size differs from the captured area
+///
+/// Maps to `SCStreamConfiguration.scalesToFit` and `preservesAspectRatio`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalingMode {
+    /// Scale to fill the output exactly, distorting the image if the aspect ratios differ
+    Stretch,
+    /// Scale to fit inside the output, keeping the aspect ratio and padding
+    /// the remaining edges with black
+    Fit,
+    /// Don't scale; content is drawn at its own size and cropped or padded to the output
+    None,
+}
+
+/// What to do when a scaled capture's aspect ratio differs from the display's
+///
+/// Only applies with [`ScalingMode::Fit`], the default for
+/// [`crate::Monitor::capture_at_resolution_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LetterboxPolicy {
+    /// Keep the whole display and pad the remaining edges with black bars
+    #[default]
+    Pad,
+    /// Cut the display's edges so its center fills the whole image
+    Crop,
+    /// Fail instead of producing a padded or cropped image
+    Error,
+}
+
+/// What fills the transparent parts of a window capture
+///
+/// Window captures that render the window on its own (with
+/// [`CaptureOptions::include_child_windows`] or [`CaptureOptions::include_shadow`])
+/// have a transparent background around and behind the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BackgroundMode {
+    /// Leave the background transparent
+    #[default]
+    Transparent,
+    /// Composite the window over a solid RGB color
+    Color([u8; 3]),
+    /// Composite the window over the desktop wallpaper behind it
+    ///
+    /// Desktop icons are included, other apps' windows are not.
+    Wallpaper,
+}
+
+/// Mirroring applied to a captured image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FlipMode {
+    /// Leave the image as captured
+    #[default]
+    None,
+    /// Mirror left to right, e.g. for a front-facing preview
+    Horizontal,
+    /// Mirror top to bottom, e.g. for a teleprompter
+    Vertical,
+    /// Mirror both ways, the same as a 180 degree rotation
+    Both,
+}
+
+impl FlipMode {
+    /// Mirror an image in place
+    pub(crate) fn apply(self, image: &mut RgbaImage) {
+        if matches!(self, FlipMode::Horizontal | FlipMode::Both) {
+            image::imageops::flip_horizontal_in_place(image);
+        }
+        if matches!(self, FlipMode::Vertical | FlipMode::Both) {
+            image::imageops::flip_vertical_in_place(image);
+        }
+    }
+}
+
+/// Options controlling how a single frame is captured
+///
+/// `CaptureOptions::default()` reproduces the behavior of `capture_image()`.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Ask ScreenCaptureKit for tone-mapped SDR output on HDR displays
+    ///
+    /// Capturing an HDR display into 8-bit BGRA without tone mapping clips
+    /// highlights and washes out colors. When set, the stream's capture dynamic
+    /// range is pinned to SDR so the frame looks right in SDR viewers. This
+    /// requires macOS 15.0+; on older versions the option is ignored and the
+    /// capture behaves as if it were unset.
+    pub hdr_to_sdr: bool,
+    /// Whether images are in logical (point) or physical (pixel) size
+    ///
+    /// Defaults to [`CoordinateSpace::Logical`], which is what
+    /// `capture_image()` has always returned.
+    pub coordinate_space: CoordinateSpace,
+    /// How to draw the cursor
+    ///
+    /// `None` keeps the default for the capture target: the system cursor for
+    /// monitors and no cursor for windows.
+    pub cursor: Option<CursorMode>,
+    /// Convert captures to sRGB
+    ///
+    /// Captured pixels are normally in the display's color space (e.g. Display
+    /// P3), so the same content produces different values on different
+    /// monitors. When set, ScreenCaptureKit is asked for sRGB output, and if a
+    /// frame still arrives in another color space it is converted from the
+    /// display's profile on the CPU. Useful for OCR and image comparison.
+    pub normalize_srgb: bool,
+    /// Include a window's sheets, popovers and tooltips in window captures
+    ///
+    /// The image grows to cover child windows that extend past the window's
+    /// frame, and only the window and its children are rendered, so other
+    /// apps' windows overlapping it are left out. Children are found by
+    /// matching process and overlapping frames, since macOS exposes no
+    /// parent-child relationship. Ignored for monitor captures.
+    pub include_child_windows: bool,
+    /// Include the window's drop shadow in window captures
+    ///
+    /// By default window captures are cropped to the window frame, so they
+    /// match [`crate::Window::bounds`] with no shadow pixels. When set, the
+    /// window is rendered on its own with its shadow on a transparent
+    /// background, and the image grows by the shadow's margins. This requires
+    /// macOS 14.0+; on older versions, or together with
+    /// `include_child_windows`, the option is ignored. Ignored for monitor captures.
+    pub include_shadow: bool,
+    /// How the capture is scaled to the output size
+    ///
+    /// `None` keeps the default for the method: [`ScalingMode::None`] for
+    /// `capture_image` and `capture_image_with` on monitors and windows, which
+    /// capture at the source size so no scaling happens, and
+    /// [`ScalingMode::Fit`] for [`crate::Monitor::capture_at_resolution`].
+    /// Preserving the aspect ratio requires macOS 14.0+; on older versions
+    /// `Fit` behaves like `Stretch`.
+    pub scaling: Option<ScalingMode>,
+    /// Capture only the desktop: the wallpaper and Finder desktop icons
+    ///
+    /// Every app window is left out, giving a clean-desktop screenshot without
+    /// hiding anything on screen. Ignored for window captures.
+    pub desktop_only: bool,
+    /// How to handle an aspect ratio mismatch in scaled captures
+    ///
+    /// See [`LetterboxPolicy`]. Ignored unless scaling is [`ScalingMode::Fit`].
+    pub letterbox: LetterboxPolicy,
+    /// Background of window captures that render the window on its own
+    ///
+    /// See [`BackgroundMode`]. The result is fully opaque unless the mode is
+    /// [`BackgroundMode::Transparent`]. Ignored for monitor captures and
+    /// ordinary window captures, which are cropped from the screen and never
+    /// transparent.
+    pub background: BackgroundMode,
+    /// Mirror the captured image
+    ///
+    /// Applied last, after the cursor is drawn, so the whole image is mirrored
+    /// consistently. Defaults to [`FlipMode::None`].
+    pub flip: FlipMode,
+    /// Upper bound on the memory of the captured frame, in bytes
+    ///
+    /// If the display would be captured at more than this many bytes of RGBA
+    /// (4 bytes per pixel), ScreenCaptureKit scales it down, keeping the aspect
+    /// ratio, until it fits, so one 8K display cannot allocate hundreds of MB.
+    /// Window captures are cropped from a capture of their display, so the cap
+    /// applies to that display frame and the window shrinks with it. Use
+    /// [`crate::Monitor::capture_image_with_scale`] to learn the scale that
+    /// was applied. `None`, the default, never downscales.
+    pub max_bytes: Option<usize>,
+}
+
+impl CaptureOptions {
+    /// Get the custom cursor bitmap to composite after capture, if any
+    pub(crate) fn custom_cursor(&self) -> Option<&RgbaImage> {
+        match &self.cursor {
+            Some(CursorMode::Custom(image)) => Some(image),
+            _ => None,
+        }
+    }
+
+    /// Get the number of output pixels per point for a display
+    ///
+    /// `sck_width` and `sck_height` are the display's size in points as
+    /// reported by ScreenCaptureKit.
+    pub(crate) fn output_scale(&self, display_id: u32, sck_width: u32, sck_height: u32) -> f64 {
+        match self.coordinate_space {
+            CoordinateSpace::Logical => 1.0,
+            CoordinateSpace::Physical => monitor::display_scale_factor(display_id, sck_width, sck_height),
+        }
+    }
+
+    /// Lower `scale` so that a `width` x `height` point capture fits in [`CaptureOptions::max_bytes`]
+    pub(crate) fn capped_scale(&self, scale: f64, width: u32, height: u32) -> f64 {
+        match self.max_bytes {
+            Some(max_bytes) => capture::capped_scale(scale, width, height, max_bytes),
+            None => scale,
+        }
+    }
+
+    /// Apply the options to a stream configuration
+    pub(crate) fn apply(&self, cfg: &mut sc::StreamCfg) {
+        match self.cursor {
+            // Custom cursors are composited after capture, over a cursor-free frame
+            Some(CursorMode::Hidden) | Some(CursorMode::Custom(_)) => cfg.set_shows_cursor(false),
+            Some(CursorMode::System) => cfg.set_shows_cursor(true),
+            None => {}
+        }
+
+        if let Some(scaling) = self.scaling {
+            cfg.set_scales_to_fit(scaling != ScalingMode::None);
+            // preservesAspectRatio was added in macOS 14.0
+            if scaling != ScalingMode::None && capture::is_os_at_least(14, 0) {
+                cfg.set_preserves_aspect_ratio(scaling == ScalingMode::Fit);
+            } else if scaling == ScalingMode::Fit {
+                debug!("ScalingMode::Fit needs macOS 14.0+ to keep the aspect ratio, stretching instead");
+            }
+        }
+
+        if self.normalize_srgb {
+            cfg.set_color_space_name(cg::color_space::names::srgb());
+        }
+
+        if self.hdr_to_sdr {
+            // captureDynamicRange was added in macOS 15.0
+            if capture::is_os_at_least(15, 0) {
+                cfg.set_capture_dynamic_range(sc::CaptureDynamicRange::Sdr);
+            } else {
+                debug!("hdr_to_sdr requested but captureDynamicRange needs macOS 15.0+, ignoring");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_logical() {
+        let options = CaptureOptions::default();
+        assert_eq!(options.coordinate_space, CoordinateSpace::Logical);
+        assert_eq!(options.output_scale(1, 1440, 900), 1.0);
+        assert_eq!(options.scaling, None);
+        assert_eq!(options.flip, FlipMode::None);
+    }
+
+    #[test]
+    fn test_flip_modes() {
+        // 3x2 BGRA with a distinct value in every pixel
+        let raw: Vec<u8> = (0..6).flat_map(|i| [0, 0, i, 255]).collect();
+        let source = capture::capture_from_buffer(&raw, 3, 2, 12, capture::BufferFormat::Bgra8).unwrap();
+        let flipped = |mode: FlipMode| {
+            let mut image = source.clone();
+            mode.apply(&mut image);
+            image.pixels().map(|p| p[0]).collect::<Vec<u8>>()
+        };
+
+        assert_eq!(flipped(FlipMode::None), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(flipped(FlipMode::Horizontal), vec![2, 1, 0, 5, 4, 3]);
+        assert_eq!(flipped(FlipMode::Vertical), vec![3, 4, 5, 0, 1, 2]);
+        assert_eq!(flipped(FlipMode::Both), vec![5, 4, 3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,109 @@
+//! Screen recording permission checks
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::debug;
+
+use crate::capture;
+use crate::error::{XCapError, XCapResult, USER_DECLINED};
+use crate::monitor;
+use crate::options::{CaptureOptions, ScalingMode};
+
+// FFI bindings for the screen capture access functions (not exposed by cidre)
+extern "C" {
+    fn CGPreflightScreenCaptureAccess() -> bool;
+    fn CGRequestScreenCaptureAccess() -> bool;
+}
+
+/// Whether `request_permission` has been called in this process
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Screen recording permission state of the current process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionStatus {
+    /// Screen recording is allowed
+    Granted,
+    /// The user denied access, or turned it off in System Settings
+    Denied,
+    /// Access has not been granted and has not been requested by this process yet
+    ///
+    /// macOS does not report whether the user was ever asked, so this only means
+    /// [`request_permission`] has not been called since the process started.
+    NotDetermined,
+}
+
+/// Check the screen recording permission without prompting the user
+///
+/// Uses `CGPreflightScreenCaptureAccess`. The result reflects the TCC state at
+/// process launch; macOS usually requires a restart before a newly granted
+/// permission is picked up.
+pub fn permission_status() -> PermissionStatus {
+    let granted = unsafe { CGPreflightScreenCaptureAccess() };
+    status_from(granted, REQUESTED.load(Ordering::Relaxed))
+}
+
+/// Ask the user for screen recording permission
+///
+/// Uses `CGRequestScreenCaptureAccess`, which shows the system prompt the first
+/// time it is called for an app and is a no-op afterwards. Returns `true` if
+/// access is already granted.
+pub fn request_permission() -> bool {
+    REQUESTED.store(true, Ordering::Relaxed);
+    let granted = unsafe { CGRequestScreenCaptureAccess() };
+    debug!("Screen capture access requested, granted={}", granted);
+    granted
+}
+
+/// Check that capturing actually works by taking a 1x1 capture of the primary display
+///
+/// [`permission_status`] can be stale: TCC decisions are cached per process,
+/// so it may say `Denied` after the user granted access or `Granted` after
+/// access was revoked. This performs a real capture, scaled down to a single
+/// pixel so it is cheap, and returns `Ok(())` only if it succeeded. A missing
+/// permission fails with [`crate::ErrorKind::PermissionDenied`]; other errors
+/// are passed through. Never shows the system prompt.
+pub fn probe_capture() -> XCapResult<()> {
+    let content = capture::get_shareable_content()?;
+    let displays = content.displays();
+    let primary = displays
+        .iter()
+        .find(|d| {
+            let frame = d.frame();
+            frame.origin.x == 0.0 && frame.origin.y == 0.0
+        })
+        .or_else(|| displays.first())
+        .ok_or_else(monitor::no_displays_error)?;
+
+    let options = CaptureOptions {
+        scaling: Some(ScalingMode::Stretch),
+        ..Default::default()
+    };
+    let result = capture::capture_monitor_scaled_sync(primary.display_id().0, 1, 1, options);
+    debug!("Capture probe: {:?}", result.as_ref().map(|_| ()));
+    result.map(|_| ()).map_err(|e| match e.os_error() {
+        // SCStreamErrorUserDeclined: the content fetch can succeed from a stale cache while the capture is refused
+        Some(os_error) if os_error.code() == USER_DECLINED => XCapError::permission_denied_os(os_error.clone()),
+        _ => e,
+    })
+}
+
+fn status_from(granted: bool, requested: bool) -> PermissionStatus {
+    match (granted, requested) {
+        (true, _) => PermissionStatus::Granted,
+        (false, true) => PermissionStatus::Denied,
+        (false, false) => PermissionStatus::NotDetermined,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_from() {
+        assert_eq!(status_from(true, false), PermissionStatus::Granted);
+        assert_eq!(status_from(true, true), PermissionStatus::Granted);
+        assert_eq!(status_from(false, true), PermissionStatus::Denied);
+        assert_eq!(status_from(false, false), PermissionStatus::NotDetermined);
+    }
+}
@@ -0,0 +1,151 @@
+//! Perceptual hashing of frames for near-duplicate detection
+//!
+//! The hash is the classic DCT pHash: luminance is averaged into a 32x32 grid,
+//! the grid's 8x8 lowest DCT frequencies are compared with their median, and
+//! each comparison is one bit. Frames that look alike differ in few bits even
+//! when compression noise or a blinking cursor changes exact pixel values.
+
+/// Side of the luminance grid the DCT runs on
+const GRID: usize = 32;
+/// Side of the block of low frequencies that make up the hash
+const HASH_SIDE: usize = 8;
+
+/// Accumulates block-averaged luminance one row of RGBA pixels at a time
+///
+/// Lets the conversion loop feed rows as it writes them, so hashing needs no
+/// second pass over the frame.
+pub(crate) struct PerceptualHasher {
+    /// Grid column of every image column
+    columns: Vec<usize>,
+    height: usize,
+    sums: [u64; GRID * GRID],
+    counts: [u32; GRID * GRID],
+}
+
+impl PerceptualHasher {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        let width = width.max(1) as usize;
+        PerceptualHasher {
+            columns: (0..width).map(|x| x * GRID / width).collect(),
+            height: height.max(1) as usize,
+            sums: [0; GRID * GRID],
+            counts: [0; GRID * GRID],
+        }
+    }
+
+    /// Add row `y` of the image, as tightly packed RGBA
+    pub(crate) fn add_row(&mut self, y: usize, rgba: &[u8]) {
+        let row = (y * GRID / self.height).min(GRID - 1) * GRID;
+        for (pixel, &column) in rgba.chunks_exact(4).zip(&self.columns) {
+            // BT.601 luma in fixed point
+            let luma = (77 * pixel[0] as u64 + 150 * pixel[1] as u64 + 29 * pixel[2] as u64) >> 8;
+            self.sums[row + column] += luma;
+            self.counts[row + column] += 1;
+        }
+    }
+
+    /// Compute the hash of the rows added so far
+    pub(crate) fn finish(&self) -> u64 {
+        let total: u64 = self.sums.iter().sum();
+        let pixels: u64 = self.counts.iter().map(|&c| c as u64).sum();
+        // Images smaller than the grid leave cells empty; give them the overall mean
+        let fallback = total as f64 / pixels.max(1) as f64;
+        let grid: Vec<f64> = self
+            .sums
+            .iter()
+            .zip(&self.counts)
+            .map(|(&sum, &count)| if count == 0 { fallback } else { sum as f64 / count as f64 })
+            .collect();
+
+        let coefficients = low_frequencies(&grid);
+        let mut sorted = coefficients;
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = (sorted[31] + sorted[32]) / 2.0;
+        coefficients
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c > median)
+            .fold(0u64, |hash, (i, _)| hash | 1 << i)
+    }
+}
+
+/// Get the 8x8 lowest frequencies of the 2D DCT-II of a 32x32 grid, row by row
+fn low_frequencies(grid: &[f64]) -> [f64; HASH_SIDE * HASH_SIDE] {
+    let mut basis = [[0.0; GRID]; HASH_SIDE];
+    for (u, row) in basis.iter_mut().enumerate() {
+        let scale = if u == 0 { (1.0 / GRID as f64).sqrt() } else { (2.0 / GRID as f64).sqrt() };
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = scale * ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * GRID) as f64).cos();
+        }
+    }
+
+    // Transform the columns, then the rows, keeping only low frequencies
+    let mut columns = [[0.0; GRID]; HASH_SIDE];
+    for (v, out) in columns.iter_mut().enumerate() {
+        for (x, value) in out.iter_mut().enumerate() {
+            *value = (0..GRID).map(|y| basis[v][y] * grid[y * GRID + x]).sum();
+        }
+    }
+    let mut coefficients = [0.0; HASH_SIDE * HASH_SIDE];
+    for v in 0..HASH_SIDE {
+        for u in 0..HASH_SIDE {
+            coefficients[v * HASH_SIDE + u] = (0..GRID).map(|x| basis[u][x] * columns[v][x]).sum();
+        }
+    }
+    coefficients
+}
+
+/// Compute the perceptual hash of an image, as the conversion loop does
+#[cfg(test)]
+pub(crate) fn hash_image(image: &image::RgbaImage) -> u64 {
+    let mut hasher = PerceptualHasher::new(image.width(), image.height());
+    let row_bytes = image.width() as usize * 4;
+    for (y, row) in image.as_raw().chunks_exact(row_bytes.max(1)).enumerate() {
+        hasher.add_row(y, row);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let v = ((x * 255 / width + y * 64 / height) % 256) as u8;
+            image::Rgba([v, v, v, 255])
+        })
+    }
+
+    #[test]
+    fn test_similar_images_hash_close() {
+        let image = gradient(320, 200);
+        let mut noisy = image.clone();
+        for (i, pixel) in noisy.pixels_mut().enumerate() {
+            // Small deterministic noise, like compression artifacts
+            let delta = (i % 5) as u8;
+            pixel.0[0] = pixel.0[0].saturating_add(delta);
+        }
+        let distance = (hash_image(&image) ^ hash_image(&noisy)).count_ones();
+        assert!(distance <= 4, "distance {}", distance);
+
+        // Same content at another size hashes alike too
+        let smaller = image::imageops::resize(&image, 160, 100, image::imageops::FilterType::Triangle);
+        assert!((hash_image(&image) ^ hash_image(&smaller)).count_ones() <= 6);
+    }
+
+    #[test]
+    fn test_different_images_hash_apart() {
+        let image = gradient(320, 200);
+        let mut inverted = image.clone();
+        image::imageops::invert(&mut inverted);
+        assert!((hash_image(&image) ^ hash_image(&inverted)).count_ones() >= 32);
+    }
+
+    #[test]
+    fn test_tiny_image() {
+        let image = gradient(3, 2);
+        assert_eq!(hash_image(&image), hash_image(&image.clone()));
+    }
+}
@@ -0,0 +1,629 @@
+//! Screen recording to a movie file via AVAssetWriter
+//!
+//! ScreenCaptureKit's sample buffers go straight into an `AVAssetWriter`, whose
+//! inputs compress them with VideoToolbox (H.264 or HEVC, plus AAC for audio)
+//! and mux them into an MP4 or QuickTime file. Frames are never converted to
+//! RGBA, so recording costs far less CPU than encoding [`crate::Frame`]s.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+
+use cidre::{arc, av, cm, cv, define_obj_type, dispatch, ns, objc, sc};
+use tracing::debug;
+
+use crate::capture;
+use crate::error::{XCapError, XCapResult};
+use crate::ffi::lock;
+
+/// Largest frame side H.264 encoders accept
+const H264_MAX_SIDE: u32 = 4096;
+
+/// Surfaces ScreenCaptureKit keeps in flight, leaving the encoder room to catch up
+const RECORDING_QUEUE_DEPTH: isize = 5;
+
+/// `kAudioFormatMPEG4AAC`
+const AUDIO_FORMAT_AAC: u32 = 0x6161_6320;
+/// Sample rate and channel count ScreenCaptureKit delivers audio in by default
+const AUDIO_SAMPLE_RATE: f64 = 48_000.0;
+const AUDIO_CHANNELS: u32 = 2;
+/// AAC bitrate in bits per second
+const AUDIO_BITRATE: u32 = 128_000;
+
+// Output settings keys and codec names exported by AVFoundation
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {
+    static AVVideoCodecKey: &'static ns::String;
+    static AVVideoCodecTypeH264: &'static ns::String;
+    static AVVideoCodecTypeHEVC: &'static ns::String;
+    static AVVideoWidthKey: &'static ns::String;
+    static AVVideoHeightKey: &'static ns::String;
+    static AVVideoCompressionPropertiesKey: &'static ns::String;
+    static AVVideoAverageBitRateKey: &'static ns::String;
+    static AVVideoExpectedSourceFrameRateKey: &'static ns::String;
+    static AVFormatIDKey: &'static ns::String;
+    static AVSampleRateKey: &'static ns::String;
+    static AVNumberOfChannelsKey: &'static ns::String;
+    static AVEncoderBitRateKey: &'static ns::String;
+}
+
+/// Build an output settings dictionary
+fn settings(entries: &[(&ns::String, &ns::Id)]) -> arc::R<ns::Dictionary<ns::String, ns::Id>> {
+    let keys: Vec<&ns::String> = entries.iter().map(|&(key, _)| key).collect();
+    let values: Vec<&ns::Id> = entries.iter().map(|&(_, value)| value).collect();
+    ns::Dictionary::with_keys_values(&keys, &values)
+}
+
+/// Get the description of the writer's error, if it has one
+fn writer_error(writer: &av::AssetWriter) -> String {
+    writer
+        .error()
+        .map(|e| e.localized_description().to_string())
+        .unwrap_or_else(|| "unknown error".to_string())
+}
+
+/// Video codec used by [`crate::Monitor::record_to_file`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VideoCodec {
+    /// H.264, playable everywhere; limited to 4096 pixels per side
+    #[default]
+    H264,
+    /// HEVC (H.265), about half the size at the same quality and up to 8K
+    Hevc,
+}
+
+/// Configuration for [`crate::Monitor::record_to_file`]
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    /// Video codec, encoded in hardware by VideoToolbox where available
+    pub codec: VideoCodec,
+    /// Target average video bitrate, in bits per second
+    pub bitrate: u32,
+    /// Maximum number of frames per second recorded
+    ///
+    /// ScreenCaptureKit only emits frames when content changes, so a static
+    /// screen is recorded as fewer, longer frames.
+    pub fps: u32,
+    /// Record system audio as AAC alongside the video (macOS 13.0+)
+    pub include_audio: bool,
+    /// Leave this process's own sound out of the recorded audio
+    ///
+    /// Maps to `excludesCurrentProcessAudio`, so an app that plays sounds
+    /// (e.g. a start chime) does not record itself. Has no effect without
+    /// `include_audio`.
+    pub exclude_current_process_audio: bool,
+    /// Whether to draw the cursor into the recording
+    pub show_cursor: bool,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::H264,
+            bitrate: 8_000_000,
+            fps: 30,
+            include_audio: false,
+            exclude_current_process_audio: false,
+            show_cursor: true,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Check the configuration for a `width` x `height` recording
+    fn validate(&self, width: u32, height: u32) -> XCapResult<()> {
+        if self.fps == 0 {
+            return Err(XCapError::new("Recording fps must be greater than 0"));
+        }
+        if self.bitrate == 0 {
+            return Err(XCapError::new("Recording bitrate must be greater than 0"));
+        }
+        if self.codec == VideoCodec::H264 && width.max(height) > H264_MAX_SIDE {
+            return Err(XCapError::new(format!(
+                "H.264 supports at most {} pixels per side, recording is {}x{}; use VideoCodec::Hevc",
+                H264_MAX_SIDE, width, height
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Movie container, chosen from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Mp4,
+    QuickTime,
+}
+
+fn container_for(path: &Path) -> XCapResult<Container> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp4") | Some("m4v") => Ok(Container::Mp4),
+        Some("mov") => Ok(Container::QuickTime),
+        _ => Err(XCapError::new(format!(
+            "Cannot record to {}: the file name must end in .mp4 or .mov",
+            path.display()
+        ))),
+    }
+}
+
+/// An `AVAssetWriter` and its inputs
+///
+/// Appends run on ScreenCaptureKit's output queue and finishing on the
+/// recording thread; the surrounding mutex keeps them from overlapping.
+struct Writer {
+    writer: arc::R<av::AssetWriter>,
+    video: arc::R<av::AssetWriterInput>,
+    audio: Option<arc::R<av::AssetWriterInput>>,
+    /// Whether the session started, which happens at the first video frame
+    started: bool,
+    /// Whether samples are no longer accepted
+    closed: bool,
+}
+
+// SAFETY: AVAssetWriter may be used from any thread as long as calls are
+// serialized, which the mutex around every Writer guarantees.
+unsafe impl Send for Writer {}
+
+impl Writer {
+    /// Create a writer for `path` and start writing
+    fn new(path: &Path, container: Container, config: &RecordingConfig, width: u32, height: u32) -> XCapResult<Self> {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| XCapError::new(format!("Recording path is not valid UTF-8: {}", path.display())))?;
+        let url = ns::Url::with_fs_path_str(path_str, false);
+        let file_type = match container {
+            Container::Mp4 => av::FileType::mp4(),
+            Container::QuickTime => av::FileType::qt(),
+        };
+        let mut writer = av::AssetWriter::with_url_and_file_type(&url, file_type)
+            .map_err(|e| XCapError::capture_failed_ns("Failed to create movie writer", &e))?;
+
+        // The AVFoundation statics are only read
+        let codec = match config.codec {
+            VideoCodec::H264 => unsafe { AVVideoCodecTypeH264 },
+            VideoCodec::Hevc => unsafe { AVVideoCodecTypeHEVC },
+        };
+        let bitrate = ns::Number::with_u32(config.bitrate);
+        let fps = ns::Number::with_u32(config.fps);
+        let width_number = ns::Number::with_u32(width);
+        let height_number = ns::Number::with_u32(height);
+        let compression = unsafe {
+            settings(&[
+                (AVVideoAverageBitRateKey, bitrate.as_id_ref()),
+                (AVVideoExpectedSourceFrameRateKey, fps.as_id_ref()),
+            ])
+        };
+        let video_settings = unsafe {
+            settings(&[
+                (AVVideoCodecKey, codec.as_id_ref()),
+                (AVVideoWidthKey, width_number.as_id_ref()),
+                (AVVideoHeightKey, height_number.as_id_ref()),
+                (AVVideoCompressionPropertiesKey, compression.as_id_ref()),
+            ])
+        };
+        let video = add_input(&mut writer, av::MediaType::video(), &video_settings, "video")?;
+
+        let audio = if config.include_audio {
+            let format = ns::Number::with_u32(AUDIO_FORMAT_AAC);
+            let sample_rate = ns::Number::with_f64(AUDIO_SAMPLE_RATE);
+            let channels = ns::Number::with_u32(AUDIO_CHANNELS);
+            let audio_bitrate = ns::Number::with_u32(AUDIO_BITRATE);
+            let audio_settings = unsafe {
+                settings(&[
+                    (AVFormatIDKey, format.as_id_ref()),
+                    (AVSampleRateKey, sample_rate.as_id_ref()),
+                    (AVNumberOfChannelsKey, channels.as_id_ref()),
+                    (AVEncoderBitRateKey, audio_bitrate.as_id_ref()),
+                ])
+            };
+            Some(add_input(&mut writer, av::MediaType::audio(), &audio_settings, "audio")?)
+        } else {
+            None
+        };
+
+        if !writer.start_writing() {
+            return Err(XCapError::new(format!(
+                "Failed to start writing {}: {}",
+                path.display(),
+                writer_error(&writer)
+            )));
+        }
+        Ok(Writer {
+            writer,
+            video,
+            audio,
+            started: false,
+            closed: false,
+        })
+    }
+
+    /// Append a sample buffer from the stream, returning whether it was written
+    ///
+    /// Idle video frames carry no image and are skipped; the session starts
+    /// at the first real frame, and audio before it is dropped so the movie
+    /// does not open on a gap.
+    fn append(&mut self, kind: sc::OutputType, sample_buf: &cm::SampleBuf) -> bool {
+        if self.closed || !sample_buf.is_valid() {
+            return false;
+        }
+        let is_video = kind == sc::OutputType::Screen;
+        let input = if is_video {
+            if sample_buf.image_buf().is_none() {
+                return false;
+            }
+            if !self.started {
+                self.writer.start_session_at_src_time(sample_buf.pts());
+                self.started = true;
+            }
+            &mut self.video
+        } else {
+            match self.audio.as_mut() {
+                Some(audio) if kind == sc::OutputType::Audio && self.started => audio,
+                _ => return false,
+            }
+        };
+
+        if !input.is_ready_for_more_media_data() {
+            debug!("Encoder busy, dropping a {} sample", if is_video { "video" } else { "audio" });
+            return false;
+        }
+        matches!(input.append_sample_buf(sample_buf), Ok(true))
+    }
+
+    /// Stop accepting samples and mark the inputs finished
+    ///
+    /// Returns the asset writer, ready to be finished. Fails if nothing was
+    /// recorded, in which case writing is cancelled and the empty file removed.
+    fn close(&mut self) -> XCapResult<arc::R<av::AssetWriter>> {
+        self.closed = true;
+        if !self.started {
+            self.writer.cancel_writing();
+            return Err(XCapError::capture_failed("No frames were recorded"));
+        }
+        self.video.mark_as_finished();
+        if let Some(audio) = self.audio.as_mut() {
+            audio.mark_as_finished();
+        }
+        Ok(self.writer.retained())
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // A writer dropped before close() never produced a valid file
+        if !self.closed {
+            self.writer.cancel_writing();
+        }
+    }
+}
+
+/// Create a real-time input with the given settings and add it to the writer
+fn add_input(
+    writer: &mut av::AssetWriter,
+    media_type: &av::MediaType,
+    settings: &ns::Dictionary<ns::String, ns::Id>,
+    kind: &str,
+) -> XCapResult<arc::R<av::AssetWriterInput>> {
+    let rejected = || XCapError::new(format!("The movie writer rejected the {} settings", kind));
+    let mut input =
+        av::AssetWriterInput::with_media_type_and_output_settings(media_type, Some(settings)).map_err(|_| rejected())?;
+    input.set_expects_media_data_in_real_time(true);
+    if !writer.can_add_input(&input) {
+        return Err(rejected());
+    }
+    writer.add_input(&input).map_err(|_| rejected())?;
+    Ok(input)
+}
+
+/// Finish the movie file once the stream has stopped
+async fn finish(writer: &Mutex<Writer>) -> XCapResult<()> {
+    let mut asset_writer = lock(writer).close()?;
+    asset_writer.finish_writing().await;
+    match asset_writer.error() {
+        Some(e) => Err(XCapError::capture_failed_ns("Failed to finish the recording", e)),
+        None => Ok(()),
+    }
+}
+
+/// State shared with the SCStream output callback
+struct RecordingOutputInner {
+    writer: Arc<Mutex<Writer>>,
+    frames: Arc<AtomicU64>,
+}
+
+define_obj_type!(
+    RecordingOutput + sc::stream::OutputImpl,
+    RecordingOutputInner,
+    SCK_RS_RECORDING_OUTPUT
+);
+
+impl sc::stream::Output for RecordingOutput {}
+
+#[objc::add_methods]
+impl sc::stream::OutputImpl for RecordingOutput {
+    extern "C" fn impl_stream_did_output_sample_buf(
+        &mut self,
+        _cmd: Option<&objc::Sel>,
+        _stream: &sc::Stream,
+        sample_buf: &mut cm::SampleBuf,
+        kind: sc::OutputType,
+    ) {
+        let inner = self.inner_mut();
+        let written = lock(&inner.writer).append(kind, sample_buf);
+        if written && kind == sc::OutputType::Screen {
+            inner.frames.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A running recording started with [`crate::Monitor::record_to_file`]
+///
+/// The SCStream and the movie writer live on a dedicated thread. Call
+/// [`Recording::stop`] to finish the file and learn whether it was written;
+/// dropping the handle also finishes it, but any error is only logged.
+pub struct Recording {
+    path: PathBuf,
+    frames: Arc<AtomicU64>,
+    /// Asks the recording thread to stop; it replies with the outcome
+    control: mpsc::Sender<mpsc::Sender<XCapResult<()>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Recording {
+    /// Start recording a display to `path`
+    pub(crate) fn start_display(
+        display_id: u32,
+        width: u32,
+        height: u32,
+        path: &Path,
+        config: RecordingConfig,
+    ) -> XCapResult<Self> {
+        config.validate(width, height)?;
+        let container = container_for(path)?;
+        if config.include_audio && !capture::is_os_at_least(13, 0) {
+            return Err(XCapError::new("Recording audio needs macOS 13.0+"));
+        }
+        // AVAssetWriter refuses to overwrite, with a less helpful message
+        if path.exists() {
+            return Err(XCapError::new(format!("Cannot record to {}: the file already exists", path.display())));
+        }
+
+        let (control_tx, control_rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let frames = Arc::new(AtomicU64::new(0));
+        let setup = RecordingSetup {
+            display_id,
+            width,
+            height,
+            path: path.to_path_buf(),
+            container,
+            config,
+            frames: frames.clone(),
+        };
+
+        let thread = std::thread::Builder::new()
+            .name(format!("sck-rs-recording-{}", display_id))
+            .spawn(move || capture::block_on(run_recording(setup, control_rx, ready_tx)))
+            .map_err(|e| XCapError::with_source("Failed to spawn recording thread", e))?;
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                path: path.to_path_buf(),
+                frames,
+                control: control_tx,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(XCapError::capture_failed("Recording thread exited before starting"))
+            }
+        }
+    }
+
+    /// Get the file being recorded to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the number of video frames written so far
+    pub fn frames_written(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Stop recording and finish the file
+    ///
+    /// Blocks until the encoder has flushed and the file is complete. Fails if
+    /// no frame was recorded (the file is then removed) or the writer failed,
+    /// e.g. because the disk is full.
+    pub fn stop(mut self) -> XCapResult<()> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        let (reply, reply_rx) = mpsc::channel();
+        let result = match self.control.send(reply) {
+            Ok(()) => reply_rx
+                .recv()
+                .unwrap_or_else(|_| Err(XCapError::capture_failed("Recording thread exited"))),
+            Err(_) => Err(XCapError::capture_failed("Recording thread exited")),
+        };
+        let _ = thread.join();
+        result
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            debug!("Recording to {} dropped with an error: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl std::fmt::Debug for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recording")
+            .field("path", &self.path)
+            .field("frames_written", &self.frames_written())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+/// Everything the recording thread needs to set up the stream and writer
+struct RecordingSetup {
+    display_id: u32,
+    width: u32,
+    height: u32,
+    path: PathBuf,
+    container: Container,
+    config: RecordingConfig,
+    frames: Arc<AtomicU64>,
+}
+
+/// Body of the recording thread: owns the SCStream and writer for their whole lifetime
+async fn run_recording(
+    setup: RecordingSetup,
+    control: mpsc::Receiver<mpsc::Sender<XCapResult<()>>>,
+    ready: mpsc::Sender<XCapResult<()>>,
+) {
+    let display_id = setup.display_id;
+    let (stream, _output, writer) = match start_recording(setup).await {
+        Ok(started) => {
+            let _ = ready.send(Ok(()));
+            started
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+
+    // Wait for a stop request (or the handle being dropped)
+    let reply = control.recv().ok();
+
+    if let Err(e) = stream.stop().await {
+        debug!("Failed to stop recording stream for display {}: {:?}", display_id, e);
+    }
+    let result = finish(&writer).await;
+    debug!("Recording of display {} finished: {:?}", display_id, result);
+    if let Some(reply) = reply {
+        let _ = reply.send(result);
+    }
+}
+
+/// Create the writer, then create and start an SCStream feeding it
+async fn start_recording(
+    setup: RecordingSetup,
+) -> XCapResult<(arc::R<sc::Stream>, arc::R<RecordingOutput>, Arc<Mutex<Writer>>)> {
+    let RecordingSetup {
+        display_id,
+        width,
+        height,
+        path,
+        container,
+        config,
+        frames,
+    } = setup;
+
+    let content = sc::ShareableContent::current()
+        .await
+        .map_err(capture::map_content_error)?;
+
+    let displays = content.displays();
+    let display = displays
+        .iter()
+        .find(|d| d.display_id().0 == display_id)
+        .ok_or_else(|| XCapError::monitor_not_found(display_id))?;
+
+    capture::check_texture_size(width, height)?;
+    let filter = capture::display_filter(&content, display);
+    let writer = Arc::new(Mutex::new(Writer::new(&path, container, &config, width, height)?));
+
+    let mut cfg = sc::StreamCfg::new();
+    cfg.set_width(width as usize);
+    cfg.set_height(height as usize);
+    // Biplanar YUV is what the encoders consume, so frames need no conversion
+    cfg.set_pixel_format(cv::PixelFormat::_420V);
+    cfg.set_shows_cursor(config.show_cursor);
+    cfg.set_scales_to_fit(false);
+    cfg.set_minimum_frame_interval(cm::Time::new(1, config.fps as i32));
+    cfg.set_queue_depth(RECORDING_QUEUE_DEPTH);
+    if config.include_audio {
+        cfg.set_captures_audio(true);
+        cfg.set_excludes_current_process_audio(config.exclude_current_process_audio);
+    }
+
+    debug!(
+        "Recording display {} at {}x{} to {}, codec={:?}, bitrate={}, fps={}, audio={}",
+        display_id,
+        width,
+        height,
+        path.display(),
+        config.codec,
+        config.bitrate,
+        config.fps,
+        config.include_audio
+    );
+
+    let output = RecordingOutput::with(RecordingOutputInner {
+        writer: writer.clone(),
+        frames,
+    });
+    // One serial queue for video and audio keeps appends in order
+    let queue = dispatch::Queue::serial_with_ar(None);
+    let stream = sc::Stream::new(&filter, &cfg);
+    stream
+        .add_stream_output(output.as_ref(), sc::OutputType::Screen, Some(&queue))
+        .map_err(|e| XCapError::capture_failed_ns("Failed to add stream output", &e))?;
+    if config.include_audio {
+        stream
+            .add_stream_output(output.as_ref(), sc::OutputType::Audio, Some(&queue))
+            .map_err(|e| XCapError::capture_failed_ns("Failed to add audio output", &e))?;
+    }
+    stream
+        .start()
+        .await
+        .map_err(|e| XCapError::capture_failed_ns("Failed to start recording stream", &e))?;
+
+    Ok((stream, output, writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_for() {
+        assert_eq!(container_for(Path::new("/tmp/a.mp4")).unwrap(), Container::Mp4);
+        assert_eq!(container_for(Path::new("/tmp/a.M4V")).unwrap(), Container::Mp4);
+        assert_eq!(container_for(Path::new("clip.mov")).unwrap(), Container::QuickTime);
+        assert!(container_for(Path::new("/tmp/a.gif")).is_err());
+        assert!(container_for(Path::new("/tmp/recording")).is_err());
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let config = RecordingConfig::default();
+        assert!(config.validate(1920, 1080).is_ok());
+        // H.264 stops at 4096 per side; HEVC takes 5K
+        assert!(config.validate(5120, 2880).is_err());
+        let hevc = RecordingConfig {
+            codec: VideoCodec::Hevc,
+            ..Default::default()
+        };
+        assert!(hevc.validate(5120, 2880).is_ok());
+
+        assert!(RecordingConfig { fps: 0, ..Default::default() }.validate(1920, 1080).is_err());
+        assert!(RecordingConfig { bitrate: 0, ..Default::default() }.validate(1920, 1080).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Fixed-size in-memory recording of recent frames on top of a CaptureStream
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::error::{XCapError, XCapResult};
+use crate::stream::{CaptureStats, CaptureStream, Frame, StreamConfig};
+
+/// How often the drain thread checks whether it should stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Configuration for a ring recording started with [`crate::Monitor::record_ring`]
+#[derive(Debug, Clone)]
+pub struct RingConfig {
+    /// Maximum number of frames per second recorded
+    pub fps: u32,
+    /// Number of frames kept; the oldest frame is evicted once this is reached
+    ///
+    /// Memory use is bounded by roughly `capacity_frames` full RGBA images
+    /// (about 8 MB each for a 1920x1080 capture).
+    pub capacity_frames: usize,
+}
+
+impl Default for RingConfig {
+    fn default() -> Self {
+        Self {
+            fps: 2,
+            capacity_frames: 60,
+        }
+    }
+}
+
+impl RingConfig {
+    fn validate(&self) -> XCapResult<()> {
+        if self.capacity_frames == 0 {
+            return Err(XCapError::new("Ring capacity_frames must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+/// Bounded FIFO that evicts its oldest entry when full
+#[derive(Debug)]
+struct Ring<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Clone> Ring<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append an item, returning true if the oldest one was evicted to make room
+    fn push(&mut self, item: T) -> bool {
+        let evicted = self.items.len() >= self.capacity;
+        if evicted {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+        evicted
+    }
+
+    fn snapshot(&self) -> Vec<T> {
+        self.items.iter().cloned().collect()
+    }
+}
+
+/// A running ring recording of a monitor
+///
+/// A background thread drains the underlying [`CaptureStream`] as fast as
+/// frames arrive, so the stream never backs up; the ring itself is what bounds
+/// memory. Recording stops when [`RingHandle::stop`] is called or the handle is
+/// dropped.
+pub struct RingHandle {
+    ring: Arc<Mutex<Ring<Frame>>>,
+    stats: Arc<Mutex<CaptureStats>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RingHandle {
+    /// Start recording a display into a new ring
+    pub(crate) fn start_display(display_id: u32, width: u32, height: u32, config: RingConfig) -> XCapResult<Self> {
+        config.validate()?;
+
+        let stream_config = StreamConfig {
+            fps: config.fps,
+            ..Default::default()
+        };
+        let stream = CaptureStream::start_display(display_id, width, height, stream_config)?;
+
+        let ring = Arc::new(Mutex::new(Ring::new(config.capacity_frames)));
+        let stats = Arc::new(Mutex::new(stream.stats()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_ring = ring.clone();
+        let thread_stats = stats.clone();
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name(format!("sck-rs-ring-{}", display_id))
+            .spawn(move || drain(stream, thread_ring, thread_stats, thread_stop))
+            .map_err(|e| XCapError::with_source("Failed to spawn ring thread", e))?;
+
+        Ok(Self {
+            ring,
+            stats,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Copy the buffered frames, oldest first and newest last
+    pub fn snapshot(&self) -> Vec<Frame> {
+        self.ring.lock().unwrap().snapshot()
+    }
+
+    /// Get the number of frames currently buffered
+    pub fn len(&self) -> usize {
+        self.ring.lock().unwrap().items.len()
+    }
+
+    /// Check whether no frames have been buffered yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the delivery statistics of the underlying stream
+    pub fn stats(&self) -> CaptureStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Stop recording and wait for the background thread to exit
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for RingHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl std::fmt::Debug for RingHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingHandle")
+            .field("frames", &self.len())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+/// Body of the ring thread: move frames from the stream into the ring until stopped
+fn drain(
+    stream: CaptureStream,
+    ring: Arc<Mutex<Ring<Frame>>>,
+    stats: Arc<Mutex<CaptureStats>>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut evicted: u64 = 0;
+    while !stop.load(Ordering::Relaxed) {
+        if let Some(frame) = stream.recv_timeout(STOP_POLL_INTERVAL) {
+            if ring.lock().unwrap().push(frame) {
+                evicted += 1;
+            }
+        }
+        *stats.lock().unwrap() = stream.stats();
+        if stream.is_stopped() {
+            // Keep the buffered frames; the cause is in the stats' last_error
+            break;
+        }
+    }
+    debug!("Ring recording stopped, {} frames evicted", evicted);
+    stream.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_evicts_oldest() {
+        let mut ring = Ring::new(3);
+        assert!(!ring.push(1));
+        assert!(!ring.push(2));
+        assert!(!ring.push(3));
+        assert!(ring.push(4));
+        assert_eq!(ring.snapshot(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_zero_capacity_rejected() {
+        let config = RingConfig { capacity_frames: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+        assert!(RingConfig::default().validate().is_ok());
+    }
+}
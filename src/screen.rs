@@ -0,0 +1,64 @@
+//! NSScreen lookups for display metadata not exposed by ScreenCaptureKit
+
+use crate::error::XCapResult;
+use crate::ffi::{self, class, from_ns_string, ns_string, sel, send_f64, send_id, send_id_id, send_id_usize, send_u32, send_usize};
+use crate::main_thread;
+
+/// Metadata about the NSScreen backing a display
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenInfo {
+    /// Localized product name, e.g. "Built-in Retina Display" or "LG UltraFine"
+    pub name: Option<String>,
+    /// Backing scale factor reported by AppKit (1.0 or 2.0 on current hardware)
+    pub backing_scale_factor: f64,
+    /// Largest EDR color component value the screen can show (1.0 on SDR displays)
+    pub max_edr: f64,
+}
+
+/// Look up the NSScreen for a CGDirectDisplayID
+///
+/// Matches on the `NSScreenNumber` entry of each screen's device description.
+/// Returns `None` if AppKit does not know the display. NSScreen is not
+/// thread-safe, so the lookup runs on the main thread; a `ThreadingViolation`
+/// error is returned if the main thread is unavailable.
+pub fn screen_info(display_id: u32) -> XCapResult<Option<ScreenInfo>> {
+    main_thread::run_on_main("NSScreen", move || ffi::autorelease_pool(|| unsafe { find_screen_info(display_id) }))
+}
+
+unsafe fn find_screen_info(display_id: u32) -> Option<ScreenInfo> {
+    let cls = class(b"NSScreen\0");
+    if cls.is_null() {
+        return None;
+    }
+
+    let screens = send_id(cls, sel(b"screens\0"));
+    if screens.is_null() {
+        return None;
+    }
+
+    let screen_number_key = ns_string(b"NSScreenNumber\0");
+    let count = send_usize(screens, sel(b"count\0"));
+    for i in 0..count {
+        let screen = send_id_usize(screens, sel(b"objectAtIndex:\0"), i);
+        let description = send_id(screen, sel(b"deviceDescription\0"));
+        if description.is_null() {
+            continue;
+        }
+        let number = send_id_id(description, sel(b"objectForKey:\0"), screen_number_key);
+        if number.is_null() || send_u32(number, sel(b"unsignedIntValue\0")) != display_id {
+            continue;
+        }
+
+        // localizedName is macOS 10.15+
+        let name = from_ns_string(send_id(screen, sel(b"localizedName\0"))).filter(|n| !n.is_empty());
+        let backing_scale_factor = send_f64(screen, sel(b"backingScaleFactor\0"));
+        let max_edr = send_f64(screen, sel(b"maximumPotentialExtendedDynamicRangeColorComponentValue\0"));
+        return Some(ScreenInfo {
+            name,
+            backing_scale_factor,
+            max_edr,
+        });
+    }
+
+    None
+}
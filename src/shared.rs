@@ -0,0 +1,392 @@
+//! Frames in shared memory for cross-process transfer
+
+use std::ffi::{c_char, c_void, CString};
+use std::fs::File;
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+
+use crate::error::{XCapError, XCapResult};
+
+/// Identifies a region written by this crate ("SCKF")
+const MAGIC: u32 = 0x534b_4346;
+/// Layout version of the header
+const VERSION: u32 = 1;
+/// Bytes reserved for the header before the pixel data
+pub const SHARED_HEADER_SIZE: usize = 64;
+/// How many times a reader retries when it races a writer
+const READ_ATTEMPTS: usize = 100;
+
+// Byte offsets of the header fields
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_VERSION: usize = 4;
+const OFFSET_SEQLOCK: usize = 8;
+const OFFSET_WIDTH: usize = 16;
+const OFFSET_HEIGHT: usize = 20;
+const OFFSET_STRIDE: usize = 24;
+const OFFSET_TIMESTAMP: usize = 32;
+
+// POSIX constants on macOS
+const O_RDWR: i32 = 0x0002;
+const O_CREAT: i32 = 0x0200;
+const PROT_READ: i32 = 0x01;
+const PROT_WRITE: i32 = 0x02;
+const MAP_SHARED: i32 = 0x0001;
+
+// FFI bindings for POSIX shared memory
+extern "C" {
+    fn shm_open(name: *const c_char, oflag: i32, ...) -> i32;
+    fn shm_unlink(name: *const c_char) -> i32;
+    fn ftruncate(fd: i32, length: i64) -> i32;
+    fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+    fn close(fd: i32) -> i32;
+}
+
+/// Description of the frame stored in a [`SharedRegion`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Image width in pixels
+    pub width: u32,
+    /// Image height in pixels
+    pub height: u32,
+    /// Bytes per row of the RGBA pixel data (`width * 4`)
+    pub stride: u32,
+    /// Time of the capture since the Unix epoch
+    pub timestamp: Duration,
+    /// Number of frames written to the region so far, starting at 1
+    pub sequence: u64,
+}
+
+/// A memory region holding the latest captured frame, readable from other processes
+///
+/// The region starts with a [`SHARED_HEADER_SIZE`]-byte header followed by
+/// tightly packed RGBA pixels. Writes are guarded by a sequence lock: a writer
+/// makes the sequence odd while it copies, so a reader in another process
+/// ([`SharedRegion::read_frame`]) retries instead of returning a torn frame.
+/// One writer at a time is supported; any number of processes may read.
+///
+/// ```rust,no_run
+/// use sck_rs::{Monitor, SharedRegion};
+///
+/// // Writer process
+/// let monitor = Monitor::primary().unwrap();
+/// let mut region = SharedRegion::create("/sck-frames", SharedRegion::size_for(3840, 2160)).unwrap();
+/// let header = monitor.capture_into_shared(&mut region).unwrap();
+///
+/// // Reader process
+/// let region = SharedRegion::open("/sck-frames", SharedRegion::size_for(3840, 2160)).unwrap();
+/// if let Some((header, image)) = region.read_frame() {
+///     println!("frame {} is {}x{}", header.sequence, header.width, header.height);
+/// }
+/// ```
+pub struct SharedRegion {
+    ptr: *mut u8,
+    len: usize,
+    /// Whether `ptr` came from `mmap` and must be unmapped on drop
+    mapped: bool,
+    /// Name to unlink on drop, for regions this process created
+    owned_name: Option<CString>,
+}
+
+// SAFETY: the region is plain memory; concurrent access goes through the
+// sequence lock and atomic header fields.
+unsafe impl Send for SharedRegion {}
+unsafe impl Sync for SharedRegion {}
+
+impl SharedRegion {
+    /// Get the region size needed for frames up to `width` x `height`
+    pub fn size_for(width: u32, height: u32) -> usize {
+        SHARED_HEADER_SIZE + width as usize * height as usize * 4
+    }
+
+    /// Create (or truncate) a named POSIX shared memory object of `len` bytes and map it
+    ///
+    /// `name` follows `shm_open` rules: it starts with `/` and is at most 31
+    /// bytes on macOS. The object is unlinked when this region is dropped;
+    /// processes that already opened it keep their mapping.
+    pub fn create(name: &str, len: usize) -> XCapResult<SharedRegion> {
+        let mut region = Self::map(name, len, true)?;
+        region.init();
+        Ok(region)
+    }
+
+    /// Map an existing named shared memory object created by [`SharedRegion::create`]
+    ///
+    /// Fails if the object is smaller than `len`, since touching memory mapped
+    /// past its end would crash the process.
+    pub fn open(name: &str, len: usize) -> XCapResult<SharedRegion> {
+        Self::map(name, len, false)
+    }
+
+    /// Use memory mapped by the caller, e.g. with a crate like `memmap2`
+    ///
+    /// The header is initialized, discarding any frame already in the memory.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads and writes of `len` bytes, aligned to 8
+    /// bytes, and outlive the returned region.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> XCapResult<SharedRegion> {
+        if len < SHARED_HEADER_SIZE || ptr.is_null() || ptr as usize % 8 != 0 {
+            return Err(XCapError::new("Shared region must be 8-byte aligned and hold at least the header"));
+        }
+        let mut region = SharedRegion {
+            ptr,
+            len,
+            mapped: false,
+            owned_name: None,
+        };
+        region.init();
+        Ok(region)
+    }
+
+    fn map(name: &str, len: usize, create: bool) -> XCapResult<SharedRegion> {
+        if len < SHARED_HEADER_SIZE {
+            return Err(XCapError::new(format!(
+                "Shared region needs at least {} bytes, got {}",
+                SHARED_HEADER_SIZE, len
+            )));
+        }
+        let c_name = CString::new(name).map_err(|e| XCapError::with_source("Invalid shared memory name", e))?;
+
+        unsafe {
+            let flags = if create { O_RDWR | O_CREAT } else { O_RDWR };
+            let fd = shm_open(c_name.as_ptr(), flags, 0o600 as u32);
+            if fd < 0 {
+                return Err(XCapError::with_source(
+                    format!("shm_open {} failed", name),
+                    std::io::Error::last_os_error(),
+                ));
+            }
+            if !create {
+                // Borrow the fd for fstat; it is closed below
+                let file = ManuallyDrop::new(File::from_raw_fd(fd));
+                let size = match file.metadata() {
+                    Ok(metadata) => metadata.len(),
+                    Err(e) => {
+                        close(fd);
+                        return Err(XCapError::with_source(format!("fstat {} failed", name), e));
+                    }
+                };
+                if size < len as u64 {
+                    close(fd);
+                    return Err(XCapError::new(format!(
+                        "Shared memory object {} holds {} bytes, less than the {} requested",
+                        name, size, len
+                    )));
+                }
+            }
+            if create && ftruncate(fd, len as i64) != 0 {
+                let error = std::io::Error::last_os_error();
+                close(fd);
+                shm_unlink(c_name.as_ptr());
+                return Err(XCapError::with_source(format!("ftruncate {} failed", name), error));
+            }
+            let ptr = mmap(std::ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+            let error = std::io::Error::last_os_error();
+            close(fd);
+            // MAP_FAILED is (void *)-1
+            if ptr as isize == -1 {
+                if create {
+                    shm_unlink(c_name.as_ptr());
+                }
+                return Err(XCapError::with_source(format!("mmap {} failed", name), error));
+            }
+
+            Ok(SharedRegion {
+                ptr: ptr as *mut u8,
+                len,
+                mapped: true,
+                owned_name: create.then_some(c_name),
+            })
+        }
+    }
+
+    /// Get the size of the region in bytes, header included
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the region has no room for pixels at all
+    pub fn is_empty(&self) -> bool {
+        self.len <= SHARED_HEADER_SIZE
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: offsets are within the header, which is 8-byte aligned
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU32) }
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: as above, and u64 offsets are multiples of 8
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+    }
+
+    fn init(&mut self) {
+        self.u64_at(OFFSET_SEQLOCK).store(0, Ordering::Relaxed);
+        self.u32_at(OFFSET_MAGIC).store(MAGIC, Ordering::Relaxed);
+        self.u32_at(OFFSET_VERSION).store(VERSION, Ordering::Release);
+    }
+
+    /// Copy a frame into the region, replacing the previous one
+    pub fn write_frame(&mut self, image: &RgbaImage) -> XCapResult<FrameHeader> {
+        let pixels = image.as_raw();
+        if SHARED_HEADER_SIZE + pixels.len() > self.len {
+            return Err(XCapError::new(format!(
+                "{}x{} frame needs {} bytes but the shared region holds {}",
+                image.width(),
+                image.height(),
+                SHARED_HEADER_SIZE + pixels.len(),
+                self.len
+            )));
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        // An odd sequence tells readers a write is in progress
+        let seqlock = self.u64_at(OFFSET_SEQLOCK);
+        let start = seqlock.load(Ordering::Relaxed) | 1;
+        seqlock.store(start, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        self.u32_at(OFFSET_WIDTH).store(image.width(), Ordering::Relaxed);
+        self.u32_at(OFFSET_HEIGHT).store(image.height(), Ordering::Relaxed);
+        self.u32_at(OFFSET_STRIDE).store(image.width() * 4, Ordering::Relaxed);
+        self.u64_at(OFFSET_TIMESTAMP).store(timestamp.as_nanos() as u64, Ordering::Relaxed);
+        unsafe {
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), self.ptr.add(SHARED_HEADER_SIZE), pixels.len());
+        }
+
+        let end = start + 1;
+        seqlock.store(end, Ordering::Release);
+
+        Ok(FrameHeader {
+            width: image.width(),
+            height: image.height(),
+            stride: image.width() * 4,
+            timestamp,
+            sequence: end / 2,
+        })
+    }
+
+    /// Copy the latest frame out of the region
+    ///
+    /// Returns `None` if no frame has been written yet, the region was not
+    /// initialized by this crate, or a writer kept the region busy for every
+    /// retry.
+    pub fn read_frame(&self) -> Option<(FrameHeader, RgbaImage)> {
+        if self.u32_at(OFFSET_MAGIC).load(Ordering::Acquire) != MAGIC
+            || self.u32_at(OFFSET_VERSION).load(Ordering::Acquire) != VERSION
+        {
+            return None;
+        }
+
+        let seqlock = self.u64_at(OFFSET_SEQLOCK);
+        for _ in 0..READ_ATTEMPTS {
+            let before = seqlock.load(Ordering::Acquire);
+            if before == 0 {
+                return None;
+            }
+            if before % 2 == 1 {
+                std::thread::yield_now();
+                continue;
+            }
+
+            let width = self.u32_at(OFFSET_WIDTH).load(Ordering::Relaxed);
+            let height = self.u32_at(OFFSET_HEIGHT).load(Ordering::Relaxed);
+            let stride = self.u32_at(OFFSET_STRIDE).load(Ordering::Relaxed);
+            let timestamp = Duration::from_nanos(self.u64_at(OFFSET_TIMESTAMP).load(Ordering::Relaxed));
+            let size = width as usize * height as usize * 4;
+            if SHARED_HEADER_SIZE + size > self.len {
+                // Torn header; the sequence check below would reject it anyway
+                std::thread::yield_now();
+                continue;
+            }
+            let mut pixels = vec![0u8; size];
+            unsafe {
+                std::ptr::copy_nonoverlapping(self.ptr.add(SHARED_HEADER_SIZE), pixels.as_mut_ptr(), size);
+            }
+
+            fence(Ordering::Acquire);
+            if seqlock.load(Ordering::Relaxed) != before {
+                continue;
+            }
+            let header = FrameHeader {
+                width,
+                height,
+                stride,
+                timestamp,
+                sequence: before / 2,
+            };
+            return RgbaImage::from_raw(width, height, pixels).map(|image| (header, image));
+        }
+        None
+    }
+}
+
+impl Drop for SharedRegion {
+    fn drop(&mut self) {
+        unsafe {
+            if self.mapped {
+                munmap(self.ptr as *mut c_void, self.len);
+            }
+            if let Some(name) = &self.owned_name {
+                shm_unlink(name.as_ptr());
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for SharedRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedRegion")
+            .field("len", &self.len)
+            .field("name", &self.owned_name)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_write_and_read_frame() {
+        let mut backing = vec![0u64; SharedRegion::size_for(4, 3) / 8];
+        let mut region = unsafe { SharedRegion::from_raw_parts(backing.as_mut_ptr() as *mut u8, backing.len() * 8) }.unwrap();
+        assert!(region.read_frame().is_none());
+
+        let image = RgbaImage::from_pixel(4, 3, Rgba([1, 2, 3, 4]));
+        let header = region.write_frame(&image).unwrap();
+        assert_eq!((header.width, header.height, header.stride, header.sequence), (4, 3, 16, 1));
+
+        let (read_header, read_image) = region.read_frame().unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(read_image, image);
+
+        let smaller = RgbaImage::from_pixel(2, 2, Rgba([9, 9, 9, 9]));
+        assert_eq!(region.write_frame(&smaller).unwrap().sequence, 2);
+        assert_eq!(region.read_frame().unwrap().1, smaller);
+    }
+
+    #[test]
+    fn test_open_checks_object_size() {
+        let name = format!("/sck-rs-{}", std::process::id());
+        let len = SharedRegion::size_for(2, 2);
+        let _owner = SharedRegion::create(&name, len).unwrap();
+        assert!(SharedRegion::open(&name, len).is_ok());
+        // Mapping past the end of the object would SIGBUS on first access
+        assert!(SharedRegion::open(&name, len * 1024).is_err());
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let mut backing = vec![0u64; SharedRegion::size_for(2, 2) / 8];
+        let mut region = unsafe { SharedRegion::from_raw_parts(backing.as_mut_ptr() as *mut u8, backing.len() * 8) }.unwrap();
+        assert!(region.write_frame(&RgbaImage::new(3, 3)).is_err());
+    }
+}
@@ -0,0 +1,1342 @@
+//! Continuous capture using an SCStream via cidre
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use arc_swap::ArcSwapOption;
+use cidre::{arc, cg, cm, cv, define_obj_type, dispatch, ns, objc, sc};
+use futures::task::AtomicWaker;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use tracing::debug;
+
+use crate::capture;
+use crate::color::{self, AlphaMode};
+use crate::cursor;
+use crate::error::{OsError, XCapError, XCapResult, USER_DECLINED};
+use crate::ffi::{i64_value, lock, CFArrayGetCount, CFArrayGetValueAtIndex, CFDictionaryGetValue, CFTypeRef};
+use crate::monitor;
+use crate::window::Window;
+
+/// Number of converted frames buffered for the consumer before new frames are dropped
+const FRAME_CHANNEL_CAPACITY: usize = 8;
+
+/// Maximum queue depth accepted by ScreenCaptureKit
+const MAX_QUEUE_DEPTH: usize = 8;
+
+/// How long a one-shot stream waits for its first frame
+const SINGLE_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `SCFrameStatus` values of a sample buffer's `SCStreamFrameInfoStatus` attachment
+const FRAME_STATUS_IDLE: i64 = 1;
+const FRAME_STATUS_BLANK: i64 = 2;
+const FRAME_STATUS_SUSPENDED: i64 = 3;
+const FRAME_STATUS_STOPPED: i64 = 5;
+
+// FFI bindings for the sample buffer attachments cidre does not expose
+extern "C" {
+    static SCStreamFrameInfoStatus: CFTypeRef;
+    static kCMSampleBufferAttachmentKey_DroppedFrameReason: CFTypeRef;
+    fn CMSampleBufferGetSampleAttachmentsArray(sbuf: CFTypeRef, create_if_necessary: bool) -> CFTypeRef;
+    fn CMGetAttachment(target: CFTypeRef, key: CFTypeRef, attachment_mode: *mut u32) -> CFTypeRef;
+}
+
+/// What ScreenCaptureKit says about a sample buffer it delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SampleStatus {
+    /// The `SCFrameStatus`, when the buffer carries frame info
+    frame_status: Option<i64>,
+    /// Whether the buffer is a marker for a frame the system dropped
+    dropped: bool,
+}
+
+impl SampleStatus {
+    /// Read the frame info and dropped-frame attachments of a sample buffer
+    fn read(sample_buf: &cm::SampleBuf) -> SampleStatus {
+        let sample_buf = sample_buf as *const cm::SampleBuf as CFTypeRef;
+        unsafe {
+            let dropped = !CMGetAttachment(
+                sample_buf,
+                kCMSampleBufferAttachmentKey_DroppedFrameReason,
+                std::ptr::null_mut(),
+            )
+            .is_null();
+            let attachments = CMSampleBufferGetSampleAttachmentsArray(sample_buf, false);
+            let frame_status = if attachments.is_null() || CFArrayGetCount(attachments) == 0 {
+                None
+            } else {
+                let info = CFArrayGetValueAtIndex(attachments, 0);
+                i64_value(CFDictionaryGetValue(info, SCStreamFrameInfoStatus))
+            };
+            SampleStatus { frame_status, dropped }
+        }
+    }
+
+    /// Why ScreenCaptureKit lost this frame, or `None` if it was delivered or idle
+    fn drop_reason(&self) -> Option<DropReason> {
+        if self.dropped {
+            return Some(DropReason::DroppedBySystem);
+        }
+        match self.frame_status {
+            Some(FRAME_STATUS_SUSPENDED) => Some(DropReason::Suspended),
+            Some(FRAME_STATUS_STOPPED) => Some(DropReason::Stopped),
+            _ => None,
+        }
+    }
+
+    /// Whether ScreenCaptureKit reported no content change
+    fn is_idle(&self) -> bool {
+        matches!(self.frame_status, Some(FRAME_STATUS_IDLE | FRAME_STATUS_BLANK))
+    }
+}
+
+/// Configuration for a [`CaptureStream`]
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Maximum number of frames per second delivered by the stream
+    ///
+    /// ScreenCaptureKit only emits frames when content changes, so this caps
+    /// the frame rate rather than guaranteeing it.
+    pub fps: u32,
+    /// Number of surfaces ScreenCaptureKit keeps in flight (maps to `SCStreamConfiguration.queueDepth`)
+    ///
+    /// Apple's default is 3 and the maximum is 8. Raising it lets the stream
+    /// absorb longer stalls in the consumer before dropping frames, at the cost
+    /// of latency and memory: each queued frame is a full pixel buffer
+    /// (about 33 MB for a 5K display at 4 bytes per pixel). Values of 3-5 are a
+    /// good range for recording; 1-2 minimize latency for live previews.
+    pub queue_depth: usize,
+    /// Whether to draw the cursor into captured frames
+    pub show_cursor: bool,
+    /// Align the frame interval to the display's refresh
+    ///
+    /// The minimum frame interval becomes a whole number of refresh periods
+    /// (the one closest to `1 / fps`), so frames land on vsync boundaries at
+    /// a steady cadence, e.g. every other refresh for 30 fps on a 60 Hz
+    /// display. Falls back to 60 Hz when the display does not report its rate.
+    /// ScreenCaptureKit still only emits frames when content changes, so this
+    /// caps and regularizes the cadence rather than guaranteeing it.
+    pub vsync: bool,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            fps: 30,
+            queue_depth: 3,
+            show_cursor: true,
+            vsync: false,
+        }
+    }
+}
+
+/// Refresh rate assumed for vsync alignment when the display does not report one
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
+/// Compute the minimum frame interval as a `(value, timescale)` pair for `CMTime`
+///
+/// Without a refresh rate this is simply `1 / fps`. With one, the interval is
+/// the whole number of refresh periods closest to `1 / fps` (at least one),
+/// expressed in millihertz so fractional rates like 59.94 Hz stay exact.
+fn frame_interval(fps: u32, refresh_rate: Option<f64>) -> (i64, i32) {
+    match refresh_rate {
+        None => (1, fps as i32),
+        Some(rate) => {
+            let periods = (rate / fps as f64).round().max(1.0) as i64;
+            (periods * 1000, (rate * 1000.0).round() as i32)
+        }
+    }
+}
+
+impl StreamConfig {
+    /// Check the configuration against ScreenCaptureKit's limits
+    fn validate(&self) -> XCapResult<()> {
+        if self.fps == 0 {
+            return Err(XCapError::new("Stream fps must be greater than 0"));
+        }
+        if self.queue_depth == 0 || self.queue_depth > MAX_QUEUE_DEPTH {
+            return Err(XCapError::new(format!(
+                "Stream queue_depth must be between 1 and {}, got {}",
+                MAX_QUEUE_DEPTH, self.queue_depth
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A single frame delivered by a [`CaptureStream`]
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The captured image, shared with the latest-frame mailbox
+    image: Arc<RgbaImage>,
+    /// Presentation timestamp reported by ScreenCaptureKit
+    timestamp: Duration,
+    /// Cursor position in image pixels, if it was drawn and inside the frame
+    cursor: Option<(u32, u32)>,
+    /// How the image's alpha channel is encoded
+    alpha_mode: AlphaMode,
+    /// Position among the frames ScreenCaptureKit delivered, starting at 0
+    sequence: u64,
+}
+
+impl Frame {
+    /// Get the captured image
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Consume the frame and return the captured image
+    ///
+    /// Copies the pixels only if the same frame is still held elsewhere, e.g.
+    /// by a clone or by the [`CaptureStream::latest_frame`] mailbox.
+    pub fn into_image(self) -> RgbaImage {
+        Arc::try_unwrap(self.image).unwrap_or_else(|image| (*image).clone())
+    }
+
+    /// Get the presentation timestamp of the frame
+    ///
+    /// This is relative to the host clock and is only meaningful for comparing
+    /// frames from the same stream.
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// Get the frame's sequence number
+    ///
+    /// Numbers are assigned as ScreenCaptureKit hands over frames, starting at
+    /// 0 and increasing by one. A gap between two received frames means the
+    /// frames in between were dropped, either because the consumer fell behind
+    /// or because conversion failed. Idle frames without new content and frames
+    /// ScreenCaptureKit drops itself are not numbered; see
+    /// [`CaptureStream::on_drop`] for those.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Get the cursor position within the image, in pixels
+    ///
+    /// The location is read when the frame arrives. Returns `None` when the
+    /// stream was configured without the cursor or the cursor is outside the
+    /// captured region.
+    pub fn cursor_position(&self) -> Option<(u32, u32)> {
+        self.cursor
+    }
+
+    /// Get how the image's alpha channel is encoded
+    ///
+    /// Display captures are [`AlphaMode::Opaque`]. Frames with transparent
+    /// pixels are usually [`AlphaMode::Premultiplied`]; blend them with a
+    /// premultiplied "over" operator or convert them with
+    /// [`Frame::unpremultiplied`] first, or edges get dark halos.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Get a copy of the image as a [`DynamicImage`], for generic `image` processing
+    pub fn to_dynamic_image(&self) -> DynamicImage {
+        DynamicImage::ImageRgba8((*self.image).clone())
+    }
+
+    /// Encode the frame's image in any format the `image` crate can write
+    ///
+    /// See [`crate::encode`] for the settings used.
+    pub fn encode(&self, format: ImageFormat) -> XCapResult<Vec<u8>> {
+        color::encode(&self.image, format)
+    }
+
+    /// Get a copy of the image with straight (non-premultiplied) alpha
+    ///
+    /// Returns the pixels unchanged unless the frame is premultiplied.
+    pub fn unpremultiplied(&self) -> RgbaImage {
+        let mut image = (*self.image).clone();
+        if self.alpha_mode == AlphaMode::Premultiplied {
+            color::unpremultiply(&mut image);
+        }
+        image
+    }
+}
+
+/// An item delivered by a [`CaptureStream`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CaptureEvent {
+    /// A captured frame
+    Frame(Frame),
+    /// ScreenCaptureKit stopped the stream on its own; no further events follow
+    ///
+    /// Sent when a display is disconnected or screen recording permission is
+    /// revoked mid-stream. Check [`XCapError::kind`] for the cause.
+    Stopped(XCapError),
+}
+
+/// Counters describing the health of a [`CaptureStream`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureStats {
+    /// Frames converted and handed to the consumer
+    pub frames_delivered: u64,
+    /// Frames lost before reaching the consumer
+    ///
+    /// Counts frames ScreenCaptureKit reported as dropped (e.g. under CPU
+    /// pressure) or not produced while the stream was suspended, as well as
+    /// frames discarded because the consumer fell behind or conversion failed.
+    pub frames_dropped: u64,
+    /// Idle updates where ScreenCaptureKit reported no content change
+    pub idle_updates: u64,
+    /// Mean time between delivered frames, once at least two have arrived
+    pub average_interval: Option<Duration>,
+    /// Description of the most recent error seen by the stream
+    pub last_error: Option<String>,
+}
+
+/// Why a stream frame was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The consumer had not picked up the buffered frames yet
+    ConsumerBehind,
+    /// The frame's pixel buffer could not be converted
+    ConversionFailed,
+    /// ScreenCaptureKit dropped the frame itself, e.g. under CPU pressure
+    DroppedBySystem,
+    /// No frame was produced because the stream was suspended, e.g. while the
+    /// display slept
+    Suspended,
+    /// No frame was produced because the stream was stopping
+    Stopped,
+}
+
+/// A dropped frame, as reported to a [`CaptureStream::on_drop`] callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedFrame {
+    /// Why the frame was dropped
+    pub reason: DropReason,
+    /// Frames dropped since the stream started, including this one
+    pub total_dropped: u64,
+}
+
+/// Callback registered with [`CaptureStream::on_drop`]
+type DropCallback = Box<dyn FnMut(DroppedFrame) + Send>;
+
+/// Accumulates [`CaptureStats`] from the output callback
+#[derive(Debug, Default)]
+struct StatsRecorder {
+    stats: CaptureStats,
+    first_timestamp: Option<Duration>,
+    last_timestamp: Option<Duration>,
+}
+
+impl StatsRecorder {
+    fn record_delivered(&mut self, timestamp: Duration) {
+        self.stats.frames_delivered += 1;
+        let first = *self.first_timestamp.get_or_insert(timestamp);
+        self.last_timestamp = Some(timestamp);
+        if self.stats.frames_delivered > 1 {
+            let span = timestamp.saturating_sub(first);
+            self.stats.average_interval = Some(span / (self.stats.frames_delivered - 1) as u32);
+        }
+    }
+
+    fn record_dropped(&mut self) {
+        self.stats.frames_dropped += 1;
+    }
+
+    fn record_idle(&mut self) {
+        self.stats.idle_updates += 1;
+    }
+
+    fn record_error(&mut self, error: &XCapError) {
+        self.stats.last_error = Some(error.to_string());
+    }
+}
+
+/// Count a dropped frame and tell the [`CaptureStream::on_drop`] callback, if any
+///
+/// No lock is held while the callback runs, so it may call
+/// [`CaptureStream::stats`] or replace itself with [`CaptureStream::on_drop`].
+/// A panic in the callback is caught and logged.
+fn record_drop(
+    stats: &Mutex<StatsRecorder>,
+    on_drop: &Mutex<Option<DropCallback>>,
+    reason: DropReason,
+    error: Option<&XCapError>,
+) {
+    let total_dropped = {
+        let mut stats = lock(stats);
+        stats.record_dropped();
+        if let Some(error) = error {
+            stats.record_error(error);
+        }
+        stats.stats.frames_dropped
+    };
+    // Drops are reported from one serial queue, so taking the callback out cannot race another drop
+    let Some(mut callback) = lock(on_drop).take() else {
+        return;
+    };
+    let dropped = DroppedFrame { reason, total_dropped };
+    if panic::catch_unwind(AssertUnwindSafe(|| callback(dropped))).is_err() {
+        debug!("on_drop callback panicked for {:?}", dropped);
+    }
+    // Keep a replacement registered while the callback ran
+    let mut slot = lock(on_drop);
+    if slot.is_none() {
+        *slot = Some(callback);
+    }
+}
+
+/// State shared with the SCStream output callback
+struct StreamOutputInner {
+    frames: mpsc::SyncSender<CaptureEvent>,
+    /// Wakes an async consumer polling the handle as a `Stream`
+    waker: Arc<AtomicWaker>,
+    /// Single-slot mailbox holding the newest frame, shared with the handle
+    latest: Arc<ArcSwapOption<Frame>>,
+    /// Set once the consumer reads the frame channel, shared with the handle
+    channel_read: Arc<AtomicBool>,
+    /// Statistics shared with the [`CaptureStream`] handle
+    stats: Arc<Mutex<StatsRecorder>>,
+    /// Callback set through [`CaptureStream::on_drop`]
+    on_drop: Arc<Mutex<Option<DropCallback>>>,
+    /// Whether the cursor is drawn into frames
+    show_cursor: bool,
+    /// Captured area in global points, used to place the cursor
+    region: cg::Rect,
+    /// Sequence number for the next frame with an image
+    next_sequence: u64,
+}
+
+impl StreamOutputInner {
+    fn handle_video(&mut self, sample_buf: &mut cm::SampleBuf) {
+        let status = SampleStatus::read(sample_buf);
+        if let Some(reason) = status.drop_reason() {
+            debug!("ScreenCaptureKit dropped a frame: {:?}", status);
+            record_drop(&self.stats, &self.on_drop, reason, None);
+            return;
+        }
+        // Idle and blank frames carry no image buffer
+        let image_buf = match sample_buf.image_buf() {
+            Some(image_buf) if !status.is_idle() => image_buf,
+            _ => {
+                lock(&self.stats).record_idle();
+                return;
+            }
+        };
+        let mut image_buf: arc::R<cv::ImageBuf> = image_buf.retained();
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let image = match capture::safe_convert(|| capture::image_buf_to_rgba(&mut image_buf)) {
+            Ok(image) => image,
+            Err(e) => {
+                debug!("Dropping stream frame: {}", e);
+                record_drop(&self.stats, &self.on_drop, DropReason::ConversionFailed, Some(&e));
+                return;
+            }
+        };
+
+        let cursor = if self.show_cursor {
+            cursor::location().and_then(|location| cursor::to_image_pixels(location, self.region, image.dimensions()))
+        } else {
+            None
+        };
+
+        let frame = Frame {
+            alpha_mode: color::alpha_mode(&image_buf, &image),
+            image: Arc::new(image),
+            timestamp: Duration::from_secs_f64(sample_buf.pts().as_secs().max(0.0)),
+            cursor,
+            sequence,
+        };
+
+        self.deliver(frame);
+    }
+
+    /// Hand a converted frame to the latest-frame mailbox and the frame channel
+    fn deliver(&mut self, frame: Frame) {
+        // Replaces the previous newest frame; the clone shares the pixels
+        self.latest.store(Some(Arc::new(frame.clone())));
+
+        let timestamp = frame.timestamp;
+        match self.frames.try_send(CaptureEvent::Frame(frame)) {
+            Ok(()) => {
+                lock(&self.stats).record_delivered(timestamp);
+                self.waker.wake();
+            }
+            // A consumer that only uses the mailbox still got the frame
+            Err(mpsc::TrySendError::Full(_)) if !self.channel_read.load(Ordering::Relaxed) => {
+                lock(&self.stats).record_delivered(timestamp);
+            }
+            Err(mpsc::TrySendError::Full(_)) => {
+                debug!("Frame channel full, dropping frame");
+                record_drop(&self.stats, &self.on_drop, DropReason::ConsumerBehind, None);
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+define_obj_type!(
+    StreamOutput + sc::stream::OutputImpl,
+    StreamOutputInner,
+    SCK_RS_STREAM_OUTPUT
+);
+
+impl sc::stream::Output for StreamOutput {}
+
+#[objc::add_methods]
+impl sc::stream::OutputImpl for StreamOutput {
+    extern "C" fn impl_stream_did_output_sample_buf(
+        &mut self,
+        _cmd: Option<&objc::Sel>,
+        _stream: &sc::Stream,
+        sample_buf: &mut cm::SampleBuf,
+        kind: sc::OutputType,
+    ) {
+        if kind == sc::OutputType::Screen {
+            self.inner_mut().handle_video(sample_buf);
+        }
+    }
+}
+
+/// Map the error ScreenCaptureKit reports when it stops a stream on its own
+fn map_stop_error(error: OsError) -> XCapError {
+    if error.code() == USER_DECLINED {
+        XCapError::permission_denied_os(error)
+    } else {
+        XCapError::capture_failed_os("Stream stopped by the system", error)
+    }
+}
+
+/// State shared with the SCStream delegate
+struct StreamDelegateInner {
+    events: mpsc::SyncSender<CaptureEvent>,
+    waker: Arc<AtomicWaker>,
+    stats: Arc<Mutex<StatsRecorder>>,
+}
+
+impl StreamDelegateInner {
+    fn handle_stopped(&mut self, error: &ns::Error) {
+        let error = map_stop_error(OsError::from_ns(error));
+        debug!("Stream stopped by ScreenCaptureKit: {}", error);
+        lock(&self.stats).record_error(&error);
+
+        // The terminal event must not be dropped like a frame when the channel
+        // is full, and the delegate queue must not block on a slow consumer, so
+        // hand it off to a thread that waits for room.
+        let events = self.events.clone();
+        let waker = self.waker.clone();
+        let _ = std::thread::Builder::new()
+            .name("sck-rs-stream-stopped".to_string())
+            .spawn(move || {
+                if events.send(CaptureEvent::Stopped(error)).is_ok() {
+                    waker.wake();
+                }
+            });
+    }
+}
+
+define_obj_type!(
+    StreamDelegate + sc::stream::DelegateImpl,
+    StreamDelegateInner,
+    SCK_RS_STREAM_DELEGATE
+);
+
+impl sc::stream::Delegate for StreamDelegate {}
+
+#[objc::add_methods]
+impl sc::stream::DelegateImpl for StreamDelegate {
+    extern "C" fn impl_stream_did_stop_with_err(
+        &mut self,
+        _cmd: Option<&objc::Sel>,
+        _stream: &sc::Stream,
+        error: &ns::Error,
+    ) {
+        self.inner_mut().handle_stopped(error);
+    }
+}
+
+/// A retained pixel buffer handed from the dispatch queue to the waiting capture
+struct SendImageBuf(arc::R<cv::ImageBuf>);
+
+// SAFETY: CVPixelBuffer is reference counted and safe to use from any thread;
+// the buffer is only read after being handed over.
+unsafe impl Send for SendImageBuf {}
+
+/// State for an output that delivers exactly one frame
+struct SingleFrameOutputInner {
+    frame: Option<tokio::sync::oneshot::Sender<SendImageBuf>>,
+}
+
+define_obj_type!(
+    SingleFrameOutput + sc::stream::OutputImpl,
+    SingleFrameOutputInner,
+    SCK_RS_SINGLE_FRAME_OUTPUT
+);
+
+impl sc::stream::Output for SingleFrameOutput {}
+
+#[objc::add_methods]
+impl sc::stream::OutputImpl for SingleFrameOutput {
+    extern "C" fn impl_stream_did_output_sample_buf(
+        &mut self,
+        _cmd: Option<&objc::Sel>,
+        _stream: &sc::Stream,
+        sample_buf: &mut cm::SampleBuf,
+        kind: sc::OutputType,
+    ) {
+        if kind != sc::OutputType::Screen {
+            return;
+        }
+        // Skip idle frames until one carries an image
+        let Some(image_buf) = sample_buf.image_buf() else {
+            return;
+        };
+        if let Some(sender) = self.inner_mut().frame.take() {
+            let _ = sender.send(SendImageBuf(image_buf.retained()));
+        }
+    }
+}
+
+/// Stops a one-shot stream if its capture is dropped before stopping it
+///
+/// Dropping happens when a cancellable capture is cancelled mid-await. The
+/// stop is asynchronous, so it is spawned on the runtime driving the capture.
+struct StopOnDrop(Option<arc::R<sc::Stream>>);
+
+/// A stream moved to the runtime to be stopped
+struct SendStream(arc::R<sc::Stream>);
+
+// SAFETY: SCStream may be stopped from any thread; the stream is only used to call stop.
+unsafe impl Send for SendStream {}
+
+impl StopOnDrop {
+    /// Take the stream back for a normal stop
+    fn disarm(&mut self) -> Option<arc::R<sc::Stream>> {
+        self.0.take()
+    }
+}
+
+impl SendStream {
+    async fn stop(self) {
+        if let Err(e) = self.0.stop().await {
+            debug!("Failed to stop abandoned one-shot stream: {:?}", e);
+        }
+    }
+}
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        let Some(stream) = self.0.take() else {
+            return;
+        };
+        debug!("One-shot stream dropped while running, stopping it");
+        let stream = SendStream(stream);
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(stream.stop());
+            return;
+        }
+        // No runtime to spawn on: stop it on the crate's runtime from a
+        // short-lived thread, so the drop does not block
+        let spawned = std::thread::Builder::new()
+            .name("sck-rs-stream-stop".to_string())
+            .spawn(move || capture::block_on(stream.stop()));
+        if let Err(e) = spawned {
+            debug!("Failed to spawn a thread to stop the one-shot stream: {}", e);
+        }
+    }
+}
+
+/// Capture one frame by running a short-lived SCStream
+///
+/// This is the single-frame path for macOS 12.3-13.x, where
+/// `SCScreenshotManager` is not available.
+pub(crate) async fn capture_single_frame(
+    filter: &sc::ContentFilter,
+    cfg: &sc::StreamCfg,
+) -> XCapResult<arc::R<cv::ImageBuf>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let output = SingleFrameOutput::with(SingleFrameOutputInner { frame: Some(tx) });
+    let queue = dispatch::Queue::serial_with_ar(None);
+    let stream = sc::Stream::new(filter, cfg);
+    stream
+        .add_stream_output(output.as_ref(), sc::OutputType::Screen, Some(&queue))
+        .map_err(|e| XCapError::capture_failed_ns("Failed to add stream output", &e))?;
+    let mut guard = StopOnDrop(Some(stream.clone()));
+    stream
+        .start()
+        .await
+        .map_err(|e| XCapError::capture_failed_ns("Failed to start stream", &e))?;
+
+    let result = tokio::time::timeout(SINGLE_FRAME_TIMEOUT, rx).await;
+
+    if let Some(stream) = guard.disarm() {
+        if let Err(e) = stream.stop().await {
+            debug!("Failed to stop one-shot stream: {:?}", e);
+        }
+    }
+
+    match result {
+        Ok(Ok(SendImageBuf(image_buf))) => Ok(image_buf),
+        Ok(Err(_)) => Err(XCapError::capture_failed("Stream ended before delivering a frame")),
+        Err(_) => Err(XCapError::capture_failed(format!(
+            "Timed out after {:?} waiting for a stream frame",
+            SINGLE_FRAME_TIMEOUT
+        ))),
+    }
+}
+
+/// Commands sent from a [`CaptureStream`] handle to its stream thread
+enum Command {
+    Stop,
+    /// Replace the content filter, excluding these window ids
+    UpdateFilter {
+        excluded_window_ids: Vec<u32>,
+        reply: mpsc::Sender<XCapResult<()>>,
+    },
+}
+
+/// A running ScreenCaptureKit stream delivering frames continuously
+///
+/// The underlying SCStream lives on a dedicated thread and is stopped when
+/// [`CaptureStream::stop`] is called or the handle is dropped. If
+/// ScreenCaptureKit stops the stream on its own, the last event is
+/// [`CaptureEvent::Stopped`] with the cause.
+pub struct CaptureStream {
+    frames: mpsc::Receiver<CaptureEvent>,
+    waker: Arc<AtomicWaker>,
+    /// Set once the terminal [`CaptureEvent::Stopped`] has been received
+    stopped: AtomicBool,
+    latest: Arc<ArcSwapOption<Frame>>,
+    /// Set once the consumer reads the frame channel
+    channel_read: Arc<AtomicBool>,
+    stats: Arc<Mutex<StatsRecorder>>,
+    on_drop: Arc<Mutex<Option<DropCallback>>>,
+    control: mpsc::Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CaptureStream {
+    /// Start streaming a display
+    pub(crate) fn start_display(display_id: u32, width: u32, height: u32, config: StreamConfig) -> XCapResult<Self> {
+        config.validate()?;
+
+        let (frame_tx, frame_rx) = mpsc::sync_channel(FRAME_CHANNEL_CAPACITY);
+        let (control_tx, control_rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let stats = Arc::new(Mutex::new(StatsRecorder::default()));
+        let thread_stats = stats.clone();
+        let on_drop = Arc::new(Mutex::new(None));
+        let thread_on_drop = on_drop.clone();
+        let latest = Arc::new(ArcSwapOption::empty());
+        let thread_latest = latest.clone();
+        let channel_read = Arc::new(AtomicBool::new(false));
+        let thread_channel_read = channel_read.clone();
+        let waker = Arc::new(AtomicWaker::new());
+        let thread_waker = waker.clone();
+
+        let thread = std::thread::Builder::new()
+            .name(format!("sck-rs-stream-{}", display_id))
+            .spawn(move || {
+                let setup = StreamSetup {
+                    display_id,
+                    width,
+                    height,
+                    config,
+                    frames: frame_tx,
+                    waker: thread_waker,
+                    latest: thread_latest,
+                    channel_read: thread_channel_read,
+                    stats: thread_stats,
+                    on_drop: thread_on_drop,
+                };
+                capture::block_on(run_stream(setup, control_rx, ready_tx))
+            })
+            .map_err(|e| XCapError::with_source("Failed to spawn stream thread", e))?;
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                frames: frame_rx,
+                waker,
+                stopped: AtomicBool::new(false),
+                latest,
+                channel_read,
+                stats,
+                on_drop,
+                control: control_tx,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(XCapError::capture_failed("Stream thread exited before starting"))
+            }
+        }
+    }
+
+    /// Block until the next event is available
+    ///
+    /// Returns `None` once the stream has stopped. If ScreenCaptureKit stopped
+    /// it, [`CaptureEvent::Stopped`] is returned first.
+    pub fn recv_event(&self) -> Option<CaptureEvent> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.observe(self.frames().recv().ok())
+    }
+
+    /// Block until the next event is available or the timeout elapses
+    pub fn recv_event_timeout(&self, timeout: Duration) -> Option<CaptureEvent> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.observe(self.frames().recv_timeout(timeout).ok())
+    }
+
+    /// Get the next event if one is already available
+    pub fn try_recv_event(&self) -> Option<CaptureEvent> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.observe(self.frames().try_recv().ok())
+    }
+
+    /// Remember when the terminal event goes past
+    fn observe(&self, event: Option<CaptureEvent>) -> Option<CaptureEvent> {
+        if let Some(CaptureEvent::Stopped(_)) = event {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        event
+    }
+
+    /// Block until the next frame is available
+    ///
+    /// Returns `None` once the stream has stopped. The cause of an unexpected
+    /// stop is then available in [`CaptureStats::last_error`]; use
+    /// [`CaptureStream::recv_event`] to get it as an [`XCapError`].
+    pub fn recv(&self) -> Option<Frame> {
+        Self::into_frame(self.recv_event())
+    }
+
+    /// Block until the next frame is available or the timeout elapses
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Frame> {
+        Self::into_frame(self.recv_event_timeout(timeout))
+    }
+
+    /// Get the next frame if one is already available
+    pub fn try_recv(&self) -> Option<Frame> {
+        Self::into_frame(self.try_recv_event())
+    }
+
+    fn into_frame(event: Option<CaptureEvent>) -> Option<Frame> {
+        match event? {
+            CaptureEvent::Frame(frame) => Some(frame),
+            CaptureEvent::Stopped(_) => None,
+        }
+    }
+
+    /// Get the newest frame, skipping any older ones
+    ///
+    /// Backed by a single-slot mailbox that every delivered frame overwrites,
+    /// independently of the frame channel, so a live preview can render at its
+    /// own pace without building up latency. Repeated calls between frames
+    /// return the same frame again (sharing its pixels); compare
+    /// [`Frame::sequence`] to skip redraws. Returns `None` only before the
+    /// first frame. Frames returned here are still delivered by
+    /// [`CaptureStream::recv`]. A preview using only this method can ignore
+    /// the channel: until the channel is read, a full channel is not counted
+    /// as a drop and does not fire [`CaptureStream::on_drop`].
+    pub fn latest_frame(&self) -> Option<Frame> {
+        self.latest.load_full().map(|frame| (*frame).clone())
+    }
+
+    /// Get the frame channel, remembering that the consumer reads it
+    fn frames(&self) -> &mpsc::Receiver<CaptureEvent> {
+        self.channel_read.store(true, Ordering::Relaxed);
+        &self.frames
+    }
+
+    /// Check whether ScreenCaptureKit stopped the stream on its own
+    ///
+    /// Becomes true once the consumer has received [`CaptureEvent::Stopped`].
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Get a snapshot of the stream's delivery statistics
+    ///
+    /// A growing `frames_dropped` means the consumer is not keeping up (or the
+    /// system is under CPU pressure); consider lowering the fps or raising
+    /// `queue_depth`.
+    pub fn stats(&self) -> CaptureStats {
+        lock(&self.stats).stats.clone()
+    }
+
+    /// Call `callback` every time a frame is dropped
+    ///
+    /// Fires as soon as the drop happens, whether ScreenCaptureKit reported it
+    /// in the frame status or the frame was lost on the way to the consumer
+    /// (see [`DropReason`]), so recorders can react to CPU pressure (e.g.
+    /// lower their encode resolution) without polling
+    /// [`CaptureStream::stats`]. The callback runs on ScreenCaptureKit's output
+    /// queue and delays the next frame while it runs, so keep it short.
+    /// Replaces any previously set callback; the callback may call this
+    /// itself. A panicking callback is logged, not propagated.
+    pub fn on_drop<F>(&self, callback: F)
+    where
+        F: FnMut(DroppedFrame) + Send + 'static,
+    {
+        *lock(&self.on_drop) = Some(Box::new(callback));
+    }
+
+    /// Exclude a new set of windows from the running stream
+    ///
+    /// Replaces the windows excluded by the previous call (if any) with
+    /// `new_exclusions`, without restarting the stream, so there is no gap in
+    /// frames. Pass an empty slice to capture every window again. Windows of
+    /// this process stay excluded when [`crate::set_exclude_current_process`]
+    /// is enabled. Windows that have closed since they were listed are ignored.
+    pub fn update_filter(&self, new_exclusions: &[Window]) -> XCapResult<()> {
+        let (reply, reply_rx) = mpsc::channel();
+        let command = Command::UpdateFilter {
+            excluded_window_ids: new_exclusions.iter().map(|w| w.raw_id()).collect(),
+            reply,
+        };
+        if self.control.send(command).is_err() {
+            return Err(XCapError::capture_failed("Stream has stopped"));
+        }
+        reply_rx
+            .recv()
+            .unwrap_or_else(|_| Err(XCapError::capture_failed("Stream has stopped")))
+    }
+
+    /// Stop the stream and wait for the stream thread to exit
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.control.send(Command::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for CaptureStream {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Frames as an async stream, for use with `futures::StreamExt` combinators
+///
+/// Yields `Ok(frame)` for each frame, then `Err(cause)` once if
+/// ScreenCaptureKit stops the stream on its own, then ends. Polling never
+/// blocks: the output callback wakes the task when a frame arrives.
+impl futures::Stream for CaptureStream {
+    type Item = XCapResult<Frame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.is_stopped() {
+            return Poll::Ready(None);
+        }
+
+        let event = match this.frames().try_recv() {
+            Ok(event) => event,
+            Err(mpsc::TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(mpsc::TryRecvError::Empty) => {
+                this.waker.register(cx.waker());
+                // A frame may have arrived before the waker was registered
+                match this.frames.try_recv() {
+                    Ok(event) => event,
+                    Err(mpsc::TryRecvError::Empty) => return Poll::Pending,
+                    Err(mpsc::TryRecvError::Disconnected) => return Poll::Ready(None),
+                }
+            }
+        };
+
+        Poll::Ready(this.observe(Some(event)).map(|event| match event {
+            CaptureEvent::Frame(frame) => Ok(frame),
+            CaptureEvent::Stopped(error) => Err(error),
+        }))
+    }
+}
+
+impl std::fmt::Debug for CaptureStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptureStream")
+            .field("running", &self.thread.is_some())
+            .field("stopped", &self.is_stopped())
+            .finish()
+    }
+}
+
+/// Everything the stream thread needs to set up an SCStream
+struct StreamSetup {
+    display_id: u32,
+    width: u32,
+    height: u32,
+    config: StreamConfig,
+    frames: mpsc::SyncSender<CaptureEvent>,
+    waker: Arc<AtomicWaker>,
+    latest: Arc<ArcSwapOption<Frame>>,
+    channel_read: Arc<AtomicBool>,
+    stats: Arc<Mutex<StatsRecorder>>,
+    on_drop: Arc<Mutex<Option<DropCallback>>>,
+}
+
+/// Body of the stream thread: owns the SCStream for its whole lifetime
+async fn run_stream(
+    setup: StreamSetup,
+    control: mpsc::Receiver<Command>,
+    ready: mpsc::Sender<XCapResult<()>>,
+) {
+    let display_id = setup.display_id;
+    let (stream, _output, _delegate) = match start_stream(setup).await {
+        Ok(started) => {
+            let _ = ready.send(Ok(()));
+            started
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+
+    // Serve commands until a stop command (or the handle being dropped)
+    while let Ok(command) = control.recv() {
+        match command {
+            Command::Stop => break,
+            Command::UpdateFilter {
+                excluded_window_ids,
+                reply,
+            } => {
+                let _ = reply.send(update_filter(&stream, display_id, &excluded_window_ids).await);
+            }
+        }
+    }
+
+    if let Err(e) = stream.stop().await {
+        debug!("Failed to stop stream for display {}: {:?}", display_id, e);
+    }
+    debug!("Stream for display {} stopped", display_id);
+}
+
+/// Swap the content filter of a running display stream
+async fn update_filter(stream: &sc::Stream, display_id: u32, excluded_window_ids: &[u32]) -> XCapResult<()> {
+    let content = sc::ShareableContent::current()
+        .await
+        .map_err(capture::map_content_error)?;
+
+    let displays = content.displays();
+    let display = displays
+        .iter()
+        .find(|d| d.display_id().0 == display_id)
+        .ok_or_else(|| XCapError::monitor_not_found(display_id))?;
+
+    let filter = capture::display_filter_excluding(&content, display, excluded_window_ids);
+    stream
+        .update_content_filter(&filter)
+        .await
+        .map_err(|e| XCapError::capture_failed_ns("Failed to update content filter", &e))?;
+    debug!(
+        "Updated filter for display {} stream, excluding {} windows",
+        display_id,
+        excluded_window_ids.len()
+    );
+    Ok(())
+}
+
+/// Create and start an SCStream for a display
+async fn start_stream(
+    setup: StreamSetup,
+) -> XCapResult<(arc::R<sc::Stream>, arc::R<StreamOutput>, arc::R<StreamDelegate>)> {
+    let StreamSetup {
+        display_id,
+        width,
+        height,
+        config,
+        frames,
+        waker,
+        latest,
+        channel_read,
+        stats,
+        on_drop,
+    } = setup;
+
+    let content = sc::ShareableContent::current()
+        .await
+        .map_err(capture::map_content_error)?;
+
+    let displays = content.displays();
+    let display = displays
+        .iter()
+        .find(|d| d.display_id().0 == display_id)
+        .ok_or_else(|| XCapError::monitor_not_found(display_id))?;
+
+    capture::check_texture_size(width, height)?;
+    let filter = capture::display_filter(&content, display);
+
+    let mut cfg = sc::StreamCfg::new();
+    cfg.set_width(width as usize);
+    cfg.set_height(height as usize);
+    cfg.set_pixel_format(cv::PixelFormat::_32_BGRA);
+    cfg.set_shows_cursor(config.show_cursor);
+    cfg.set_scales_to_fit(false);
+    let refresh_rate = config
+        .vsync
+        .then(|| monitor::display_refresh_rate(display_id).unwrap_or(DEFAULT_REFRESH_RATE));
+    let (interval, timescale) = frame_interval(config.fps, refresh_rate);
+    cfg.set_minimum_frame_interval(cm::Time::new(interval, timescale));
+    cfg.set_queue_depth(config.queue_depth as isize);
+
+    debug!(
+        "Starting stream for display {} at {}x{}, fps={}, queue_depth={}",
+        display_id, width, height, config.fps, config.queue_depth
+    );
+
+    let delegate = StreamDelegate::with(StreamDelegateInner {
+        events: frames.clone(),
+        waker: waker.clone(),
+        stats: stats.clone(),
+    });
+    let output = StreamOutput::with(StreamOutputInner {
+        frames,
+        waker,
+        latest,
+        channel_read,
+        stats,
+        on_drop,
+        show_cursor: config.show_cursor,
+        region: display.frame(),
+        next_sequence: 0,
+    });
+    let queue = dispatch::Queue::serial_with_ar(None);
+    let stream = sc::Stream::with_delegate(&filter, &cfg, delegate.as_ref());
+    stream
+        .add_stream_output(output.as_ref(), sc::OutputType::Screen, Some(&queue))
+        .map_err(|e| XCapError::capture_failed_ns("Failed to add stream output", &e))?;
+    stream
+        .start()
+        .await
+        .map_err(|e| XCapError::capture_failed_ns("Failed to start stream", &e))?;
+
+    Ok((stream, output, delegate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = StreamConfig::default();
+        assert_eq!(config.queue_depth, 3);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_queue_depth_bounds() {
+        let mut config = StreamConfig { queue_depth: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+        config.queue_depth = MAX_QUEUE_DEPTH;
+        assert!(config.validate().is_ok());
+        config.queue_depth = MAX_QUEUE_DEPTH + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_stats_average_interval() {
+        let mut recorder = StatsRecorder::default();
+        recorder.record_delivered(Duration::from_millis(1000));
+        assert_eq!(recorder.stats.average_interval, None);
+        recorder.record_delivered(Duration::from_millis(1100));
+        recorder.record_delivered(Duration::from_millis(1300));
+        assert_eq!(recorder.stats.frames_delivered, 3);
+        assert_eq!(recorder.stats.average_interval, Some(Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn test_record_drop_calls_back() {
+        let stats = Mutex::new(StatsRecorder::default());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_callback = seen.clone();
+        let callback: DropCallback = Box::new(move |dropped| seen_by_callback.lock().unwrap().push(dropped));
+        let on_drop = Mutex::new(Some(callback));
+
+        record_drop(&stats, &on_drop, DropReason::ConsumerBehind, None);
+        record_drop(
+            &stats,
+            &on_drop,
+            DropReason::ConversionFailed,
+            Some(&XCapError::capture_failed("bad buffer")),
+        );
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].reason, DropReason::ConsumerBehind);
+        assert_eq!(seen[0].total_dropped, 1);
+        assert_eq!(seen[1].reason, DropReason::ConversionFailed);
+        assert_eq!(seen[1].total_dropped, 2);
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.stats.frames_dropped, 2);
+        assert_eq!(stats.stats.last_error.as_deref(), Some("Capture failed: bad buffer"));
+    }
+
+    #[test]
+    fn test_mailbox_only_consumer_is_not_behind() {
+        let (frames, receiver) = mpsc::sync_channel(1);
+        let mut output = StreamOutputInner {
+            frames,
+            waker: Arc::new(AtomicWaker::new()),
+            latest: Arc::new(ArcSwapOption::empty()),
+            channel_read: Arc::new(AtomicBool::new(false)),
+            stats: Arc::new(Mutex::new(StatsRecorder::default())),
+            on_drop: Arc::new(Mutex::new(None)),
+            show_cursor: false,
+            region: cg::Rect {
+                origin: cg::Point { x: 0.0, y: 0.0 },
+                size: cg::Size { width: 1.0, height: 1.0 },
+            },
+            next_sequence: 0,
+        };
+        let frame = |sequence| Frame {
+            image: Arc::new(RgbaImage::new(1, 1)),
+            timestamp: Duration::from_millis(sequence * 33),
+            cursor: None,
+            alpha_mode: AlphaMode::Opaque,
+            sequence,
+        };
+
+        // The channel fills up, but nobody reads it, so nothing is dropped
+        for sequence in 0..3 {
+            output.deliver(frame(sequence));
+        }
+        assert_eq!(lock(&output.stats).stats.frames_dropped, 0);
+        assert_eq!(lock(&output.stats).stats.frames_delivered, 3);
+        // The mailbox keeps returning the newest frame
+        assert_eq!(output.latest.load_full().map(|f| f.sequence), Some(2));
+        assert_eq!(output.latest.load_full().map(|f| f.sequence), Some(2));
+
+        // Once the consumer reads the channel, overflow counts as falling behind
+        output.channel_read.store(true, Ordering::Relaxed);
+        output.deliver(frame(3));
+        assert_eq!(lock(&output.stats).stats.frames_dropped, 1);
+        drop(receiver);
+    }
+
+    #[test]
+    fn test_record_drop_survives_panicking_callback() {
+        let stats = Mutex::new(StatsRecorder::default());
+        let callback: DropCallback = Box::new(|_| panic!("callback bug"));
+        let on_drop = Arc::new(Mutex::new(Some(callback)));
+
+        record_drop(&stats, &on_drop, DropReason::ConsumerBehind, None);
+        // Neither lock is poisoned and the callback stays registered
+        assert!(!on_drop.is_poisoned() && !stats.is_poisoned());
+        assert!(on_drop.lock().unwrap().is_some());
+
+        // A callback may replace itself without deadlocking
+        let calls = Arc::new(AtomicBool::new(false));
+        let slot = on_drop.clone();
+        let replaced = calls.clone();
+        *on_drop.lock().unwrap() = Some(Box::new(move |_| {
+            let replaced = replaced.clone();
+            *lock(&slot) = Some(Box::new(move |_| replaced.store(true, Ordering::Relaxed)));
+        }));
+        record_drop(&stats, &on_drop, DropReason::ConsumerBehind, None);
+        record_drop(&stats, &on_drop, DropReason::ConsumerBehind, None);
+        assert!(calls.load(Ordering::Relaxed));
+        assert_eq!(lock(&stats).stats.frames_dropped, 3);
+    }
+
+    #[test]
+    fn test_frame_interval() {
+        assert_eq!(frame_interval(30, None), (1, 30));
+        // Every other refresh at 60 Hz
+        assert_eq!(frame_interval(30, Some(60.0)), (2000, 60000));
+        // Every fourth refresh at 120 Hz
+        assert_eq!(frame_interval(30, Some(120.0)), (4000, 120000));
+        // Fractional rates keep their precision
+        assert_eq!(frame_interval(60, Some(59.94)), (1000, 59940));
+        // Faster than the display is capped at one refresh
+        assert_eq!(frame_interval(240, Some(60.0)), (1000, 60000));
+    }
+
+    #[test]
+    fn test_stats_drops_and_errors() {
+        let mut recorder = StatsRecorder::default();
+        recorder.record_dropped();
+        recorder.record_idle();
+        recorder.record_error(&XCapError::capture_failed("boom"));
+        assert_eq!(recorder.stats.frames_dropped, 1);
+        assert_eq!(recorder.stats.idle_updates, 1);
+        assert_eq!(recorder.stats.last_error.as_deref(), Some("Capture failed: boom"));
+    }
+
+    #[test]
+    fn test_sample_status_drop_reason() {
+        let status = |frame_status, dropped| SampleStatus { frame_status, dropped };
+        assert_eq!(status(Some(FRAME_STATUS_SUSPENDED), false).drop_reason(), Some(DropReason::Suspended));
+        assert_eq!(status(Some(FRAME_STATUS_STOPPED), false).drop_reason(), Some(DropReason::Stopped));
+        assert_eq!(status(None, true).drop_reason(), Some(DropReason::DroppedBySystem));
+        // A dropped-frame marker wins over the frame status
+        assert_eq!(status(Some(0), true).drop_reason(), Some(DropReason::DroppedBySystem));
+        // Complete frames and idle updates are not drops
+        assert_eq!(status(Some(0), false).drop_reason(), None);
+        assert_eq!(status(Some(FRAME_STATUS_IDLE), false).drop_reason(), None);
+        assert!(status(Some(FRAME_STATUS_IDLE), false).is_idle());
+        assert!(status(Some(FRAME_STATUS_BLANK), false).is_idle());
+        assert!(!status(None, false).is_idle());
+    }
+
+    #[test]
+    fn test_stop_error_mapping() {
+        let domain = "com.apple.ScreenCaptureKit.SCStreamErrorDomain";
+        let revoked = map_stop_error(OsError::new(domain, USER_DECLINED, "User declined"));
+        assert_eq!(revoked.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(revoked.os_code(), Some(USER_DECLINED));
+
+        let disconnected = map_stop_error(OsError::new(domain, -3815, "No display"));
+        assert_eq!(disconnected.kind(), ErrorKind::CaptureFailed);
+        assert!(disconnected.to_string().contains("-3815"));
+    }
+
+    #[test]
+    fn test_into_frame_ends_on_stop() {
+        let event = CaptureEvent::Stopped(XCapError::permission_denied());
+        assert!(CaptureStream::into_frame(Some(event)).is_none());
+        assert!(CaptureStream::into_frame(None).is_none());
+    }
+
+    #[test]
+    fn test_into_image_copies_only_when_shared() {
+        let frame = Frame {
+            image: Arc::new(RgbaImage::new(4, 4)),
+            timestamp: Duration::ZERO,
+            cursor: None,
+            alpha_mode: AlphaMode::Opaque,
+            sequence: 0,
+        };
+        let shared = frame.clone();
+        assert!(Arc::ptr_eq(&frame.image, &shared.image));
+        assert_eq!(frame.into_image().dimensions(), (4, 4));
+        // Last owner gets the pixels without a copy
+        let ptr = shared.image.as_raw().as_ptr();
+        assert_eq!(shared.into_image().as_raw().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_zero_fps_rejected() {
+        let config = StreamConfig { fps: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Capture targets prepared once and captured repeatedly
+
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+use image::RgbaImage;
+
+use crate::capture::{self, PreparedCapture};
+use crate::content::Content;
+use crate::error::{XCapError, XCapResult};
+use crate::monitor::Monitor;
+use crate::options::CaptureOptions;
+use crate::window::Window;
+
+/// A window or monitor with its ScreenCaptureKit filter and configuration built ahead of time
+///
+/// `capture_image` looks the target up in a fresh ShareableContent and builds a
+/// new `SCContentFilter` and `SCStreamConfiguration` on every call. When
+/// capturing the same target repeatedly (e.g. once a second), build a
+/// `CaptureTarget` once and call [`CaptureTarget::capture`] instead:
+///
+/// ```rust,no_run
+/// use sck_rs::{CaptureTarget, Monitor};
+///
+/// let target = CaptureTarget::monitor(&Monitor::primary().unwrap()).unwrap();
+/// for _ in 0..10 {
+///     let image = target.capture().unwrap();
+/// }
+/// ```
+///
+/// The geometry is fixed when the target is built: if a window moves or is
+/// resized, or a display changes resolution, build a new target. Cloning is
+/// cheap and clones share the prepared filter.
+#[derive(Clone)]
+pub struct CaptureTarget {
+    prepared: Arc<PreparedCapture>,
+    description: String,
+}
+
+impl CaptureTarget {
+    /// Prepare a window capture with default options
+    pub fn window(window: &Window) -> XCapResult<CaptureTarget> {
+        Self::window_in(&Content::current()?, window, &CaptureOptions::default())
+    }
+
+    /// Prepare a window capture with explicit options
+    pub fn window_with(window: &Window, options: &CaptureOptions) -> XCapResult<CaptureTarget> {
+        Self::window_in(&Content::current()?, window, options)
+    }
+
+    /// Prepare a window capture from an already-fetched [`Content`]
+    pub fn window_in(content: &Content, window: &Window, options: &CaptureOptions) -> XCapResult<CaptureTarget> {
+        let window_id = window.raw_id();
+        let prepared = capture::prepare_window(content.raw(), window_id, options.clone())?;
+        Ok(CaptureTarget {
+            prepared: Arc::new(prepared),
+            description: format!("window {}", window_id),
+        })
+    }
+
+    /// Prepare a monitor capture with default options
+    pub fn monitor(monitor: &Monitor) -> XCapResult<CaptureTarget> {
+        Self::monitor_in(&Content::current()?, monitor, &CaptureOptions::default())
+    }
+
+    /// Prepare a monitor capture with explicit options
+    pub fn monitor_with(monitor: &Monitor, options: &CaptureOptions) -> XCapResult<CaptureTarget> {
+        Self::monitor_in(&Content::current()?, monitor, options)
+    }
+
+    /// Prepare a monitor capture from an already-fetched [`Content`]
+    pub fn monitor_in(content: &Content, monitor: &Monitor, options: &CaptureOptions) -> XCapResult<CaptureTarget> {
+        let prepared = capture::prepare_monitor(
+            content.raw(),
+            monitor.id(),
+            monitor.raw_width(),
+            monitor.raw_height(),
+            options.clone(),
+        )?;
+        Ok(CaptureTarget {
+            prepared: Arc::new(prepared),
+            description: format!("monitor {}", monitor.id()),
+        })
+    }
+
+    /// Capture a single frame of the target
+    ///
+    /// Produces the same image as `capture_image_with` on the window or
+    /// monitor with the same options, without fetching the shareable content
+    /// or building a filter.
+    pub fn capture(&self) -> XCapResult<RgbaImage> {
+        capture::capture_prepared_sync(self.prepared.clone())
+    }
+
+    /// Arm the target for captures triggered by an external signal
+    ///
+    /// Starts a dedicated capture thread and takes one warm-up frame, so the
+    /// async runtime, the ScreenCaptureKit connection and the GPU pipeline are
+    /// all ready before the first [`ArmedCapture::trigger`]. Fails if the
+    /// warm-up capture fails, e.g. without screen recording permission.
+    pub fn prepare(&self) -> XCapResult<ArmedCapture> {
+        let prepared = self.prepared.clone();
+        let (requests, rx) = mpsc::channel::<mpsc::Sender<XCapResult<RgbaImage>>>();
+        let worker = std::thread::Builder::new()
+            .name("sck-rs-armed-capture".to_string())
+            .spawn(move || {
+                for reply in rx {
+                    let result = capture::block_on(capture::capture_prepared_async(&prepared));
+                    let _ = reply.send(result);
+                }
+            })
+            .map_err(|e| XCapError::with_source("Failed to start the capture thread", e))?;
+
+        let armed = ArmedCapture {
+            requests: Some(requests),
+            worker: Some(worker),
+            description: self.description.clone(),
+        };
+        armed.trigger()?;
+        Ok(armed)
+    }
+}
+
+/// A [`CaptureTarget`] armed by [`CaptureTarget::prepare`] for low-latency captures
+///
+/// Each [`ArmedCapture::trigger`] hands the capture to a thread that is
+/// already waiting, instead of setting up a thread and runtime per call.
+/// Dropping the handle stops the thread.
+pub struct ArmedCapture {
+    requests: Option<mpsc::Sender<mpsc::Sender<XCapResult<RgbaImage>>>>,
+    worker: Option<JoinHandle<()>>,
+    description: String,
+}
+
+impl ArmedCapture {
+    /// Capture a frame now
+    ///
+    /// Blocks until the frame is ready. Triggers from several threads are
+    /// captured one after the other.
+    pub fn trigger(&self) -> XCapResult<RgbaImage> {
+        let (reply, result) = mpsc::channel();
+        self.requests
+            .as_ref()
+            .and_then(|requests| requests.send(reply).ok())
+            .ok_or_else(|| XCapError::capture_failed("The capture thread has stopped"))?;
+        result
+            .recv()
+            .map_err(|_| XCapError::capture_failed("The capture thread stopped before replying"))?
+    }
+}
+
+impl Drop for ArmedCapture {
+    fn drop(&mut self) {
+        // Closing the channel ends the worker's loop
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for ArmedCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArmedCapture").field("target", &self.description).finish()
+    }
+}
+
+impl std::fmt::Debug for CaptureTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptureTarget").field("target", &self.description).finish()
+    }
+}
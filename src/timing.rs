@@ -0,0 +1,50 @@
+//! Capture pipeline timings, enabled with the `trace-timings` feature
+//!
+//! Each step runs inside a `capture_step` span carrying the step name and an
+//! `elapsed_us` field, and emits an event when it finishes. Without the feature
+//! these helpers compile down to calling the wrapped code directly.
+
+use std::future::Future;
+
+#[cfg(feature = "trace-timings")]
+use std::time::Instant;
+#[cfg(feature = "trace-timings")]
+use tracing::{field, info, info_span, Instrument};
+
+/// Time a synchronous pipeline step
+#[cfg(feature = "trace-timings")]
+pub(crate) fn timed<T>(step: &'static str, f: impl FnOnce() -> T) -> T {
+    let span = info_span!("capture_step", step, elapsed_us = field::Empty);
+    let start = Instant::now();
+    let out = span.in_scope(f);
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    span.record("elapsed_us", elapsed_us);
+    info!(parent: &span, step, elapsed_us, "capture step finished");
+    out
+}
+
+/// Time a synchronous pipeline step
+#[cfg(not(feature = "trace-timings"))]
+#[inline(always)]
+pub(crate) fn timed<T>(_step: &'static str, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+/// Time an asynchronous pipeline step
+#[cfg(feature = "trace-timings")]
+pub(crate) async fn timed_async<F: Future>(step: &'static str, f: F) -> F::Output {
+    let span = info_span!("capture_step", step, elapsed_us = field::Empty);
+    let start = Instant::now();
+    let out = f.instrument(span.clone()).await;
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    span.record("elapsed_us", elapsed_us);
+    info!(parent: &span, step, elapsed_us, "capture step finished");
+    out
+}
+
+/// Time an asynchronous pipeline step
+#[cfg(not(feature = "trace-timings"))]
+#[inline(always)]
+pub(crate) async fn timed_async<F: Future>(_step: &'static str, f: F) -> F::Output {
+    f.await
+}
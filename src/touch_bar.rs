@@ -0,0 +1,76 @@
+//! Touch Bar capture
+//!
+//! The Touch Bar is a separate framebuffer driven by the T1/T2 chip. Neither
+//! ScreenCaptureKit nor CoreGraphics enumerates it as a display, so it cannot
+//! be a [`crate::Monitor`]. The system `screencapture -b` tool is the only
+//! supported way to read it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use image::RgbaImage;
+use tracing::debug;
+
+use crate::error::{ErrorKind, XCapError, XCapResult};
+
+const SCREENCAPTURE: &str = "/usr/sbin/screencapture";
+
+/// Capture the Touch Bar through `screencapture -b`
+pub(crate) fn capture() -> XCapResult<RgbaImage> {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let path: PathBuf = std::env::temp_dir().join(format!(
+        "sck-rs-touch-bar-{}-{}.png",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // -x: no sound, -t png: lossless output
+    let output = Command::new(SCREENCAPTURE)
+        .args(["-b", "-x", "-t", "png"])
+        .arg(&path)
+        .output()
+        .map_err(|e| XCapError::capture_failed(format!("could not run {}: {}", SCREENCAPTURE, e)))?;
+    debug!(
+        "screencapture -b exited with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    let result = load(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Load the image `screencapture` wrote
+///
+/// `screencapture -b` writes nothing on Macs without a Touch Bar, so a
+/// missing or empty file means there is no Touch Bar to capture.
+fn load(path: &Path) -> XCapResult<RgbaImage> {
+    let has_image = std::fs::metadata(path).is_ok_and(|meta| meta.len() > 0);
+    if !has_image {
+        return Err(XCapError::with_kind(
+            ErrorKind::MonitorNotFound,
+            "No Touch Bar found. Only MacBook Pro models with a Touch Bar have one",
+        ));
+    }
+    let image = image::open(path).map_err(|e| XCapError::capture_failed(format!("could not read Touch Bar image: {}", e)))?;
+    Ok(image.into_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_image_is_no_touch_bar() {
+        let path = std::env::temp_dir().join("sck-rs-touch-bar-test-missing.png");
+        let _ = std::fs::remove_file(&path);
+        let err = load(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MonitorNotFound);
+
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(load(&path).unwrap_err().kind(), ErrorKind::MonitorNotFound);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+//! Polling-based window open/close/move notifications
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::error::{ErrorKind, XCapError, XCapResult};
+use crate::geometry::Bounds;
+use crate::window::Window;
+
+/// A change reported by a [`WindowWatcher`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum WindowEvent {
+    /// A window appeared
+    Opened(Window),
+    /// The window with this id no longer exists
+    Closed(u32),
+    /// The window with this id moved or was resized to the given bounds
+    Moved(u32, Bounds),
+}
+
+/// Watches the window list and reports windows opening, closing and moving
+///
+/// A background thread calls [`Window::all_changed_since`] every `interval`
+/// and turns the differences into [`WindowEvent`]s, so each change is
+/// reported once. Windows that exist when the watcher starts are not
+/// reported as opened; read them with [`WindowWatcher::windows`]. Changes
+/// that come and go between two polls are not seen.
+///
+/// Watching stops when [`WindowWatcher::stop`] is called or the watcher is dropped.
+pub struct WindowWatcher {
+    events: mpsc::Receiver<WindowEvent>,
+    windows: Arc<Mutex<Vec<Window>>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WindowWatcher {
+    /// Take a first snapshot and start polling every `interval`
+    ///
+    /// Fails if the first snapshot cannot be taken, e.g. without screen
+    /// recording permission.
+    pub fn start(interval: Duration) -> XCapResult<WindowWatcher> {
+        if interval.is_zero() {
+            return Err(XCapError::new("Window watcher interval must be greater than 0"));
+        }
+
+        let initial = Window::all_changed_since(&[])?.current;
+        let windows = Arc::new(Mutex::new(initial));
+        let (event_tx, events) = mpsc::channel();
+        let (stop, stop_rx) = mpsc::channel();
+
+        let thread_windows = windows.clone();
+        let thread = std::thread::Builder::new()
+            .name("sck-rs-window-watcher".to_string())
+            .spawn(move || poll(interval, thread_windows, event_tx, stop_rx))
+            .map_err(|e| XCapError::with_source("Failed to spawn window watcher thread", e))?;
+
+        Ok(WindowWatcher {
+            events,
+            windows,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Get the windows as of the latest poll
+    pub fn windows(&self) -> Vec<Window> {
+        self.windows.lock().unwrap().clone()
+    }
+
+    /// Block until the next event
+    ///
+    /// Returns `None` once the watcher has stopped.
+    pub fn recv(&self) -> Option<WindowEvent> {
+        self.events.recv().ok()
+    }
+
+    /// Block until the next event or the timeout elapses
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<WindowEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Get the next event if one is pending, without blocking
+    pub fn try_recv(&self) -> Option<WindowEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Stop polling and wait for the background thread to exit
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // Dropping the sender wakes the thread out of its wait
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WindowWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl std::fmt::Debug for WindowWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowWatcher")
+            .field("windows", &self.windows.lock().unwrap().len())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+/// Body of the watcher thread: diff snapshots until the stop channel closes
+fn poll(
+    interval: Duration,
+    windows: Arc<Mutex<Vec<Window>>>,
+    events: mpsc::Sender<WindowEvent>,
+    stop: mpsc::Receiver<()>,
+) {
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let previous = windows.lock().unwrap().clone();
+        let diff = match Window::all_changed_since(&previous) {
+            Ok(diff) => diff,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                debug!("Window watcher stopping: {}", e);
+                break;
+            }
+            Err(e) => {
+                debug!("Window watcher poll failed, retrying: {}", e);
+                continue;
+            }
+        };
+
+        for event in diff.events() {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+        *windows.lock().unwrap() = diff.current;
+    }
+    debug!("Window watcher stopped");
+}
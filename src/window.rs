@@ -1,30 +1,314 @@
 //! Window capture using ScreenCaptureKit via cidre
 
-use cidre::ns;
-use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use cidre::sc;
+use image::{RgbImage, RgbaImage};
 use tracing::debug;
 
-/// Get the PID of the frontmost application using cidre's NSWorkspace API.
-fn get_frontmost_pid() -> i32 {
-    let workspace = ns::Workspace::shared();
-    let apps = workspace.running_apps();
-    for i in 0..apps.len() {
-        if let Ok(app) = apps.get(i) {
-            if app.is_active() {
-                return app.pid();
+use crate::cancel::CancellationToken;
+use crate::ax::{self, AxElement, AxWindowInfo};
+use crate::capture::{self, CgImage};
+use crate::cg_window;
+use crate::content::Content;
+use crate::focus;
+use crate::icon;
+use crate::monitor;
+use crate::error::{ErrorKind, XCapError, XCapResult};
+use crate::geometry::Bounds;
+use crate::options::CaptureOptions;
+use crate::shared::{FrameHeader, SharedRegion};
+use crate::watcher::WindowEvent;
+
+/// Order of the windows returned by [`Window::all_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowOrder {
+    /// Frontmost window first (the default)
+    #[default]
+    FrontToBack,
+    /// Backmost window first, the natural order for compositing
+    BackToFront,
+    /// Whatever order ScreenCaptureKit reports, skipping the stacking lookup
+    Unsorted,
+}
+
+/// Filtering policy applied by [`Window::all_with_options`]
+///
+/// The default matches [`Window::all`]: windows smaller than 10x10 are skipped,
+/// while off-screen windows and desktop elements are kept.
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    /// Skip windows whose width or height is below this many points (0 keeps everything)
+    pub min_size: u32,
+    /// Include windows that are not currently on screen (minimized, hidden, other Spaces)
+    pub include_offscreen: bool,
+    /// Include desktop elements such as the wallpaper and Finder desktop icons
+    pub include_desktop_elements: bool,
+    /// Keep only windows at the normal window layer (0)
+    ///
+    /// Skips menu bar items, the Dock, floating panels and other overlays.
+    pub normal_layer_only: bool,
+    /// Skip windows with neither a title nor an owning app name
+    pub require_title_or_app: bool,
+    /// Skip fully transparent windows (`kCGWindowAlpha` of 0), such as invisible HUDs
+    ///
+    /// Costs one extra `CGWindowListCopyWindowInfo` call per listing.
+    pub skip_transparent: bool,
+    /// Skip windows whose `kCGWindowAlpha` is below this value (0.0 keeps everything)
+    ///
+    /// Alpha ranges from 0.0 to 1.0. A threshold such as 0.1 also drops nearly
+    /// invisible overlays that `skip_transparent` keeps, e.g. faded-out HUDs
+    /// and input method candidate windows. Like `skip_transparent`, costs one
+    /// extra `CGWindowListCopyWindowInfo` call per listing.
+    pub min_alpha: f64,
+    /// Order of the returned windows
+    pub order: WindowOrder,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 10,
+            include_offscreen: true,
+            include_desktop_elements: true,
+            normal_layer_only: false,
+            require_title_or_app: false,
+            skip_transparent: false,
+            min_alpha: 0.0,
+            order: WindowOrder::FrontToBack,
+        }
+    }
+}
+
+impl ListOptions {
+    /// Options that disable all filtering
+    pub fn unfiltered() -> Self {
+        Self {
+            min_size: 0,
+            include_offscreen: true,
+            include_desktop_elements: true,
+            normal_layer_only: false,
+            require_title_or_app: false,
+            skip_transparent: false,
+            min_alpha: 0.0,
+            order: WindowOrder::FrontToBack,
+        }
+    }
+
+    /// Options keeping only windows that are likely to capture successfully
+    ///
+    /// Used by [`Window::all_capturable`]. A window is kept if it:
+    ///
+    /// - is on screen (minimized windows and other Spaces are not rendered),
+    /// - is at least 100x100 points,
+    /// - is at the normal window layer, not a menu bar item, Dock or overlay,
+    /// - is not a desktop element,
+    /// - is not fully transparent,
+    /// - has a title or an owning app name.
+    ///
+    /// Adjust individual fields to loosen or tighten the policy.
+    pub fn capturable() -> Self {
+        Self {
+            min_size: 100,
+            include_offscreen: false,
+            include_desktop_elements: false,
+            normal_layer_only: true,
+            require_title_or_app: true,
+            skip_transparent: true,
+            min_alpha: 0.0,
+            order: WindowOrder::FrontToBack,
+        }
+    }
+
+    /// Check whether a window with the given size and visibility passes the filter
+    fn accepts(&self, width: u32, height: u32, is_on_screen: bool) -> bool {
+        width >= self.min_size && height >= self.min_size && (self.include_offscreen || is_on_screen)
+    }
+
+    /// Check whether the alpha filters need window alphas from CGWindowList
+    fn filters_alpha(&self) -> bool {
+        self.skip_transparent || self.min_alpha > 0.0
+    }
+
+    /// Check whether a window with the given `kCGWindowAlpha` passes the filter
+    fn accepts_alpha(&self, alpha: f64) -> bool {
+        !(self.skip_transparent && alpha <= 0.0) && alpha >= self.min_alpha
+    }
+
+    /// Check whether a window with the given layer and names passes the filter
+    fn accepts_identity(&self, window_layer: isize, title: &str, app_name: &str) -> bool {
+        (!self.normal_layer_only || window_layer == 0)
+            && (!self.require_title_or_app || !title.is_empty() || !app_name.is_empty())
+    }
+}
+
+/// Sort windows front to back
+///
+/// `stacking` is the window server's front-to-back order of on-screen windows,
+/// which already accounts for window layers. Windows missing from it (off
+/// screen, other Spaces) go last, higher layers first, keeping their relative order.
+fn sort_by_stacking(windows: &mut [Window], stacking: &[u32]) {
+    windows.sort_by_key(|w| {
+        match stacking.iter().position(|&id| id == w.window_id) {
+            Some(index) => (0, index, 0),
+            None => (1, 0, -w.window_layer),
+        }
+    });
+}
+
+/// Number windows front to back within each layer
+///
+/// `windows` must be sorted frontmost first.
+fn assign_stacking_indices(windows: &mut [Window]) {
+    let mut next: HashMap<isize, usize> = HashMap::new();
+    for window in windows {
+        let index = next.entry(window.window_layer).or_insert(0);
+        window.stacking_index = *index;
+        *index += 1;
+    }
+}
+
+/// Changes between two window snapshots, keyed by window id
+///
+/// Returned by [`Window::all_changed_since`]. Windows in `title_changed` and
+/// `moved` are in their new state; a window can appear in both.
+#[derive(Debug, Clone, Default)]
+pub struct WindowDiff {
+    /// Windows present now but not in the previous snapshot
+    pub added: Vec<Window>,
+    /// Windows in the previous snapshot that no longer exist
+    pub removed: Vec<Window>,
+    /// Windows whose title changed
+    pub title_changed: Vec<Window>,
+    /// Windows whose position or size changed
+    pub moved: Vec<Window>,
+    /// The full new snapshot, to pass as `previous` on the next call
+    pub current: Vec<Window>,
+}
+
+impl WindowDiff {
+    /// Compare two snapshots
+    pub fn between(previous: &[Window], current: Vec<Window>) -> Self {
+        let before: HashMap<u32, &Window> = previous.iter().map(|w| (w.window_id, w)).collect();
+        let now: HashSet<u32> = current.iter().map(|w| w.window_id).collect();
+
+        let mut diff = WindowDiff::default();
+        for window in &current {
+            match before.get(&window.window_id) {
+                None => diff.added.push(window.clone()),
+                Some(old) => {
+                    if old.title != window.title {
+                        diff.title_changed.push(window.clone());
+                    }
+                    if (old.x, old.y, old.width, old.height) != (window.x, window.y, window.width, window.height) {
+                        diff.moved.push(window.clone());
+                    }
+                }
             }
         }
+        diff.removed = previous
+            .iter()
+            .filter(|w| !now.contains(&w.window_id))
+            .cloned()
+            .collect();
+        diff.current = current;
+        diff
+    }
+
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.title_changed.is_empty() && self.moved.is_empty()
+    }
+
+    /// Turn the diff into open, close and move events
+    ///
+    /// Title changes have no event. Events come in that order: opened, closed, moved.
+    pub fn events(&self) -> Vec<WindowEvent> {
+        let opened = self.added.iter().cloned().map(WindowEvent::Opened);
+        let closed = self.removed.iter().map(|w| WindowEvent::Closed(w.window_id));
+        let moved = self.moved.iter().map(|w| WindowEvent::Moved(w.window_id, w.bounds()));
+        opened.chain(closed).chain(moved).collect()
+    }
+}
+
+/// Check whether an accessibility element describes a window
+///
+/// A known CGWindowID decides on its own; otherwise the pid, title and frame
+/// (to within a point) must all agree.
+fn ax_info_matches(window: &Window, info: &AxWindowInfo) -> bool {
+    if info.pid != window.pid {
+        return false;
     }
-    -1
+    if let Some(window_id) = info.window_id {
+        return window_id == window.window_id;
+    }
+    let title_matches = info.title.as_deref().unwrap_or_default() == window.title;
+    let frame_matches = info.frame.is_some_and(|(x, y, width, height)| {
+        (x - window.x as f64).abs() < 1.0
+            && (y - window.y as f64).abs() < 1.0
+            && (width - window.width as f64).abs() < 1.0
+            && (height - window.height as f64).abs() < 1.0
+    });
+    title_matches && frame_matches
+}
+
+/// Decide whether a window is minimized
+///
+/// `ax_minimized` is the window's `AXMinimized` attribute, if it could be
+/// read. Without it, off-screen windows of visible apps are assumed minimized.
+fn resolve_minimized(is_on_screen: bool, ax_minimized: Option<bool>, app_hidden: bool) -> bool {
+    if is_on_screen {
+        return false;
+    }
+    ax_minimized.unwrap_or(!app_hidden)
 }
 
-use crate::capture;
-use crate::error::{XCapError, XCapResult};
+/// Pick the first focused window from a frontmost-first list
+fn pick_focused(windows: Vec<Window>) -> Option<Window> {
+    windows
+        .into_iter()
+        .find(|w| w.is_on_screen && w.is_app_active && w.window_layer == 0)
+}
+
+/// Capture the focused window in one call
+///
+/// Resolves [`Window::focused`] and captures it, returning both so callers
+/// know which window the image shows.
+pub fn capture_active_window() -> XCapResult<(Window, RgbaImage)> {
+    let window = Window::focused()?;
+    let image = window.capture_image()?;
+    Ok((window, image))
+}
+
+/// Check that a rect lies within a window of the given size
+fn validate_rect(x: u32, y: u32, width: u32, height: u32, window_width: u32, window_height: u32) -> XCapResult<()> {
+    let fits = width > 0
+        && height > 0
+        && x.checked_add(width).is_some_and(|right| right <= window_width)
+        && y.checked_add(height).is_some_and(|bottom| bottom <= window_height);
+    if !fits {
+        return Err(XCapError::new(format!(
+            "Rect {}x{} at ({}, {}) is outside the {}x{} window",
+            width, height, x, y, window_width, window_height
+        )));
+    }
+    Ok(())
+}
 
 /// Represents a capturable window
 ///
 /// This type provides an API compatible with xcap::Window
+///
+/// Equality and hashing use only the window ID, so two snapshots of the same
+/// window compare equal even if its title or geometry changed in between.
+///
+/// With the `serde` feature, windows can be serialized, e.g. to return window
+/// lists over an API. A deserialized `Window` is a metadata-only snapshot: its
+/// getters work, but capturing it only succeeds if a window with that ID still
+/// exists.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Window {
     /// The window ID
     window_id: u32,
@@ -48,16 +332,62 @@ pub struct Window {
     is_app_active: bool,
     /// The window layer (0 = normal, >0 = overlay/floating/panel)
     window_layer: isize,
+    /// ID of the display containing the window origin
+    display_id: u32,
+    /// Backing scale factor of the display containing the window
+    scale_factor: f64,
+    /// Position among the listed windows of the same layer, 0 = frontmost
+    stacking_index: usize,
+}
+
+impl PartialEq for Window {
+    fn eq(&self, other: &Self) -> bool {
+        self.window_id == other.window_id
+    }
+}
+
+impl Eq for Window {}
+
+impl std::hash::Hash for Window {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.window_id.hash(state);
+    }
 }
 
 impl Window {
     /// Get all available windows
     ///
-    /// Returns a list of all windows that can be captured.
-    /// Requires screen recording permission.
+    /// Returns a list of all windows that can be captured, frontmost first.
+    /// On-screen windows follow the window server's stacking order; off-screen
+    /// windows come after them. Requires screen recording permission.
     pub fn all() -> XCapResult<Vec<Window>> {
-        let content = capture::get_shareable_content()?;
+        Content::current()?.windows()
+    }
+
+    /// Count the windows ScreenCaptureKit reports
+    ///
+    /// Counts every window, like [`ListOptions::unfiltered`], without building
+    /// `Window` values or reading the window server's stacking order, so it
+    /// can be larger than `Window::all()?.len()`.
+    pub fn count() -> XCapResult<usize> {
+        Ok(capture::get_shareable_content()?.windows().len())
+    }
+
+    /// Get all available windows using a custom filtering policy
+    ///
+    /// Use [`ListOptions::unfiltered`] to get every window ScreenCaptureKit reports,
+    /// including tiny and off-screen ones.
+    pub fn all_with_options(options: ListOptions) -> XCapResult<Vec<Window>> {
+        let content = if options.include_desktop_elements && options.include_offscreen {
+            capture::get_shareable_content()?
+        } else {
+            capture::get_shareable_content_filtered(!options.include_desktop_elements, !options.include_offscreen)?
+        };
+        Self::from_content(&content, &options)
+    }
 
+    /// Build windows from an already-fetched ShareableContent
+    pub(crate) fn from_content(content: &sc::ShareableContent, options: &ListOptions) -> XCapResult<Vec<Window>> {
         let sc_windows = content.windows();
 
         if sc_windows.is_empty() {
@@ -65,7 +395,29 @@ impl Window {
         }
 
         // Get the frontmost app PID once for all windows
-        let frontmost_pid = get_frontmost_pid();
+        let frontmost_pid = focus::frontmost_pid();
+
+        // Window alpha is only available from CGWindowList
+        let transparent: Vec<u32> = if options.filters_alpha() {
+            cg_window::get_cgwindow_info(cg_window::LIST_OPTION_ALL)
+                .into_iter()
+                .filter(|info| !options.accepts_alpha(info.alpha))
+                .map(|info| info.window_id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Resolve each display's scale factor once, keyed by display id
+        let displays = content.displays();
+        let display_scales: Vec<(u32, f64)> = displays
+            .iter()
+            .map(|d| {
+                let display_id = d.display_id().0;
+                let scale = monitor::display_scale_factor(display_id, d.width() as u32, d.height() as u32);
+                (display_id, scale)
+            })
+            .collect();
 
         let windows: Vec<Window> = sc_windows
             .iter()
@@ -93,12 +445,34 @@ impl Window {
                 let width = frame.size.width as u32;
                 let height = frame.size.height as u32;
 
-                // Skip windows that are too small (likely invisible)
-                if width < 10 || height < 10 {
-                    debug!("Skipping small window: {} ({}x{})", title, width, height);
+                // Skip windows that are too small (likely invisible) or off screen
+                let is_on_screen = w.is_on_screen();
+                if !options.accepts(width, height, is_on_screen) {
+                    debug!(
+                        "Skipping window: {} ({}x{}, on_screen={})",
+                        title, width, height, is_on_screen
+                    );
+                    return None;
+                }
+                if !options.accepts_identity(window_layer, &title, &app_name) {
+                    debug!("Skipping window: {} (layer={}, app={})", title, window_layer, app_name);
+                    return None;
+                }
+                if transparent.contains(&w.id()) {
+                    debug!("Skipping transparent window: {}", title);
                     return None;
                 }
 
+                // Display containing the window origin (same lookup as capture)
+                let display_id = capture::display_for_point(&displays, frame.origin.x, frame.origin.y)
+                    .map(|d| d.display_id().0)
+                    .unwrap_or(0);
+                let scale_factor = display_scales
+                    .iter()
+                    .find(|(id, _)| *id == display_id)
+                    .map(|(_, s)| *s)
+                    .unwrap_or(1.0);
+
                 debug!(
                     "Found window: id={}, app={}, title={}, {}x{} at ({}, {}), layer={}, active={}",
                     w.id(), app_name, title, width, height, frame.origin.x, frame.origin.y,
@@ -114,9 +488,13 @@ impl Window {
                     y: frame.origin.y as i32,
                     width,
                     height,
-                    is_on_screen: w.is_on_screen(),
+                    is_on_screen,
                     is_app_active,
                     window_layer,
+                    display_id,
+                    scale_factor,
+                    // Assigned below, once the windows are in stacking order
+                    stacking_index: 0,
                 })
             })
             .collect();
@@ -125,9 +503,148 @@ impl Window {
             return Err(XCapError::no_windows());
         }
 
+        let mut windows = windows;
+        if options.order != WindowOrder::Unsorted {
+            sort_by_stacking(&mut windows, &cg_window::window_stacking_order());
+        }
+        assign_stacking_indices(&mut windows);
+        if options.order == WindowOrder::BackToFront {
+            windows.reverse();
+        }
+
+        Ok(windows)
+    }
+
+    /// Get the windows that are likely to capture successfully
+    ///
+    /// Applies [`ListOptions::capturable`]: on screen, at least 100x100, at the
+    /// normal layer, not transparent, not a desktop element, and with a title
+    /// or app name. Use [`Window::all_with_options`] with a modified
+    /// `ListOptions::capturable()` to override individual rules.
+    pub fn all_capturable() -> XCapResult<Vec<Window>> {
+        Self::all_with_options(ListOptions::capturable())
+    }
+
+    /// Get the focused window: the frontmost normal window of the active app
+    ///
+    /// Windows are listed frontmost first, so this is the app's key window
+    /// in practice. Floating panels and overlays are skipped, as in
+    /// [`Window::is_focused`]. Fails with [`ErrorKind::WindowNotFound`] if the
+    /// active app has no on-screen window (e.g. Finder with every window closed).
+    pub fn focused() -> XCapResult<Window> {
+        let options = ListOptions {
+            include_offscreen: false,
+            ..Default::default()
+        };
+        pick_focused(Self::all_with_options(options)?)
+            .ok_or_else(|| XCapError::with_kind(ErrorKind::WindowNotFound, "The active app has no focused window"))
+    }
+
+    /// Capture several windows, fetching ShareableContent only once
+    ///
+    /// Up to `concurrency` windows are captured in parallel on the async path,
+    /// which is much faster than calling [`Window::capture_image`] in a loop
+    /// for tools that snapshot every open window. Returns each window's ID with
+    /// its result, in the same order as `windows`; a window that closed in the
+    /// meantime only fails its own entry.
+    pub fn capture_many(windows: &[Window], concurrency: usize) -> Vec<(u32, XCapResult<RgbaImage>)> {
+        let ids: Vec<u32> = windows.iter().map(|w| w.window_id).collect();
+        let results = capture::get_shareable_content()
+            .and_then(|content| capture::capture_windows_sync(content, ids.clone(), concurrency));
+        match results {
+            Ok(results) => ids.into_iter().zip(results).collect(),
+            Err(e) => {
+                debug!("capture_many failed before capturing: {}", e);
+                ids.into_iter()
+                    .map(|id| (id, Err(XCapError::with_kind(e.kind(), e.to_string()))))
+                    .collect()
+            }
+        }
+    }
+
+    /// Get all windows across every Space, not just the active one
+    ///
+    /// Requests ScreenCaptureKit content with `onScreenWindowsOnly` disabled, then
+    /// adds the normal-layer windows that `CGWindowListCopyWindowInfo` with
+    /// `kCGWindowListOptionAll` reports but ScreenCaptureKit left out, which
+    /// happens for some windows on inactive Spaces. Desktop elements are excluded.
+    ///
+    /// Windows on an inactive Space are listed with [`Window::is_on_screen`] set to
+    /// `false`, after the on-screen ones. Capturing them may still fail or return
+    /// stale content because the window server does not render them; callers
+    /// indexing windows should fall back to a cached thumbnail in that case.
+    pub fn all_including_spaces() -> XCapResult<Vec<Window>> {
+        let options = ListOptions {
+            include_offscreen: true,
+            include_desktop_elements: false,
+            ..Default::default()
+        };
+        let content = capture::get_shareable_content_filtered(true, false)?;
+        let mut windows = match Self::from_content(&content, &options) {
+            Ok(windows) => windows,
+            Err(e) if e.kind() == ErrorKind::NoWindows => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let listed: HashSet<u32> = windows.iter().map(|w| w.window_id).collect();
+        let frontmost_pid = focus::frontmost_pid();
+        let displays = content.displays();
+        let missing: Vec<Window> = cg_window::get_cgwindow_info(cg_window::LIST_OPTION_ALL)
+            .into_iter()
+            // Desktop elements and system overlays sit on other layers
+            .filter(|info| info.layer == 0 && info.pid >= 0 && !listed.contains(&info.window_id))
+            .filter(|info| {
+                options.accepts(info.bounds.size.width as u32, info.bounds.size.height as u32, info.is_on_screen)
+                    && options.accepts_identity(0, &info.name, &info.owner_name)
+                    && options.accepts_alpha(info.alpha)
+            })
+            .map(|info| {
+                let display = capture::display_for_point(&displays, info.bounds.origin.x, info.bounds.origin.y);
+                debug!("Adding window missing from ScreenCaptureKit: id={}, app={}", info.window_id, info.owner_name);
+                Window {
+                    window_id: info.window_id,
+                    is_app_active: info.pid == frontmost_pid,
+                    x: info.bounds.origin.x as i32,
+                    y: info.bounds.origin.y as i32,
+                    width: info.bounds.size.width as u32,
+                    height: info.bounds.size.height as u32,
+                    is_on_screen: info.is_on_screen,
+                    window_layer: 0,
+                    display_id: display.map_or(0, |d| d.display_id().0),
+                    scale_factor: display.map_or(1.0, |d| {
+                        monitor::display_scale_factor(d.display_id().0, d.width() as u32, d.height() as u32)
+                    }),
+                    stacking_index: 0,
+                    app_name: info.owner_name,
+                    title: info.name,
+                    pid: info.pid,
+                }
+            })
+            .collect();
+
+        windows.extend(missing);
+        if windows.is_empty() {
+            return Err(XCapError::no_windows());
+        }
+        assign_stacking_indices(&mut windows);
         Ok(windows)
     }
 
+    /// Enumerate windows and report what changed since a previous snapshot
+    ///
+    /// Windows are matched by id and classified as added, removed, retitled or
+    /// moved, so an activity tracker only has to handle the changes. Pass
+    /// [`WindowDiff::current`] as `previous` on the next call. An empty window
+    /// list is not an error here; all previous windows are reported removed.
+    pub fn all_changed_since(previous: &[Window]) -> XCapResult<WindowDiff> {
+        let current = match Self::all() {
+            Ok(windows) => windows,
+            Err(e) if e.kind() == ErrorKind::NoWindows => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(WindowDiff::between(previous, current))
+    }
+
     /// Get the window ID
     pub fn id(&self) -> XCapResult<u32> {
         Ok(self.window_id)
@@ -176,10 +693,81 @@ impl Window {
         Ok(self.height)
     }
 
-    /// Check if the window is minimized
+    /// Get the window frame in global points with a top-left origin
+    ///
+    /// Use [`Bounds::relative_to`] with [`crate::Monitor::bounds`] to locate the
+    /// window within a monitor capture.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::top_left(self.x, self.y, self.width, self.height)
+    }
+
+    /// Get the window origin in global points, top-left origin
+    pub(crate) fn origin(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// Check if the window is minimized to the Dock
+    ///
+    /// Windows on another Space or of a hidden app are off screen too, but are
+    /// not minimized. The state is read from the window's `AXMinimized`
+    /// attribute, which needs the Accessibility permission; without it, an
+    /// off-screen window is reported as minimized unless its app is hidden.
     pub fn is_minimized(&self) -> XCapResult<bool> {
-        // SCK provides is_on_screen which is the inverse
-        Ok(!self.is_on_screen)
+        if self.is_on_screen {
+            return Ok(false);
+        }
+        let ax_minimized = ax::window_minimized(self.pid, self.window_id);
+        let app_hidden = ax_minimized.is_none() && focus::is_app_hidden(self.pid);
+        Ok(resolve_minimized(self.is_on_screen, ax_minimized, app_hidden))
+    }
+
+    /// Find the window an accessibility element refers to
+    ///
+    /// Matches the element's CGWindowID when the Accessibility API reveals it,
+    /// and otherwise its pid, title and frame, so tools that identify windows
+    /// through `AXUIElement` can capture them directly. Fails with
+    /// [`ErrorKind::WindowNotFound`] if no window matches, e.g. when the
+    /// element is not a window.
+    pub fn from_ax_element(element: &AxElement) -> XCapResult<Window> {
+        let info = ax::element_info(element);
+        Self::all_with_options(ListOptions::unfiltered())?
+            .into_iter()
+            .find(|window| ax_info_matches(window, &info))
+            .ok_or_else(|| {
+                XCapError::with_kind(
+                    ErrorKind::WindowNotFound,
+                    format!("No window of process {} matches the accessibility element", info.pid),
+                )
+            })
+    }
+
+    /// Get the window's accessibility element
+    ///
+    /// Requires the Accessibility permission, and fails with
+    /// [`ErrorKind::PermissionDenied`] without it. Fails with
+    /// [`ErrorKind::WindowNotFound`] if the app does not expose the window,
+    /// which is common for windows on another Space.
+    pub fn ax_element(&self) -> XCapResult<AxElement> {
+        if !ax::is_trusted() {
+            return Err(XCapError::with_kind(
+                ErrorKind::PermissionDenied,
+                "Accessibility permission not granted. Grant access in System Settings > Privacy & Security > Accessibility",
+            ));
+        }
+        ax::window_element(self.pid, self.window_id, |info| ax_info_matches(self, info)).ok_or_else(|| {
+            XCapError::with_kind(
+                ErrorKind::WindowNotFound,
+                format!("Window {} has no accessibility element", self.window_id),
+            )
+        })
+    }
+
+    /// Check if the window's application is hidden (Cmd-H)
+    ///
+    /// Hidden apps' windows are off screen but not minimized, and reappear
+    /// when the app is unhidden.
+    pub fn is_hidden(&self) -> XCapResult<bool> {
+        Ok(focus::is_app_hidden(self.pid))
     }
 
     /// Check if the window is maximized
@@ -214,11 +802,279 @@ impl Window {
         self.window_layer
     }
 
+    /// Get the window's position in the stacking order of its layer
+    ///
+    /// 0 is the frontmost window of [`Window::window_layer`], 1 the one behind
+    /// it, and so on; a window covers any window of the same layer with a
+    /// higher index, and higher layers cover lower ones. Together with
+    /// [`Window::bounds`] this is enough to work out occlusion.
+    ///
+    /// The index is only meaningful within a single [`Window::all`] (or
+    /// [`Window::all_with_options`]) snapshot: it counts the windows in that
+    /// listing, so filtering and later window changes shift it. Off-screen
+    /// windows rank behind on-screen ones, and with [`WindowOrder::Unsorted`]
+    /// the index follows ScreenCaptureKit's order rather than the stacking order.
+    pub fn stacking_index(&self) -> usize {
+        self.stacking_index
+    }
+
+    /// Get the ID of the display the window is on
+    ///
+    /// For windows spanning several displays this is the one containing the
+    /// window's top-left corner, which is also the display capture crops from.
+    /// Matches [`crate::Monitor::id`]. Returns 0 if no display was found.
+    pub fn display_id(&self) -> u32 {
+        self.display_id
+    }
+
+    /// Get the backing scale factor of the display containing the window
+    ///
+    /// This is the ratio of physical pixels to points (2.0 on Retina displays).
+    /// The display is resolved from the window's origin, the same way capture does.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Estimate the size in bytes of a native-resolution capture of the window
+    ///
+    /// Computed from the cached geometry as `width * height * scale_factor² * 4`,
+    /// the size of an RGBA capture with [`crate::CoordinateSpace::Physical`].
+    /// The default logical capture is `scale_factor²` times smaller. Capture
+    /// briefly needs more, as the whole containing display is converted
+    /// before cropping.
+    pub fn estimated_capture_bytes(&self) -> usize {
+        capture::estimated_rgba_bytes(self.width, self.height, self.scale_factor)
+    }
+
+    /// Map a global point (in points, top-left origin) to a pixel in the captured image
+    ///
+    /// The result indexes into the image returned by [`Window::capture_image`],
+    /// which has one pixel per point, so the mapping only removes the window
+    /// origin. Returns `None` if the point lies outside the window.
+    pub fn to_local_pixel(&self, global_x: i32, global_y: i32) -> Option<(u32, u32)> {
+        let local_x = global_x.checked_sub(self.x)?;
+        let local_y = global_y.checked_sub(self.y)?;
+        if local_x < 0 || local_y < 0 || local_x as u32 >= self.width || local_y as u32 >= self.height {
+            return None;
+        }
+        Some((local_x as u32, local_y as u32))
+    }
+
     /// Capture an image of the window
     ///
-    /// Returns an RGBA image of the window contents.
+    /// Returns an RGBA image of the window contents. The image is in logical
+    /// pixels: one pixel per point, so its size matches [`Window::width`] and
+    /// [`Window::height`] regardless of [`Window::scale_factor`]. For native
+    /// pixels, use [`Window::capture_image_with`] with
+    /// [`crate::CoordinateSpace::Physical`].
+    ///
+    /// Parts of the window off its display cannot be captured. With the
+    /// `xcap-compat` feature (on by default) they are left transparent, so the
+    /// image always covers the window's whole frame at capture time, as xcap
+    /// returns it. Without the feature they are cropped away. The same rule
+    /// applies to every method that captures the whole window, from
+    /// [`Window::capture_image_with`] to [`Window::capture_into`]. If the
+    /// window was resized since it was listed, the image has its new size.
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
-        capture::capture_window_sync(self.window_id, self.width, self.height)
+        capture::capture_window_sync(self.window_id, self.width, self.height, CaptureOptions::default())
+    }
+
+    /// Capture the window into shared memory for another process to read
+    ///
+    /// Same image as [`Window::capture_image`], written to `region` with
+    /// [`SharedRegion::write_frame`]. Fails if the region is too small.
+    pub fn capture_into_shared(&self, region: &mut SharedRegion) -> XCapResult<FrameHeader> {
+        region.write_frame(&self.capture_image()?)
+    }
+
+    /// Capture an image of the window with explicit options
+    ///
+    /// See [`CaptureOptions`] for what can be configured.
+    pub fn capture_image_with(&self, options: &CaptureOptions) -> XCapResult<RgbaImage> {
+        capture::capture_window_sync(self.window_id, self.width, self.height, options.clone())
+    }
+
+    /// Capture the window and refresh its cached geometry
+    ///
+    /// The position and size returned by [`Window::x`], [`Window::width`] and
+    /// friends are read once by [`Window::all`] and go stale when the window
+    /// moves. Capture itself always crops to the window's frame at capture
+    /// time; this method also re-reads that frame from the same content fetch
+    /// and updates the cached fields, so they describe the returned image.
+    pub fn capture_image_live(&mut self) -> XCapResult<RgbaImage> {
+        let content = Content::current()?;
+        let options = ListOptions {
+            order: WindowOrder::Unsorted,
+            ..ListOptions::unfiltered()
+        };
+        let current = content
+            .windows_with_options(&options)?
+            .into_iter()
+            .find(|w| w.window_id == self.window_id)
+            .ok_or_else(|| XCapError::window_not_found(self.window_id))?;
+
+        let image = content.capture_window(&current)?;
+        *self = current;
+        Ok(image)
+    }
+
+    /// Wait until the window's application is frontmost, then capture the window
+    ///
+    /// Waits on NSWorkspace activation notifications rather than polling, and
+    /// captures right away if the application is already frontmost. Fails with
+    /// [`crate::ErrorKind::Timeout`] if `timeout` elapses first; `None` waits
+    /// indefinitely.
+    ///
+    /// The notifications are delivered through the main run loop, so this only
+    /// works in processes that run one (any AppKit app). In a plain command-line
+    /// tool it waits until the timeout.
+    pub fn capture_on_focus(&self, timeout: Option<Duration>) -> XCapResult<RgbaImage> {
+        focus::wait_until_frontmost(self.pid, timeout)?;
+        self.capture_image()
+    }
+
+    /// Capture an image of the window unless `token` is cancelled first
+    ///
+    /// See [`crate::Monitor::capture_image_cancellable`]. The image is the same
+    /// as from [`Window::capture_image`].
+    pub fn capture_image_cancellable(&self, token: CancellationToken) -> XCapResult<RgbaImage> {
+        capture::capture_window_cancellable_sync(self.window_id, self.width, self.height, token)
+    }
+
+    /// Capture an image of the window without an alpha channel
+    ///
+    /// See [`crate::Monitor::capture_rgb`].
+    pub fn capture_rgb(&self) -> XCapResult<RgbImage> {
+        capture::capture_window_rgb_sync(self.window_id)
+    }
+
+    /// Get the owning application's icon as a `size` x `size` image
+    ///
+    /// Reads `NSRunningApplication.icon` for the window's process, so it works
+    /// for any app that is still running, with or without a bundle. The image
+    /// has straight (not premultiplied) alpha. Does not need screen recording
+    /// permission.
+    pub fn app_icon(&self, size: u32) -> XCapResult<RgbaImage> {
+        icon::app_icon(self.pid, size)
+    }
+
+    /// Capture a sub-region of the window
+    ///
+    /// `x`, `y`, `width` and `height` are in points relative to the window's
+    /// top-left corner, e.g. to grab only a toolbar or a video area. The rect
+    /// must lie within the window. Like [`Window::capture_image`], the image has
+    /// one pixel per point.
+    pub fn capture_rect(&self, x: u32, y: u32, width: u32, height: u32) -> XCapResult<RgbaImage> {
+        validate_rect(x, y, width, height, self.width, self.height)?;
+        capture::capture_window_rect_sync(self.window_id, x, y, width, height)
+    }
+
+    /// Capture the window, falling back to its last snapshot when minimized
+    ///
+    /// On-screen windows are captured with [`Window::capture_image`]. For
+    /// windows that are not on screen, ScreenCaptureKit has nothing to render,
+    /// so this returns the window server's cached image instead (the one shown
+    /// by the Dock and Mission Control), which may be stale.
+    pub fn capture_snapshot(&self) -> XCapResult<RgbaImage> {
+        if self.is_on_screen {
+            self.capture_image()
+        } else {
+            debug!("Window {} is not on screen, using cached snapshot", self.window_id);
+            capture::capture_window_snapshot_sync(self.window_id)
+        }
+    }
+
+    /// Capture any window known to the window server by its `CGWindowID`
+    ///
+    /// Some windows, such as screensaver and login overlays, appear in
+    /// `CGWindowListCopyWindowInfo` but never in ScreenCaptureKit's window
+    /// list, so [`Window::all`] cannot return them. This goes straight to
+    /// `CGWindowListCreateImage` for the id, bypassing ScreenCaptureKit. The
+    /// image is at the window's nominal resolution (one pixel per point).
+    ///
+    /// `CGWindowListCreateImage` is deprecated since macOS 14; prefer
+    /// [`Window::capture_image`] for windows ScreenCaptureKit can see.
+    pub fn capture_cg_window(window_id: u32) -> XCapResult<RgbaImage> {
+        capture::capture_window_snapshot_sync(window_id)
+    }
+
+    /// Capture the window as a CoreGraphics image
+    ///
+    /// See [`crate::Monitor::capture_cg_image`]. ScreenCaptureKit crops to the
+    /// window bounds directly, so no pixels are copied. Requires macOS 14.0 or later.
+    pub fn capture_cg_image(&self) -> XCapResult<CgImage> {
+        capture::capture_window_cg_image_sync(self.window_id)
+    }
+
+    /// Capture the window once and downscale it to several sizes
+    ///
+    /// See [`crate::Monitor::capture_pyramid`].
+    pub fn capture_pyramid(&self, sizes: &[u32]) -> XCapResult<Vec<RgbaImage>> {
+        capture::build_pyramid(self.capture_image()?, sizes)
+    }
+
+    /// Capture an image of the window only if it changed since the last call
+    ///
+    /// `last_hash` holds the hash of the previous frame and is updated on every
+    /// successful capture. Returns `None` when the window contents are identical,
+    /// in which case the RGBA conversion is skipped entirely. Start with `None`
+    /// to always get the first frame.
+    pub fn capture_if_changed(&self, last_hash: &mut Option<u64>) -> XCapResult<Option<RgbaImage>> {
+        let (hash, image) = capture::capture_window_if_changed_sync(self.window_id, *last_hash)?;
+        *last_hash = Some(hash);
+        Ok(image)
+    }
+
+    /// Capture an image of the window together with its perceptual hash
+    ///
+    /// The image is the same as from [`Window::capture_image`]; the hash is
+    /// the one described in [`crate::Monitor::capture_phash`], computed over
+    /// the window's visible pixels while they are converted.
+    pub fn capture_phash(&self) -> XCapResult<(RgbaImage, u64)> {
+        capture::capture_window_phash_sync(self.window_id)
+    }
+
+    /// Hash a tiny downscaled capture of the window
+    ///
+    /// Much cheaper than a full capture: ScreenCaptureKit scales the window
+    /// down to at most 64 pixels on a side on the GPU and only that is read
+    /// back. Compare hashes across calls to decide whether expensive work such
+    /// as OCR is needed. The hash is unrelated to the one used by
+    /// [`Window::capture_if_changed`], and tiny changes (a blinking caret) may
+    /// not change it.
+    pub fn probe_hash(&self) -> XCapResult<u64> {
+        capture::window_probe_hash_sync(self.window_id)
+    }
+
+    /// Check whether the window's contents changed since a previous [`Window::probe_hash`]
+    ///
+    /// Useful to gate OCR in a capture loop:
+    ///
+    /// ```rust,no_run
+    /// # let window = sck_rs::Window::all().unwrap().remove(0);
+    /// let mut last = window.probe_hash().unwrap();
+    /// loop {
+    ///     if window.content_changed_since(last).unwrap() {
+    ///         let image = window.capture_image().unwrap();
+    ///         // ... run OCR on image ...
+    ///         last = window.probe_hash().unwrap();
+    ///     }
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    /// }
+    /// ```
+    pub fn content_changed_since(&self, prev_frame_hash: u64) -> XCapResult<bool> {
+        Ok(self.probe_hash()? != prev_frame_hash)
+    }
+
+    /// Capture the window into a caller-provided buffer
+    ///
+    /// Writes tightly packed RGBA bytes into `buf`, resizing it as needed and
+    /// reusing its allocation, and returns the image width and height. Useful in
+    /// capture loops that recycle one buffer across frames.
+    pub fn capture_into(&self, buf: &mut Vec<u8>) -> XCapResult<(u32, u32)> {
+        let (returned, result) = capture::capture_window_into_sync(self.window_id, std::mem::take(buf))?;
+        *buf = returned;
+        result
     }
 }
 
@@ -240,6 +1096,9 @@ mod tests {
             is_on_screen: true,
             is_app_active: true,
             window_layer: 0,
+            display_id: 1,
+            scale_factor: 1.0,
+            stacking_index: 0,
         };
 
         assert_eq!(window.id().unwrap(), 123);
@@ -254,6 +1113,8 @@ mod tests {
         assert!(!window.is_minimized().unwrap());
         assert!(window.is_on_screen());
         assert!(window.is_focused().unwrap());
+        assert_eq!(window.display_id(), 1);
+        assert_eq!(window.scale_factor(), 1.0);
     }
 
     #[test]
@@ -270,6 +1131,9 @@ mod tests {
             is_on_screen: true,
             is_app_active: true,  // App is frontmost...
             window_layer: 3isize, // ...but window is an overlay
+            display_id: 1,
+            scale_factor: 1.0,
+            stacking_index: 0,
         };
 
         // Should NOT be considered focused because layer > 0
@@ -290,6 +1154,9 @@ mod tests {
             is_on_screen: true,
             is_app_active: false, // Not the frontmost app
             window_layer: 0,     // Normal window level
+            display_id: 1,
+            scale_factor: 2.0,
+            stacking_index: 0,
         };
 
         assert!(!window.is_focused().unwrap());
@@ -309,12 +1176,240 @@ mod tests {
             is_on_screen: false,
             is_app_active: false,
             window_layer: 0,
+            display_id: 1,
+            scale_factor: 1.0,
+            stacking_index: 0,
         };
 
         assert!(window.is_minimized().unwrap());
         assert!(!window.is_on_screen());
     }
 
+    #[test]
+    fn test_to_local_pixel() {
+        let window = Window {
+            window_id: 1,
+            app_name: "App".to_string(),
+            title: "Title".to_string(),
+            pid: 1,
+            x: -100,
+            y: 50,
+            width: 200,
+            height: 100,
+            is_on_screen: true,
+            is_app_active: false,
+            window_layer: 0,
+            display_id: 1,
+            scale_factor: 2.0,
+            stacking_index: 0,
+        };
+
+        assert_eq!(window.to_local_pixel(-100, 50), Some((0, 0)));
+        assert_eq!(window.to_local_pixel(99, 149), Some((199, 99)));
+        assert_eq!(window.to_local_pixel(100, 50), None);
+        assert_eq!(window.to_local_pixel(0, 49), None);
+    }
+
+    fn window_with_id(window_id: u32, window_layer: isize) -> Window {
+        Window {
+            window_id,
+            app_name: "App".to_string(),
+            title: String::new(),
+            pid: 1,
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+            is_on_screen: true,
+            is_app_active: false,
+            window_layer,
+            display_id: 1,
+            scale_factor: 1.0,
+            stacking_index: 0,
+        }
+    }
+
+    #[test]
+    fn test_equality_is_by_id() {
+        let mut moved = window_with_id(7, 0);
+        moved.x += 100;
+        moved.title = "Renamed".to_string();
+        assert_eq!(moved, window_with_id(7, 0));
+        assert_ne!(window_with_id(7, 0), window_with_id(8, 0));
+
+        let set: std::collections::HashSet<_> = [window_with_id(7, 0), moved].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_window_diff() {
+        let mut kept = window_with_id(1, 0);
+        let removed = window_with_id(2, 0);
+        let previous = vec![kept.clone(), removed.clone(), window_with_id(3, 0)];
+
+        kept.title = "New title".to_string();
+        let mut moved = window_with_id(3, 0);
+        moved.x = 50;
+        let current = vec![kept, moved, window_with_id(4, 0)];
+
+        let diff = WindowDiff::between(&previous, current);
+        let ids = |windows: &[Window]| windows.iter().map(|w| w.window_id).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.added), vec![4]);
+        assert_eq!(ids(&diff.removed), vec![2]);
+        assert_eq!(ids(&diff.title_changed), vec![1]);
+        assert_eq!(ids(&diff.moved), vec![3]);
+        assert_eq!(diff.current.len(), 3);
+        assert!(!diff.is_empty());
+
+        assert!(WindowDiff::between(&diff.current, diff.current.clone()).is_empty());
+
+        let events = diff.events();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], WindowEvent::Opened(w) if w.window_id == 4));
+        assert!(matches!(events[1], WindowEvent::Closed(2)));
+        assert!(matches!(events[2], WindowEvent::Moved(3, bounds) if bounds.x == 50));
+    }
+
+    #[test]
+    fn test_ax_info_matches() {
+        let mut window = window_with_id(7, 0);
+        window.title = "Inbox".to_string();
+        let info = AxWindowInfo {
+            pid: 1,
+            window_id: None,
+            title: Some("Inbox".to_string()),
+            frame: Some((0.2, 0.0, 100.0, 99.6)),
+        };
+        assert!(ax_info_matches(&window, &info));
+
+        // A known window id overrides the geometry
+        assert!(!ax_info_matches(&window, &AxWindowInfo { window_id: Some(8), ..info.clone() }));
+        assert!(ax_info_matches(&window, &AxWindowInfo { window_id: Some(7), frame: None, ..info.clone() }));
+
+        assert!(!ax_info_matches(&window, &AxWindowInfo { pid: 2, ..info.clone() }));
+        assert!(!ax_info_matches(&window, &AxWindowInfo { frame: Some((50.0, 0.0, 100.0, 100.0)), ..info.clone() }));
+        assert!(!ax_info_matches(&window, &AxWindowInfo { title: None, ..info }));
+    }
+
+    #[test]
+    fn test_resolve_minimized() {
+        assert!(!resolve_minimized(true, None, false));
+        // Off screen on another Space, known not to be minimized
+        assert!(!resolve_minimized(false, Some(false), false));
+        assert!(resolve_minimized(false, Some(true), false));
+        // Without accessibility, hidden apps' windows are not minimized
+        assert!(resolve_minimized(false, None, false));
+        assert!(!resolve_minimized(false, None, true));
+    }
+
+    #[test]
+    fn test_pick_focused() {
+        let mut overlay = window_with_id(1, 25);
+        overlay.is_app_active = true;
+        let background = window_with_id(2, 0);
+        let mut key = window_with_id(3, 0);
+        key.is_app_active = true;
+        let mut other = window_with_id(4, 0);
+        other.is_app_active = true;
+
+        let focused = pick_focused(vec![overlay.clone(), background.clone(), key, other]);
+        assert_eq!(focused.map(|w| w.window_id), Some(3));
+        assert!(pick_focused(vec![overlay, background]).is_none());
+    }
+
+    #[test]
+    fn test_validate_rect() {
+        assert!(validate_rect(0, 0, 800, 600, 800, 600).is_ok());
+        assert!(validate_rect(10, 560, 200, 40, 800, 600).is_ok());
+        assert!(validate_rect(0, 0, 0, 10, 800, 600).is_err());
+        assert!(validate_rect(700, 0, 101, 10, 800, 600).is_err());
+        assert!(validate_rect(0, u32::MAX, 10, 10, 800, 600).is_err());
+    }
+
+    #[test]
+    fn test_sort_by_stacking() {
+        let mut windows = vec![
+            window_with_id(1, 0),
+            window_with_id(2, 0),
+            window_with_id(3, 0),
+            window_with_id(4, 25),
+        ];
+        // 3 is frontmost, then 1; 2 and 4 are off screen
+        sort_by_stacking(&mut windows, &[3, 1]);
+        let ids: Vec<u32> = windows.iter().map(|w| w.window_id).collect();
+        assert_eq!(ids, vec![3, 1, 4, 2]);
+    }
+
+    #[test]
+    fn test_assign_stacking_indices() {
+        let mut windows = vec![
+            window_with_id(1, 25),
+            window_with_id(2, 0),
+            window_with_id(3, 0),
+            window_with_id(4, 25),
+            window_with_id(5, 0),
+        ];
+        assign_stacking_indices(&mut windows);
+        let indices: Vec<(u32, usize)> = windows.iter().map(|w| (w.window_id, w.stacking_index)).collect();
+        assert_eq!(indices, vec![(1, 0), (2, 0), (3, 1), (4, 1), (5, 2)]);
+    }
+
+    #[test]
+    fn test_list_options_alpha() {
+        let options = ListOptions::default();
+        assert!(!options.filters_alpha());
+        assert!(options.accepts_alpha(0.0));
+
+        let options = ListOptions { skip_transparent: true, ..Default::default() };
+        assert!(options.filters_alpha());
+        assert!(!options.accepts_alpha(0.0));
+        assert!(options.accepts_alpha(0.05));
+
+        let options = ListOptions { min_alpha: 0.1, ..Default::default() };
+        assert!(options.filters_alpha());
+        assert!(!options.accepts_alpha(0.05));
+        assert!(options.accepts_alpha(0.1));
+        assert!(options.accepts_alpha(1.0));
+    }
+
+    #[test]
+    fn test_list_options_default_skips_tiny() {
+        let options = ListOptions::default();
+        assert!(!options.accepts(9, 100, true));
+        assert!(!options.accepts(100, 9, true));
+        assert!(options.accepts(10, 10, true));
+        assert!(options.accepts(10, 10, false));
+    }
+
+    #[test]
+    fn test_list_options_onscreen_only() {
+        let options = ListOptions {
+            include_offscreen: false,
+            ..Default::default()
+        };
+        assert!(options.accepts(100, 100, true));
+        assert!(!options.accepts(100, 100, false));
+    }
+
+    #[test]
+    fn test_list_options_unfiltered() {
+        let options = ListOptions::unfiltered();
+        assert!(options.accepts(0, 0, false));
+        assert!(options.accepts_identity(25, "", ""));
+    }
+
+    #[test]
+    fn test_list_options_capturable() {
+        let options = ListOptions::capturable();
+        assert!(options.accepts(100, 100, true));
+        assert!(!options.accepts(100, 100, false));
+        assert!(!options.accepts(99, 500, true));
+        assert!(options.accepts_identity(0, "", "Safari"));
+        assert!(options.accepts_identity(0, "Untitled", ""));
+        assert!(!options.accepts_identity(0, "", ""));
+        assert!(!options.accepts_identity(3, "Panel", "Safari"));
+    }
+
     #[test]
     fn test_window_all() {
         // This test verifies the API works
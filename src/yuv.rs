@@ -0,0 +1,96 @@
+//! Biplanar YUV 4:2:0 (NV12) frames for video encoders
+
+/// A frame in NV12 layout, returned by [`crate::Monitor::capture_yuv`]
+///
+/// The luma plane holds one byte per pixel. The chroma plane holds
+/// interleaved Cb/Cr byte pairs for every 2x2 block of pixels, so it has half
+/// as many rows and each row is `2 * ceil(width / 2)` bytes of samples. Rows of
+/// both planes may be padded, so index them with the strides. Values use the
+/// BT.709 video range (Y in 16-235, Cb/Cr in 16-240), which VideoToolbox and
+/// most H.264/HEVC encoders accept as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YuvFrame {
+    width: u32,
+    height: u32,
+    y: Vec<u8>,
+    y_stride: usize,
+    uv: Vec<u8>,
+    uv_stride: usize,
+}
+
+impl YuvFrame {
+    /// Build a frame from its planes, checking they are large enough
+    pub(crate) fn new(width: u32, height: u32, y: Vec<u8>, y_stride: usize, uv: Vec<u8>, uv_stride: usize) -> Option<Self> {
+        let (chroma_width, chroma_height) = chroma_size(width, height);
+        let fits = y_stride >= width as usize
+            && y.len() >= y_stride * height as usize
+            && uv_stride >= chroma_width as usize * 2
+            && uv.len() >= uv_stride * chroma_height as usize;
+        fits.then_some(YuvFrame {
+            width,
+            height,
+            y,
+            y_stride,
+            uv,
+            uv_stride,
+        })
+    }
+
+    /// Get the width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the luma (Y) plane
+    pub fn y_plane(&self) -> &[u8] {
+        &self.y
+    }
+
+    /// Get the number of bytes between the starts of two rows of the Y plane
+    pub fn y_stride(&self) -> usize {
+        self.y_stride
+    }
+
+    /// Get the interleaved chroma (CbCr) plane
+    pub fn uv_plane(&self) -> &[u8] {
+        &self.uv
+    }
+
+    /// Get the number of bytes between the starts of two rows of the UV plane
+    pub fn uv_stride(&self) -> usize {
+        self.uv_stride
+    }
+}
+
+/// Get the number of chroma samples per row and column for a frame size
+pub(crate) fn chroma_size(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(2), height.div_ceil(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chroma_size() {
+        assert_eq!(chroma_size(1920, 1080), (960, 540));
+        assert_eq!(chroma_size(3, 5), (2, 3));
+    }
+
+    #[test]
+    fn test_new_checks_plane_sizes() {
+        // 4x2 with 8-byte rows: two luma rows, one chroma row
+        let frame = YuvFrame::new(4, 2, vec![16; 16], 8, vec![128; 8], 8).unwrap();
+        assert_eq!(frame.y_stride(), 8);
+        assert_eq!(frame.uv_plane().len(), 8);
+
+        assert!(YuvFrame::new(4, 2, vec![16; 15], 8, vec![128; 8], 8).is_none());
+        assert!(YuvFrame::new(4, 2, vec![16; 16], 8, vec![128; 8], 3).is_none());
+        assert!(YuvFrame::new(4, 2, vec![16; 6], 3, vec![128; 8], 8).is_none());
+    }
+}